@@ -0,0 +1,59 @@
+// Modules From Crates.io //
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+// Local Modules Use //
+use chip8emu::{ChipSystem, Speed};
+
+/// A busy loop of arithmetic, a skip and a memory transfer, the
+/// kind of code batch runs spend most of their time in
+const LOOP_ROM: [u8; 16] = [
+    0xA3, 0x00, // LD I, 0x300
+    0x70, 0x01, // ADD V0, 0x01
+    0x81, 0x04, // ADD V1, V0
+    0x82, 0x13, // XOR V2, V1
+    0x33, 0x00, // SE V3, 0x00
+    0x73, 0x01, // ADD V3, 0x01
+    0xF2, 0x65, // LD V2, [I]
+    0x12, 0x00  // JP 0x200
+];
+
+/// Number of frames per benchmark iteration
+const FRAMES: usize = 60;
+/// Instructions per frame, high enough that frame overhead doesn't
+/// hide the cost of fetching and decoding
+const SPEED: u32 = 1000;
+
+/// Create a system running `LOOP_ROM`, the system is reused between
+/// iterations so compiled blocks and cached instructions stay warm
+fn loop_system(cache: bool) -> ChipSystem {
+    let mut system = ChipSystem::init();
+    system.ram.set_cache_enabled(cache);
+    system.set_speed(Speed::InstructionsPerFrame(SPEED));
+    system.load_rom(LOOP_ROM.to_vec()).unwrap();
+    system
+}
+
+fn run_frames(system: &mut ChipSystem) -> u16 {
+    for _ in 0..FRAMES {
+        system.run_frame().unwrap();
+    }
+    system.registers.get_pc()
+}
+
+fn bench_step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("run_frames");
+    let mut system = loop_system(true);
+    group.bench_function("cache", |b| b.iter(|| black_box(run_frames(&mut system))));
+    let mut system = loop_system(false);
+    group.bench_function("no_cache", |b| b.iter(|| black_box(run_frames(&mut system))));
+    #[cfg(feature = "jit")]
+    {
+        let mut system = loop_system(true);
+        system.set_jit_enabled(true);
+        group.bench_function("jit", |b| b.iter(|| black_box(run_frames(&mut system))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_step);
+criterion_main!(benches);
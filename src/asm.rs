@@ -0,0 +1,783 @@
+// Standard Library Modules //
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Local Modules Use //
+use crate::instruction::Instruction;
+use crate::memory::PROGRAM_START;
+
+/// Deepest chain of `INCLUDE`s allowed, catches files including themselves
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Error from assembling a program, with the location it was found at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// File the error is in, `<input>` for source passed as a string
+    pub file: String,
+    /// Line number, starting at 1 (0 if the error isn't on a line)
+    pub line: usize,
+    /// Column number, starting at 1
+    pub column: usize,
+    /// What went wrong
+    pub message: String
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "{}: {}", self.file, self.message),
+            line => write!(f, "{}:{}:{}: {}", self.file, line, self.column, self.message)
+        }
+    }
+}
+
+impl error::Error for AsmError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+/// Result type used while assembling
+pub type AsmResult<T> = Result<T, AsmError>;
+
+/// An assembled program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    /// Address the first byte of `rom` loads at
+    pub origin: u16,
+    /// The ROM image
+    pub rom: Vec<u8>,
+    /// Address of every label
+    pub labels: BTreeMap<String, u16>
+}
+
+impl Assembly {
+    /// Symbol map listing, one `address label` line per label in
+    /// address order
+    pub fn symbol_map(&self) -> String {
+        let mut labels: Vec<(&String, &u16)> = self.labels.iter().collect();
+        labels.sort_by_key(|&(name, addr)| (*addr, name.clone()));
+        labels.iter().map(|(name, addr)| format!("{:04X} {}\n", addr, name)).collect()
+    }
+}
+
+/// Assemble source with the default settings, returns the ROM image
+///
+/// # Arguments
+///
+/// * `source` - assembly source
+pub fn assemble(source: &str) -> AsmResult<Vec<u8>> {
+    Assembler::init().assemble(source).map(|assembly| assembly.rom)
+}
+
+/// Where a token or statement came from
+#[derive(Debug, Clone, Copy)]
+struct Loc {
+    file: usize,
+    line: usize,
+    column: usize
+}
+
+/// A lexical token
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Num(i64),
+    Str(String),
+    Punct(&'static str)
+}
+
+/// A token with the column it starts at
+#[derive(Debug, Clone)]
+struct Token {
+    tok: Tok,
+    column: usize
+}
+
+/// An expression, evaluated once every label is known
+#[derive(Debug, Clone)]
+enum Expr {
+    Num(i64),
+    Sym(String, usize),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>, usize)
+}
+
+/// An instruction operand
+#[derive(Debug, Clone)]
+enum Operand {
+    Reg(u8),
+    I,
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    Hf,
+    R,
+    Long(Expr, usize),
+    Value(Expr, usize)
+}
+
+/// An item of a `DB` directive
+#[derive(Debug, Clone)]
+enum Data {
+    Value(Expr, usize),
+    Str(String)
+}
+
+/// What a statement emits
+#[derive(Debug, Clone)]
+enum Body {
+    Instruction(String, Vec<Operand>),
+    Bytes(Vec<Data>),
+    Words(Vec<(Expr, usize)>)
+}
+
+/// A statement placed at an address by the first pass
+#[derive(Debug, Clone)]
+struct Statement {
+    addr: u32,
+    loc: Loc,
+    body: Body
+}
+
+/// State of a single assembly run
+struct Program {
+    /// Names of the files read, `Loc::file` indexes this
+    files: Vec<String>,
+    statements: Vec<Statement>,
+    /// Value of every symbol, labels and constants
+    symbols: HashMap<String, i64>,
+    labels: BTreeMap<String, u16>,
+    /// Address of the next statement
+    addr: u32
+}
+
+/// A two pass assembler for classic CHIP-8 mnemonics
+///
+/// The syntax matches the `Display` output of `Instruction` (e.g.
+/// `LD V3, 0x10`, `DRW V0, V1, 5`, `LD [I], VF`), case insensitive.
+/// On top of the instructions it supports:
+///
+/// * `name:` labels and `name EQU expr` (or `name = expr`) constants
+/// * `DB` bytes and strings, `DW` big endian words
+/// * `ORG addr` to move the output address
+/// * `INCLUDE "file"` relative to the including file
+/// * expressions with `+ - * / % & | ^ ~ << >>` and parentheses over
+///   decimal, `0x`/`$` hex, `0b` binary and `'c'` character numbers
+/// * `;` comments
+///
+/// `I`, `DT`, `ST`, `K`, `F`, `B`, `HF`, `R`, `LONG` and `V0`-`VF` are
+/// reserved and can't be used as symbol names.
+pub struct Assembler {
+    /// Address the ROM image starts at
+    origin: u16,
+    /// Directory includes in string source are relative to
+    include_dir: PathBuf
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Assembler::init()
+    }
+}
+
+impl Assembler {
+    /// Create an assembler for programs loaded at the program start
+    /// location, with includes relative to the working directory
+    pub fn init() -> Self {
+        Assembler {
+            origin: PROGRAM_START,
+            include_dir: PathBuf::from(".")
+        }
+    }
+
+    /// Set the address the ROM image starts at
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - load address of the first byte
+    pub fn set_origin(&mut self, origin: u16) {
+        self.origin = origin;
+    }
+
+    /// Set the directory `INCLUDE`s in string source are relative to
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - directory to resolve includes from
+    pub fn set_include_dir<P: AsRef<Path>>(&mut self, dir: P) {
+        self.include_dir = dir.as_ref().to_path_buf();
+    }
+
+    /// Assemble source held in a string
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - assembly source
+    pub fn assemble(&self, source: &str) -> AsmResult<Assembly> {
+        let mut program = self.program();
+        program.read_source(String::from("<input>"), source, &self.include_dir, 0)?;
+        program.finish(self.origin)
+    }
+
+    /// Assemble a source file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path of the source file
+    pub fn assemble_file<P: AsRef<Path>>(&self, path: P) -> AsmResult<Assembly> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let source = fs::read_to_string(path).map_err(|e| AsmError {
+            file: name.clone(), line: 0, column: 0, message: format!("can't read file: {}", e)
+        })?;
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let mut program = self.program();
+        program.read_source(name, &source, dir, 0)?;
+        program.finish(self.origin)
+    }
+
+    /// Fresh state for a run
+    fn program(&self) -> Program {
+        Program {
+            files: Vec::new(),
+            statements: Vec::new(),
+            symbols: HashMap::new(),
+            labels: BTreeMap::new(),
+            addr: self.origin as u32
+        }
+    }
+}
+
+/// Returns true for names that can't be symbols
+fn is_reserved(name: &str) -> bool {
+    let upper = name.to_uppercase();
+    matches!(upper.as_str(), "I" | "DT" | "ST" | "K" | "F" | "B" | "HF" | "R" | "LONG") || register(&upper).is_some()
+}
+
+/// Register index of `V0`-`VF`
+fn register(name: &str) -> Option<u8> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('v'), Some(x), None) | (Some('V'), Some(x), None) => x.to_digit(16).map(|x| x as u8),
+        _ => None
+    }
+}
+
+/// Split a line into tokens, stopping at a comment
+fn lex(text: &str, err: &dyn Fn(usize, String) -> AsmError) -> AsmResult<Vec<Token>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut n = 0;
+    while n < chars.len() {
+        let c = chars[n];
+        let column = n + 1;
+        let start = n;
+        let tok = match c {
+            ';' => break,
+            _ if c.is_whitespace() => {
+                n += 1;
+                continue;
+            },
+            '0'..='9' => {
+                while n < chars.len() && (chars[n].is_ascii_alphanumeric() || chars[n] == '_') {
+                    n += 1;
+                }
+                let word: String = chars[start..n].iter().filter(|&&c| c != '_').collect();
+                let lower = word.to_lowercase();
+                let parsed = match (lower.strip_prefix("0x"), lower.strip_prefix("0b")) {
+                    (Some(hex), _) => i64::from_str_radix(hex, 16),
+                    (_, Some(bin)) => i64::from_str_radix(bin, 2),
+                    _ => lower.parse::<i64>()
+                };
+                Tok::Num(parsed.map_err(|_| err(column, format!("invalid number `{}`", word)))?)
+            },
+            '$' if chars.get(n + 1).is_some_and(|c| c.is_ascii_hexdigit()) => {
+                n += 1;
+                while n < chars.len() && chars[n].is_ascii_alphanumeric() {
+                    n += 1;
+                }
+                let word: String = chars[start + 1..n].iter().collect();
+                Tok::Num(i64::from_str_radix(&word, 16).map_err(|_| err(column, format!("invalid number `${}`", word)))?)
+            },
+            'a'..='z' | 'A'..='Z' | '_' | '.' => {
+                while n < chars.len() && (chars[n].is_ascii_alphanumeric() || chars[n] == '_' || chars[n] == '.') {
+                    n += 1;
+                }
+                Tok::Ident(chars[start..n].iter().collect())
+            },
+            '"' => {
+                n += 1;
+                while n < chars.len() && chars[n] != '"' {
+                    n += 1;
+                }
+                if n == chars.len() {
+                    return Err(err(column, String::from("unterminated string")));
+                }
+                n += 1;
+                Tok::Str(chars[start + 1..n - 1].iter().collect())
+            },
+            '\'' => match (chars.get(n + 1), chars.get(n + 2)) {
+                (Some(&value), Some('\'')) => {
+                    n += 3;
+                    Tok::Num(value as i64)
+                },
+                _ => return Err(err(column, String::from("invalid character literal")))
+            },
+            _ => {
+                let two: String = chars[n..(n + 2).min(chars.len())].iter().collect();
+                let punct = match two.as_str() {
+                    "<<" => "<<",
+                    ">>" => ">>",
+                    _ => match c {
+                        ',' => ",", ':' => ":", '[' => "[", ']' => "]", '(' => "(", ')' => ")",
+                        '+' => "+", '-' => "-", '*' => "*", '/' => "/", '%' => "%",
+                        '&' => "&", '|' => "|", '^' => "^", '~' => "~", '=' => "=",
+                        _ => return Err(err(column, format!("unexpected character `{}`", c)))
+                    }
+                };
+                n += punct.len();
+                Tok::Punct(punct)
+            }
+        };
+        tokens.push(Token {tok, column});
+    }
+    Ok(tokens)
+}
+
+/// Binary operators from lowest to highest precedence
+const PRECEDENCE: [&[&str]; 6] = [&["|"], &["^"], &["&"], &["<<", ">>"], &["+", "-"], &["*", "/", "%"]];
+
+/// Recursive descent expression parser over the tokens of one operand
+struct ExprParser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    /// Column to report when the operand ends early
+    end_column: usize
+}
+
+impl<'a> ExprParser<'a> {
+    /// Column of the current token
+    fn column(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end_column, |token| token.column)
+    }
+
+    /// Parse operators of precedence `level` and above
+    fn binary(&mut self, level: usize, err: &dyn Fn(usize, String) -> AsmError) -> AsmResult<Expr> {
+        if level == PRECEDENCE.len() {
+            return self.unary(err);
+        }
+        let mut lhs = self.binary(level + 1, err)?;
+        while let Some(Token {tok: Tok::Punct(op), column}) = self.tokens.get(self.pos) {
+            if !PRECEDENCE[level].contains(op) {
+                break;
+            }
+            let column = *column;
+            self.pos += 1;
+            let rhs = self.binary(level + 1, err)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs), column);
+        }
+        Ok(lhs)
+    }
+
+    /// Parse a unary operator or a primary
+    fn unary(&mut self, err: &dyn Fn(usize, String) -> AsmError) -> AsmResult<Expr> {
+        let column = self.column();
+        let token = self.tokens.get(self.pos).map(|token| token.tok.clone());
+        self.pos += 1;
+        match token {
+            Some(Tok::Num(value)) => Ok(Expr::Num(value)),
+            Some(Tok::Ident(name)) if !is_reserved(&name) => Ok(Expr::Sym(name, column)),
+            Some(Tok::Punct("-")) => Ok(Expr::Neg(Box::new(self.unary(err)?))),
+            Some(Tok::Punct("+")) => self.unary(err),
+            Some(Tok::Punct("~")) => Ok(Expr::Not(Box::new(self.unary(err)?))),
+            Some(Tok::Punct("(")) => {
+                let inner = self.binary(0, err)?;
+                match self.tokens.get(self.pos) {
+                    Some(Token {tok: Tok::Punct(")"), ..}) => {
+                        self.pos += 1;
+                        Ok(inner)
+                    },
+                    _ => Err(err(self.column(), String::from("expected `)`")))
+                }
+            },
+            Some(Tok::Ident(name)) => Err(err(column, format!("`{}` is reserved and can't be used in an expression", name))),
+            Some(_) => Err(err(column, String::from("expected a value"))),
+            None => Err(err(column, String::from("missing value")))
+        }
+    }
+}
+
+/// Parse a complete expression from an operand's tokens
+fn parse_expr(tokens: &[Token], end_column: usize, err: &dyn Fn(usize, String) -> AsmError) -> AsmResult<Expr> {
+    let mut parser = ExprParser {tokens, pos: 0, end_column};
+    let expr = parser.binary(0, err)?;
+    match tokens.get(parser.pos) {
+        Some(token) => Err(err(token.column, String::from("unexpected token after expression"))),
+        None => Ok(expr)
+    }
+}
+
+/// Split tokens at commas outside of parentheses and brackets
+fn split_operands(tokens: &[Token]) -> Vec<&[Token]> {
+    if tokens.is_empty() {
+        return Vec::new();
+    }
+    let mut operands = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (n, token) in tokens.iter().enumerate() {
+        match token.tok {
+            Tok::Punct("(") | Tok::Punct("[") => depth += 1,
+            Tok::Punct(")") | Tok::Punct("]") => depth -= 1,
+            Tok::Punct(",") if depth == 0 => {
+                operands.push(&tokens[start..n]);
+                start = n + 1;
+            },
+            _ => {}
+        }
+    }
+    operands.push(&tokens[start..]);
+    operands
+}
+
+impl Program {
+    /// Build an error at a location
+    fn error(&self, loc: Loc, column: usize, message: String) -> AsmError {
+        AsmError {
+            file: self.files[loc.file].clone(),
+            line: loc.line,
+            column,
+            message
+        }
+    }
+
+    /// First pass over a file: define labels and constants, place
+    /// statements and read includes
+    fn read_source(&mut self, name: String, source: &str, dir: &Path, depth: usize) -> AsmResult<()> {
+        self.files.push(name);
+        let file = self.files.len() - 1;
+        for (n, text) in source.lines().enumerate() {
+            let loc = Loc {file, line: n + 1, column: 1};
+            let err = |column: usize, message: String| self.error(loc, column, message);
+            let tokens = lex(text, &err)?;
+            let end_column = text.chars().count() + 1;
+            let mut rest = &tokens[..];
+
+            // Label
+            if let [Token {tok: Tok::Ident(label), column}, Token {tok: Tok::Punct(":"), ..}, ..] = rest {
+                let addr = self.addr as u16;
+                self.define(label, addr as i64, loc, *column)?;
+                self.labels.insert(label.clone(), addr);
+                rest = &rest[2..];
+            }
+
+            let (word, column) = match rest.first() {
+                Some(Token {tok: Tok::Ident(word), column}) => (word.clone(), *column),
+                Some(token) => return Err(self.error(loc, token.column, String::from("expected an instruction or directive"))),
+                None => continue
+            };
+
+            // Constant
+            if let Some(Token {tok, ..}) = rest.get(1) {
+                let is_constant = match tok {
+                    Tok::Punct("=") => true,
+                    Tok::Ident(equ) => equ.eq_ignore_ascii_case("EQU"),
+                    _ => false
+                };
+                if is_constant {
+                    let err = |column: usize, message: String| self.error(loc, column, message);
+                    let expr = parse_expr(&rest[2..], end_column, &err)?;
+                    let value = self.eval(&expr, loc)?;
+                    self.define(&word, value, loc, column)?;
+                    continue;
+                }
+            }
+
+            let operands = split_operands(&rest[1..]);
+            let loc = Loc {column, ..loc};
+            let err = |column: usize, message: String| self.error(loc, column, message);
+            let upper = word.to_uppercase();
+            let body = match upper.as_str() {
+                "ORG" => {
+                    let expr = match operands.as_slice() {
+                        [operand] => parse_expr(operand, end_column, &err)?,
+                        _ => return Err(err(column, String::from("ORG takes one address")))
+                    };
+                    let addr = self.eval(&expr, loc)?;
+                    if !(0..=u16::MAX as i64).contains(&addr) {
+                        return Err(err(column, format!("ORG address {} is out of range", addr)));
+                    }
+                    self.addr = addr as u32;
+                    continue;
+                },
+                "INCLUDE" => {
+                    let path = match operands.as_slice() {
+                        [[Token {tok: Tok::Str(path), ..}]] => dir.join(path),
+                        _ => return Err(err(column, String::from("INCLUDE takes a quoted file name")))
+                    };
+                    if depth + 1 >= MAX_INCLUDE_DEPTH {
+                        return Err(err(column, String::from("includes are nested too deeply")));
+                    }
+                    let source = fs::read_to_string(&path)
+                        .map_err(|e| err(column, format!("can't include {}: {}", path.display(), e)))?;
+                    let include_dir = path.parent().unwrap_or(dir).to_path_buf();
+                    self.read_source(path.display().to_string(), &source, &include_dir, depth + 1)?;
+                    continue;
+                },
+                "DB" => {
+                    let mut data = Vec::new();
+                    for operand in &operands {
+                        match operand {
+                            [Token {tok: Tok::Str(text), ..}] => data.push(Data::Str(text.clone())),
+                            _ => data.push(Data::Value(parse_expr(operand, end_column, &err)?, Program::operand_column(operand, end_column)))
+                        }
+                    }
+                    Body::Bytes(data)
+                },
+                "DW" => {
+                    let mut words = Vec::new();
+                    for operand in &operands {
+                        words.push((parse_expr(operand, end_column, &err)?, Program::operand_column(operand, end_column)));
+                    }
+                    Body::Words(words)
+                },
+                _ => {
+                    let mut parsed = Vec::new();
+                    for operand in &operands {
+                        parsed.push(Program::parse_operand(operand, end_column, &err)?);
+                    }
+                    Body::Instruction(upper, parsed)
+                }
+            };
+            let size = match &body {
+                Body::Bytes(data) => data.iter().map(|item| match item {
+                    Data::Str(text) => text.len() as u32,
+                    Data::Value(..) => 1
+                }).sum(),
+                Body::Words(words) => words.len() as u32 * 2,
+                Body::Instruction(_, operands) => match operands.iter().any(|op| matches!(op, Operand::Long(..))) {
+                    true => 4,
+                    false => 2
+                }
+            };
+            if self.addr + size > u16::MAX as u32 + 1 {
+                return Err(err(column, String::from("program runs past the end of the address space")));
+            }
+            self.statements.push(Statement {addr: self.addr, loc, body});
+            self.addr += size;
+        }
+        Ok(())
+    }
+
+    /// Column an operand starts at
+    fn operand_column(operand: &[Token], end_column: usize) -> usize {
+        operand.first().map_or(end_column, |token| token.column)
+    }
+
+    /// Classify an operand as a register, a special name or a value
+    fn parse_operand(tokens: &[Token], end_column: usize, err: &dyn Fn(usize, String) -> AsmError) -> AsmResult<Operand> {
+        let column = Program::operand_column(tokens, end_column);
+        if let [Token {tok: Tok::Ident(name), ..}] = tokens {
+            if let Some(x) = register(name) {
+                return Ok(Operand::Reg(x));
+            }
+            let special = match name.to_uppercase().as_str() {
+                "I" => Some(Operand::I),
+                "DT" => Some(Operand::Dt),
+                "ST" => Some(Operand::St),
+                "K" => Some(Operand::K),
+                "F" => Some(Operand::F),
+                "B" => Some(Operand::B),
+                "HF" => Some(Operand::Hf),
+                "R" => Some(Operand::R),
+                _ => None
+            };
+            if let Some(special) = special {
+                return Ok(special);
+            }
+        }
+        match tokens {
+            [Token {tok: Tok::Punct("["), ..}, Token {tok: Tok::Ident(name), ..}, Token {tok: Tok::Punct("]"), ..}]
+                if name.eq_ignore_ascii_case("I") => Ok(Operand::IndirectI),
+            [Token {tok: Tok::Ident(long), ..}, rest @ ..] if long.eq_ignore_ascii_case("LONG") =>
+                Ok(Operand::Long(parse_expr(rest, end_column, err)?, column)),
+            [] => Err(err(column, String::from("missing operand"))),
+            _ => Ok(Operand::Value(parse_expr(tokens, end_column, err)?, column))
+        }
+    }
+
+    /// Define a symbol, names can only be defined once
+    fn define(&mut self, name: &str, value: i64, loc: Loc, column: usize) -> AsmResult<()> {
+        if is_reserved(name) {
+            return Err(self.error(loc, column, format!("`{}` is reserved and can't be a symbol", name)));
+        }
+        if self.symbols.insert(name.to_string(), value).is_some() {
+            return Err(self.error(loc, column, format!("`{}` is already defined", name)));
+        }
+        Ok(())
+    }
+
+    /// Evaluate an expression with the symbols defined so far
+    fn eval(&self, expr: &Expr, loc: Loc) -> AsmResult<i64> {
+        match expr {
+            Expr::Num(value) => Ok(*value),
+            Expr::Sym(name, column) => self.symbols.get(name).copied()
+                .ok_or_else(|| self.error(loc, *column, format!("undefined symbol `{}`", name))),
+            Expr::Neg(inner) => Ok(self.eval(inner, loc)?.wrapping_neg()),
+            Expr::Not(inner) => Ok(!self.eval(inner, loc)?),
+            Expr::Binary(op, lhs, rhs, column) => {
+                let (lhs, rhs) = (self.eval(lhs, loc)?, self.eval(rhs, loc)?);
+                let value = match *op {
+                    "|" => Some(lhs | rhs),
+                    "^" => Some(lhs ^ rhs),
+                    "&" => Some(lhs & rhs),
+                    "<<" => u32::try_from(rhs).ok().and_then(|rhs| lhs.checked_shl(rhs)),
+                    ">>" => u32::try_from(rhs).ok().and_then(|rhs| lhs.checked_shr(rhs)),
+                    "+" => lhs.checked_add(rhs),
+                    "-" => lhs.checked_sub(rhs),
+                    "*" => lhs.checked_mul(rhs),
+                    "/" => lhs.checked_div(rhs),
+                    _ => lhs.checked_rem(rhs)
+                };
+                value.ok_or_else(|| self.error(loc, *column, format!("`{}` overflows or divides by zero", op)))
+            }
+        }
+    }
+
+    /// Evaluate a value operand and check it fits in `min..=max`
+    fn value(&self, expr: &Expr, column: usize, loc: Loc, min: i64, max: i64, what: &str) -> AsmResult<i64> {
+        let value = self.eval(expr, loc)?;
+        match (min..=max).contains(&value) {
+            true => Ok(value),
+            false => Err(self.error(loc, column, format!("{} doesn't fit in {}", value, what)))
+        }
+    }
+
+    /// Encode an instruction statement
+    fn encode(&self, mnemonic: &str, operands: &[Operand], loc: Loc) -> AsmResult<Instruction> {
+        use self::Instruction::*;
+        use self::Operand::{B as OpB, F as OpF, I as OpI, K as OpK, R as OpR};
+        use self::Operand::{Dt, Hf, IndirectI, Long, Reg, St, Value};
+        let addr = |expr: &Expr, column: usize| self.value(expr, column, loc, 0, 0xFFF, "a 12 bit address").map(|v| v as u16);
+        let byte = |expr: &Expr, column: usize| self.value(expr, column, loc, -128, 0xFF, "a byte").map(|v| v as u8);
+        let nibble = |expr: &Expr, column: usize| self.value(expr, column, loc, 0, 0xF, "4 bits").map(|v| v as u8);
+        let instruction = match (mnemonic, operands) {
+            ("CLS", []) => Cls,
+            ("RET", []) => Ret,
+            ("SCR", []) => Scr,
+            ("SCL", []) => Scl,
+            ("EXIT", []) => Exit,
+            ("LOW", []) => Low,
+            ("HIGH", []) => High,
+            ("AUDIO", []) => Audio,
+            ("SYS", [Value(e, c)]) => Sys(addr(e, *c)?),
+            ("JP", [Value(e, c)]) => Jp(addr(e, *c)?),
+            ("JP", [Reg(0), Value(e, c)]) => JpV0(addr(e, *c)?),
+            ("CALL", [Value(e, c)]) => Call(addr(e, *c)?),
+            ("SE", [Reg(x), Reg(y)]) => SeReg(*x, *y),
+            ("SE", [Reg(x), Value(e, c)]) => Se(*x, byte(e, *c)?),
+            ("SNE", [Reg(x), Reg(y)]) => SneReg(*x, *y),
+            ("SNE", [Reg(x), Value(e, c)]) => Sne(*x, byte(e, *c)?),
+            ("LD", [Reg(x), Reg(y)]) => LdReg(*x, *y),
+            ("LD", [Reg(x), Value(e, c)]) => Ld(*x, byte(e, *c)?),
+            ("LD", [OpI, Value(e, c)]) => LdI(addr(e, *c)?),
+            ("LD", [OpI, Long(e, c)]) => LdILong(self.value(e, *c, loc, 0, 0xFFFF, "a 16 bit address")? as u16),
+            ("LD", [Reg(x), Dt]) => LdVxDt(*x),
+            ("LD", [Reg(x), OpK]) => LdVxK(*x),
+            ("LD", [Dt, Reg(x)]) => LdDt(*x),
+            ("LD", [St, Reg(x)]) => LdSt(*x),
+            ("LD", [OpF, Reg(x)]) => LdF(*x),
+            ("LD", [Hf, Reg(x)]) => LdHf(*x),
+            ("LD", [OpB, Reg(x)]) => LdB(*x),
+            ("LD", [IndirectI, Reg(x)]) => StoreRegs(*x),
+            ("LD", [Reg(x), IndirectI]) => LoadRegs(*x),
+            ("LD", [OpR, Reg(x)]) => StoreFlags(*x),
+            ("LD", [Reg(x), OpR]) => LoadFlags(*x),
+            ("ADD", [Reg(x), Reg(y)]) => AddReg(*x, *y),
+            ("ADD", [Reg(x), Value(e, c)]) => Add(*x, byte(e, *c)?),
+            ("ADD", [OpI, Reg(x)]) => AddI(*x),
+            ("OR", [Reg(x), Reg(y)]) => Or(*x, *y),
+            ("AND", [Reg(x), Reg(y)]) => And(*x, *y),
+            ("XOR", [Reg(x), Reg(y)]) => Xor(*x, *y),
+            ("SUB", [Reg(x), Reg(y)]) => Sub(*x, *y),
+            ("SUBN", [Reg(x), Reg(y)]) => Subn(*x, *y),
+            ("SHR", [Reg(x)]) => Shr(*x, *x),
+            ("SHR", [Reg(x), Reg(y)]) => Shr(*x, *y),
+            ("SHL", [Reg(x)]) => Shl(*x, *x),
+            ("SHL", [Reg(x), Reg(y)]) => Shl(*x, *y),
+            ("RND", [Reg(x), Value(e, c)]) => Rnd(*x, byte(e, *c)?),
+            ("DRW", [Reg(x), Reg(y), Value(e, c)]) => Drw(*x, *y, nibble(e, *c)?),
+            ("SKP", [Reg(x)]) => Skp(*x),
+            ("SKNP", [Reg(x)]) => Sknp(*x),
+            ("SCD", [Value(e, c)]) => Scd(nibble(e, *c)?),
+            ("SCU", [Value(e, c)]) => Scu(nibble(e, *c)?),
+            ("SAVE", [Reg(x), Reg(y)]) => StoreRange(*x, *y),
+            ("LOAD", [Reg(x), Reg(y)]) => LoadRange(*x, *y),
+            ("PLANE", [Value(e, c)]) => Plane(nibble(e, *c)?),
+            ("PITCH", [Reg(x)]) => Pitch(*x),
+            _ => {
+                const MNEMONICS: [&str; 32] = [
+                    "CLS", "RET", "SCR", "SCL", "EXIT", "LOW", "HIGH", "AUDIO", "SYS", "JP", "CALL",
+                    "SE", "SNE", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SUBN", "SHR", "SHL", "RND",
+                    "DRW", "SKP", "SKNP", "SCD", "SCU", "SAVE", "LOAD", "PLANE", "PITCH"
+                ];
+                let message = match MNEMONICS.contains(&mnemonic) {
+                    true => format!("invalid operands for `{}`", mnemonic),
+                    false => format!("unknown instruction `{}`", mnemonic)
+                };
+                return Err(self.error(loc, loc.column, message));
+            }
+        };
+        Ok(instruction)
+    }
+
+    /// Second pass: evaluate and encode every statement into the image
+    fn finish(self, origin: u16) -> AsmResult<Assembly> {
+        let mut rom: Vec<u8> = Vec::new();
+        for statement in &self.statements {
+            let loc = statement.loc;
+            let bytes = match &statement.body {
+                Body::Instruction(mnemonic, operands) => self.encode(mnemonic, operands, loc)?.to_bytes(),
+                Body::Bytes(data) => {
+                    let mut bytes = Vec::new();
+                    for item in data {
+                        match item {
+                            Data::Str(text) => bytes.extend(text.bytes()),
+                            Data::Value(expr, column) => bytes.push(self.value(expr, *column, loc, -128, 0xFF, "a byte")? as u8)
+                        }
+                    }
+                    bytes
+                },
+                Body::Words(words) => {
+                    let mut bytes = Vec::new();
+                    for (expr, column) in words {
+                        let word = self.value(expr, *column, loc, -0x8000, 0xFFFF, "a word")? as u16;
+                        bytes.extend(word.to_be_bytes());
+                    }
+                    bytes
+                }
+            };
+            if bytes.is_empty() {
+                continue;
+            }
+            let start = match (statement.addr as usize).checked_sub(origin as usize) {
+                Some(start) => start,
+                None => return Err(self.error(loc, loc.column, format!("address {:04X} is below the origin {:04X}", statement.addr, origin)))
+            };
+            if rom.len() < start + bytes.len() {
+                rom.resize(start + bytes.len(), 0);
+            }
+            rom[start..start + bytes.len()].copy_from_slice(&bytes);
+        }
+        Ok(Assembly {origin, rom, labels: self.labels})
+    }
+}
@@ -0,0 +1,61 @@
+/// Length of the XO-CHIP audio pattern buffer in bytes
+pub const PATTERN_LEN: usize = 16;
+
+/// A struct representing the XO-CHIP audio pattern and pitch registers
+///
+/// The pattern is a 128 sample, 1-bit waveform (MSB first) that is looped
+/// while the sound timer is non-zero. The playback rate is controlled
+/// by the pitch register.
+pub struct ChipAudio {
+    /// 1-bit sample pattern
+    pattern: Vec<u8>,
+    /// Pitch register, 64 plays back at 4000 samples per second
+    pitch: u8
+}
+
+impl ChipAudio {
+    /// Initialize the audio registers with a square wave at the default pitch
+    pub fn init() -> Self {
+        let mut pattern = vec![0xFF; PATTERN_LEN / 2];
+        pattern.extend(vec![0x00; PATTERN_LEN / 2]);
+        ChipAudio {
+            pattern,
+            pitch: 64
+        }
+    }
+
+    /// Set the audio pattern buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - 16 bytes of 1-bit samples, missing bytes are zeroed
+    pub fn set_pattern(&mut self, pattern: &[u8]) {
+        for (i, byte) in self.pattern.iter_mut().enumerate() {
+            *byte = *pattern.get(i).unwrap_or(&0);
+        }
+    }
+
+    /// Get the audio pattern buffer
+    pub fn get_pattern(&self) -> &[u8] {
+        &self.pattern
+    }
+
+    /// Set the pitch register
+    ///
+    /// # Arguments
+    ///
+    /// * `pitch` - new pitch value
+    pub fn set_pitch(&mut self, pitch: u8) {
+        self.pitch = pitch;
+    }
+
+    /// Get the pitch register
+    pub fn get_pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    /// Playback rate of the pattern in samples (bits) per second
+    pub fn sample_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
+}
@@ -0,0 +1,51 @@
+// Standard Library Modules //
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+// Local Modules Use //
+use chip8emu::asm::Assembler;
+
+const USAGE: &str = "usage: chip8-asm <source> [-o <rom>] [-s <symbols>]";
+
+/// Print an error and exit with status 1
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn main() {
+    let mut source = None;
+    let mut output = None;
+    let mut symbols = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = args.next(),
+            "-s" => symbols = args.next(),
+            _ if source.is_none() && !arg.starts_with('-') => source = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
+    let source = source.unwrap_or_else(|| {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    });
+    // Default to the source name with a .ch8 extension
+    let output = output.map(PathBuf::from).unwrap_or_else(|| PathBuf::from(&source).with_extension("ch8"));
+
+    let assembly = Assembler::init().assemble_file(&source).unwrap_or_else(|e| fail(e.to_string()));
+    if let Err(e) = fs::write(&output, &assembly.rom) {
+        fail(format!("can't write {}: {}", output.display(), e));
+    }
+    if let Some(path) = symbols {
+        if let Err(e) = fs::write(&path, assembly.symbol_map()) {
+            fail(format!("can't write {}: {}", path, e));
+        }
+    }
+    println!("{}: {} bytes, {} labels", output.display(), assembly.rom.len(), assembly.labels.len());
+}
@@ -0,0 +1,344 @@
+// Standard Library Modules //
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+// Local Modules Use //
+use chip8emu::bus::Bus;
+use chip8emu::debugger::{Access, Compare, Condition, Debugger, Register, StopReason};
+use chip8emu::gdb::GdbStub;
+use chip8emu::quirks::Quirks;
+use chip8emu::rewind;
+use chip8emu::ChipSystem;
+
+const HELP: &str = "\
+Numbers are decimal, or hex with a 0x prefix. Registers are V0-VF, I, DT, ST and PC.
+
+  break <addr> [if <reg> <op> <value>]  set a breakpoint, op is == != < <= > >=
+  delete <addr>                         remove a breakpoint
+  watch <addr|reg> [read|write|rw]      stop when memory or a register is accessed
+  unwatch <addr|reg>                    remove a watchpoint
+  continue [n]                          run until something stops execution, or n instructions
+  step                                  execute one instruction
+  next                                  execute one instruction, running calls to completion
+  finish                                run until the current subroutine returns
+  back [n]                              undo the last n executed instructions (default 1)
+  regs                                  show the registers
+  mem <addr> <len>                      show memory
+  disasm [addr] [n]                     disassemble n instructions (default 10 from PC)
+  set <reg> <value>                     change a register
+  stack                                 show the call stack
+  screen                                draw the display
+  press <key> / release <key>           press or release a key (0-F)
+  gdb [port]                            wait for a GDB client on localhost (default port 1234)
+  quit                                  exit the debugger";
+
+/// Parse a number, hex with a `0x` prefix or decimal
+fn parse_num(text: &str) -> Result<u16, String> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => text.parse::<u16>()
+    };
+    parsed.map_err(|_| format!("invalid number `{}`", text))
+}
+
+/// Parse a register name, PC isn't a `Register` so it is None
+fn parse_reg(text: &str) -> Result<Option<Register>, String> {
+    let upper = text.to_uppercase();
+    match upper.as_str() {
+        "I" => Ok(Some(Register::I)),
+        "DT" => Ok(Some(Register::Dt)),
+        "ST" => Ok(Some(Register::St)),
+        "PC" => Ok(None),
+        _ => match upper.strip_prefix('V').map(|x| u8::from_str_radix(x, 16)) {
+            Some(Ok(x)) if x < 16 => Ok(Some(Register::V(x))),
+            _ => Err(format!("invalid register `{}`", text))
+        }
+    }
+}
+
+/// Parse a comparison operator
+fn parse_compare(text: &str) -> Result<Compare, String> {
+    match text {
+        "==" => Ok(Compare::Eq),
+        "!=" => Ok(Compare::Ne),
+        "<" => Ok(Compare::Lt),
+        "<=" => Ok(Compare::Le),
+        ">" => Ok(Compare::Gt),
+        ">=" => Ok(Compare::Ge),
+        _ => Err(format!("invalid comparison `{}`", text))
+    }
+}
+
+/// Get a required argument
+fn arg<'a>(args: &[&'a str], index: usize, name: &str) -> Result<&'a str, String> {
+    args.get(index).copied().ok_or_else(|| format!("missing {}", name))
+}
+
+/// Print the registers
+fn print_regs(dbg: &Debugger) {
+    let regs = &dbg.system.registers;
+    for row in 0..2 {
+        let line: Vec<String> = (0..8)
+            .map(|col| row * 8 + col)
+            .map(|x| format!("V{:X}={:02x}", x, regs.get_gp(x)))
+            .collect();
+        println!("{}", line.join(" "));
+    }
+    println!("I={:03x} PC={:03x} SP={} DT={:02x} ST={:02x} state={:?}",
+        regs.get_i(), regs.get_pc(), regs.get_sp(), regs.get_d(), regs.get_s(), dbg.system.state());
+}
+
+/// Print a hex dump of memory, 16 bytes per line, stopping at the
+/// end of memory
+fn print_mem(dbg: &Debugger, addr: u16, len: u16) {
+    let start = addr as usize;
+    let end = (start + len as usize).min(dbg.system.ram.size());
+    for line_start in (start..end).step_by(16) {
+        let bytes: Vec<String> = (line_start..end.min(line_start + 16))
+            .map(|n| match dbg.system.ram.peek_byte(n as u16) {
+                Ok(byte) => format!("{:02x}", byte),
+                Err(_) => String::from("--")
+            })
+            .collect();
+        println!("{:03x}: {}", line_start, bytes.join(" "));
+    }
+}
+
+/// Print instructions starting at `addr`
+fn print_disasm(dbg: &Debugger, addr: u16, count: u16) {
+    let pc = dbg.system.registers.get_pc();
+    let mut loc = addr;
+    for _ in 0..count {
+        let marker = if loc == pc { "=>" } else { "  " };
+        match dbg.peek_instruction(loc) {
+            Some(instruction) => {
+                println!("{} {:03x}: {:04x}  {}", marker, loc, instruction.encode(), instruction);
+                loc = loc.wrapping_add(instruction.size());
+            },
+            None => {
+                let high = dbg.system.ram.peek_byte(loc).unwrap_or(0);
+                let low = dbg.system.ram.peek_byte(loc.wrapping_add(1)).unwrap_or(0);
+                println!("{} {:03x}: {:02x}{:02x}  ???", marker, loc, high, low);
+                loc = loc.wrapping_add(2);
+            }
+        }
+    }
+}
+
+/// Report why execution stopped and show the next instruction
+fn report(dbg: &Debugger, reason: StopReason) {
+    if reason != StopReason::Done {
+        println!("{}", reason);
+    }
+    print_disasm(dbg, dbg.system.registers.get_pc(), 1);
+}
+
+/// Run a single command, returns false when the debugger should exit
+fn run_command(dbg: &mut Debugger, line: &str) -> Result<bool, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let (command, args) = match words.split_first() {
+        Some((command, args)) => (*command, args),
+        None => return Ok(true)
+    };
+    match command {
+        "break" | "b" => {
+            let addr = parse_num(arg(args, 0, "address")?)?;
+            let condition = match args.get(1) {
+                Some(&"if") => Some(Condition {
+                    register: parse_reg(arg(args, 2, "register")?)?.ok_or("PC can't be used in a condition")?,
+                    compare: parse_compare(arg(args, 3, "comparison")?)?,
+                    value: parse_num(arg(args, 4, "value")?)?
+                }),
+                Some(other) => return Err(format!("expected `if`, found `{}`", other)),
+                None => None
+            };
+            dbg.set_breakpoint(addr, condition);
+            println!("breakpoint at {:03x}", addr);
+        },
+        "delete" | "d" => {
+            let addr = parse_num(arg(args, 0, "address")?)?;
+            if !dbg.clear_breakpoint(addr) {
+                return Err(format!("no breakpoint at {:03x}", addr));
+            }
+        },
+        "watch" | "w" | "unwatch" => {
+            let target = arg(args, 0, "address or register")?;
+            let access = match args.get(1).copied() {
+                None | Some("rw") => Access::ReadWrite,
+                Some("read") => Access::Read,
+                Some("write") => Access::Write,
+                Some(other) => return Err(format!("invalid access `{}`", other))
+            };
+            match (parse_reg(target), command) {
+                (Ok(Some(reg)), "unwatch") => dbg.unwatch_register(reg),
+                (Ok(Some(reg)), _) => dbg.watch_register(reg, access),
+                (Ok(None), _) => return Err(String::from("PC can't be watched, use break")),
+                (Err(_), "unwatch") => dbg.unwatch_memory(parse_num(target)?, 1),
+                (Err(_), _) => dbg.watch_memory(parse_num(target)?, 1, access)
+            }
+        },
+        "continue" | "c" => {
+            let limit = match args.first() {
+                Some(n) => Some(parse_num(n)? as u64),
+                None => None
+            };
+            let reason = dbg.cont(limit);
+            report(dbg, reason);
+        },
+        "step" | "s" => {
+            let reason = dbg.step_into();
+            report(dbg, reason);
+        },
+        "next" | "n" => {
+            let reason = dbg.step_over();
+            report(dbg, reason);
+        },
+        "finish" | "f" => {
+            let reason = dbg.step_out();
+            report(dbg, reason);
+        },
+        "back" => {
+            let count = match args.first() {
+                Some(n) => parse_num(n)? as usize,
+                None => 1
+            };
+            match dbg.rewind(count) {
+                0 => return Err(String::from("no history to step back over")),
+                undone => println!("stepped back {} instruction(s)", undone)
+            }
+            print_disasm(dbg, dbg.system.registers.get_pc(), 1);
+        },
+        "regs" | "r" => print_regs(dbg),
+        "mem" | "m" => {
+            let addr = parse_num(arg(args, 0, "address")?)?;
+            let len = parse_num(arg(args, 1, "length")?)?;
+            if addr as usize >= dbg.system.ram.size() {
+                return Err(format!("{:03x} is outside memory", addr));
+            }
+            print_mem(dbg, addr, len);
+        },
+        "disasm" | "x" => {
+            let addr = match args.first() {
+                Some(addr) => parse_num(addr)?,
+                None => dbg.system.registers.get_pc()
+            };
+            let count = match args.get(1) {
+                Some(count) => parse_num(count)?,
+                None => 10
+            };
+            print_disasm(dbg, addr, count);
+        },
+        "set" => {
+            let value = parse_num(arg(args, 1, "value")?)?;
+            let reg = parse_reg(arg(args, 0, "register")?)?;
+            // V0-VF and the timers are 8 bit and PC has to stay in memory
+            let fits = match reg {
+                Some(Register::I) => true,
+                Some(_) => value <= 0xFF,
+                None => (value as usize) < dbg.system.ram.size()
+            };
+            if !fits {
+                return Err(format!("{:x} is out of range for {}", value, arg(args, 0, "register")?));
+            }
+            let regs = &mut dbg.system.registers;
+            match reg {
+                Some(Register::V(x)) => regs.set_gp(x as usize, value as u8),
+                Some(Register::I) => regs.set_i(value),
+                Some(Register::Dt) => regs.set_d(value as u8),
+                Some(Register::St) => regs.set_s(value as u8),
+                None => regs.set_pc(value)
+            }
+        },
+        "stack" => {
+            let stack = dbg.system.registers.get_stack();
+            if stack.is_empty() {
+                println!("stack is empty");
+            }
+            for (depth, addr) in stack.iter().enumerate().rev() {
+                println!("#{} {:03x}", depth, addr);
+            }
+        },
+        "screen" => dbg.system.display.draw_display(),
+        "press" | "release" => {
+            let key = parse_num(arg(args, 0, "key")?)?;
+            if key > 0xF {
+                return Err(format!("invalid key `{}`", key));
+            }
+            match command {
+                "press" => dbg.system.press(key as u8),
+                _ => dbg.system.release(key as u8)
+            }
+        },
+        "gdb" => {
+            let port = match args.first() {
+                Some(port) => parse_num(port)?,
+                None => 1234
+            };
+            println!("waiting for GDB on 127.0.0.1:{}", port);
+            GdbStub::listen(dbg, ("127.0.0.1", port)).map_err(|e| format!("gdb: {}", e))?;
+            println!("GDB detached");
+        },
+        "help" | "h" => println!("{}", HELP),
+        "quit" | "q" => return Ok(false),
+        _ => return Err(format!("unknown command `{}`, try help", command))
+    }
+    Ok(true)
+}
+
+/// Pick a quirks preset by name
+fn quirks_named(name: &str) -> Option<Quirks> {
+    match name {
+        "vip" => Some(Quirks::cosmac_vip()),
+        "chip48" => Some(Quirks::chip48()),
+        "schip" => Some(Quirks::superchip()),
+        "octo" => Some(Quirks::octo()),
+        _ => None
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("usage: chip8-dbg <rom> [vip|chip48|schip|octo]");
+        process::exit(2);
+    }
+    let quirks = match args.get(2) {
+        Some(name) => quirks_named(name).unwrap_or_else(|| {
+            eprintln!("unknown quirks preset `{}`", name);
+            process::exit(2);
+        }),
+        None => Quirks::default()
+    };
+    let mut system = ChipSystem::init_with_quirks(quirks);
+    if let Err(e) = system.ram.load_rom_file(&args[1]) {
+        eprintln!("can't load {}: {}", args[1], e);
+        process::exit(1);
+    }
+    let mut dbg = Debugger::init(system);
+    dbg.enable_rewind(rewind::DEFAULT_BUDGET);
+    println!("loaded {}, type help for commands", args[1]);
+    print_disasm(&dbg, dbg.system.registers.get_pc(), 1);
+
+    let stdin = io::stdin();
+    let mut last = String::new();
+    loop {
+        print!("(chip8) ");
+        io::stdout().flush().ok();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        // An empty line repeats the last command
+        let line = match line.trim() {
+            "" => last.clone(),
+            trimmed => trimmed.to_string()
+        };
+        match run_command(&mut dbg, &line) {
+            Ok(true) => last = line,
+            Ok(false) => break,
+            Err(e) => println!("{}", e)
+        }
+    }
+}
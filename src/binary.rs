@@ -0,0 +1,199 @@
+// Standard Library Modules //
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Problem found by the shared parts of the binary formats (save
+/// states, movies and traces), each format turns it into its own error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    /// The data doesn't start with the expected magic
+    BadMagic,
+    /// The data was written with an incompatible major version
+    UnsupportedVersion { major: u16, minor: u16 },
+    /// The checksum doesn't match, the data is corrupted
+    Checksum,
+    /// The data ends in the middle of a field
+    Truncated,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::BadMagic => write!(f, "unknown magic"),
+            FormatError::UnsupportedVersion { major, minor } => write!(f, "unsupported version {}.{}", major, minor),
+            FormatError::Checksum => write!(f, "checksum mismatch"),
+            FormatError::Truncated => write!(f, "data is truncated"),
+        }
+    }
+}
+
+impl error::Error for FormatError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+impl From<FormatError> for io::Error {
+    fn from(e: FormatError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// CRC-32 (IEEE 802.3, as used by zip and png) of `bytes`
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Start a checksummed file: the magic, then the major and minor
+/// version as 2 byte little endian numbers
+///
+/// # Arguments
+///
+/// * `magic` - first four bytes of the format
+/// * `major` - major version, readers reject any other
+/// * `minor` - minor version, readers accept any
+pub fn header(magic: [u8; 4], major: u16, minor: u16) -> Vec<u8> {
+    let mut out = magic.to_vec();
+    out.extend(major.to_le_bytes());
+    out.extend(minor.to_le_bytes());
+    out
+}
+
+/// Finish a file started with `header` with the CRC-32 of its contents
+///
+/// # Arguments
+///
+/// * `out` - the file so far
+pub fn seal(out: &mut Vec<u8>) {
+    let crc = crc32(out);
+    out.extend(crc.to_le_bytes());
+}
+
+/// Check the header and checksum of a file written with `header` and
+/// `seal`, returns a reader over the body after the header and the
+/// minor version
+///
+/// # Arguments
+///
+/// * `data` - the whole file
+/// * `magic` - expected first four bytes
+/// * `major` - the only major version that can be read
+pub fn open(data: &[u8], magic: [u8; 4], major: u16) -> Result<(Reader<'_>, u16), FormatError> {
+    Reader::init(data).magic(magic)?;
+    if data.len() < 12 {
+        return Err(FormatError::Truncated);
+    }
+    let (body, crc) = data.split_at(data.len() - 4);
+    let mut reader = Reader::init(body);
+    reader.skip(4)?;
+    let (found, minor) = (reader.u16()?, reader.u16()?);
+    if found != major {
+        return Err(FormatError::UnsupportedVersion {major: found, minor});
+    }
+    if crc32(body) != u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]) {
+        return Err(FormatError::Checksum);
+    }
+    Ok((reader, minor))
+}
+
+/// Little endian reader over a byte slice
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    /// Read from the start of `bytes`
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - the data to read
+    pub fn init(bytes: &'a [u8]) -> Self {
+        Reader {bytes, pos: 0}
+    }
+
+    /// Returns true once every byte has been read
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    /// Bytes not read yet
+    pub fn rest(&self) -> &'a [u8] {
+        self.bytes.get(self.pos..).unwrap_or(&[])
+    }
+
+    /// Read the next `len` bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - number of bytes
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], FormatError> {
+        let end = self.pos.checked_add(len).ok_or(FormatError::Truncated)?;
+        let bytes = self.bytes.get(self.pos..end).ok_or(FormatError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// Skip the next `len` bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - number of bytes
+    pub fn skip(&mut self, len: usize) -> Result<(), FormatError> {
+        self.take(len).map(|_| ())
+    }
+
+    /// Read four bytes and check they are `magic`
+    ///
+    /// # Arguments
+    ///
+    /// * `magic` - the expected bytes
+    pub fn magic(&mut self, magic: [u8; 4]) -> Result<(), FormatError> {
+        match self.take(4) {
+            Ok(bytes) if bytes == magic => Ok(()),
+            _ => Err(FormatError::BadMagic)
+        }
+    }
+
+    /// Read a `len` byte little endian number
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - number of bytes, at most 8
+    pub fn uint(&mut self, len: usize) -> Result<u64, FormatError> {
+        Ok(self.take(len)?.iter().rev().fold(0, |value, &byte| (value << 8) | byte as u64))
+    }
+
+    /// Read a byte
+    pub fn u8(&mut self) -> Result<u8, FormatError> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Read a byte as a flag, anything but 0 is true
+    pub fn bool(&mut self) -> Result<bool, FormatError> {
+        Ok(self.u8()? != 0)
+    }
+
+    /// Read a 2 byte number
+    pub fn u16(&mut self) -> Result<u16, FormatError> {
+        Ok(self.uint(2)? as u16)
+    }
+
+    /// Read a 4 byte number
+    pub fn u32(&mut self) -> Result<u32, FormatError> {
+        Ok(self.uint(4)? as u32)
+    }
+
+    /// Read an 8 byte number
+    pub fn u64(&mut self) -> Result<u64, FormatError> {
+        self.uint(8)
+    }
+}
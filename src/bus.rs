@@ -0,0 +1,162 @@
+// Local Modules Use //
+use crate::instruction::{DecodeError, Instruction};
+use crate::memory::{ChipMemory, MemResult, OutOfRange, PROGRAM_START};
+
+/// The memory bus a `ChipSystem` executes programs from
+///
+/// Every instruction fetch, data read and data write of the emulator
+/// goes through the bus, so a wrapper around `ChipMemory` can log
+/// accesses, trigger on watched addresses, map debug ports or make
+/// regions read-only. Only the first five methods have to be
+/// implemented, the rest are built on them.
+pub trait Bus {
+    /// Read a byte as data (`Dxyn`, `Fx65`, ...)
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address to read
+    fn read_byte(&mut self, addr: u16) -> MemResult<u8>;
+
+    /// Write a byte as data (`Fx33`, `Fx55`, ...)
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address to write
+    /// * `val` - value to write
+    fn write_byte(&mut self, addr: u16, val: u8) -> MemResult<()>;
+
+    /// Fetch the two byte opcode at `addr`
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address of the opcode
+    fn fetch_opcode(&mut self, addr: u16) -> MemResult<u16>;
+
+    /// Read a byte without side effects, used by tools that inspect
+    /// memory (disassemblers, debuggers, the JIT) rather than the program
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address to read
+    fn peek_byte(&self, addr: u16) -> MemResult<u8>;
+
+    /// Size of the address space in bytes
+    fn size(&self) -> usize;
+
+    /// Fetch and decode the instruction starting at `addr`, the inner
+    /// result holds the opcode that could not be decoded
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address of the instruction
+    fn fetch_instruction(&mut self, addr: u16) -> MemResult<Result<Instruction, DecodeError>> {
+        let opcode = self.fetch_opcode(addr)?;
+        // Only the XO-CHIP long load reads past the opcode
+        match opcode {
+            0xF000 => Ok(Instruction::decode_long(opcode, self.fetch_opcode(addr.wrapping_add(2))?)),
+            _ => Ok(Instruction::decode(opcode))
+        }
+    }
+
+    /// Read a range of bytes as data
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - start address
+    /// * `len` - number of bytes
+    fn read_bytes(&mut self, addr: u16, len: u16) -> MemResult<Vec<u8>> {
+        (0..len as usize)
+            .map(|n| match addr as usize + n {
+                loc if loc <= u16::MAX as usize => self.read_byte(loc as u16),
+                loc => Err(OutOfRange {addr: loc})
+            })
+            .collect()
+    }
+
+    /// Load a binary at the program start location
+    ///
+    /// # Arguments
+    ///
+    /// * `rom` - program bytes
+    fn load_bytes(&mut self, rom: Vec<u8>) -> MemResult<()> {
+        let start = PROGRAM_START as usize;
+        if start + rom.len() > self.size() {
+            return Err(OutOfRange {addr: self.size()});
+        }
+        for (n, byte) in rom.into_iter().enumerate() {
+            self.write_byte((start + n) as u16, byte)?;
+        }
+        Ok(())
+    }
+
+    /// Returns true if a ROM has been loaded from a file
+    fn has_loaded(&self) -> bool {
+        false
+    }
+
+    /// Copy of the whole address space, used by save states
+    fn dump(&self) -> Vec<u8> {
+        (0..self.size()).map(|addr| self.peek_byte(addr as u16).unwrap_or(0)).collect()
+    }
+
+    /// Replace the whole address space, used by save states
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - new contents, must be `size()` bytes long
+    fn restore(&mut self, bytes: &[u8]) -> MemResult<()> {
+        if bytes.len() != self.size() {
+            return Err(OutOfRange {addr: bytes.len()});
+        }
+        for (addr, &byte) in bytes.iter().enumerate() {
+            self.write_byte(addr as u16, byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl Bus for ChipMemory {
+    fn read_byte(&mut self, addr: u16) -> MemResult<u8> {
+        self.get_byte(addr)
+    }
+
+    fn write_byte(&mut self, addr: u16, val: u8) -> MemResult<()> {
+        self.set_byte(addr, val)
+    }
+
+    fn fetch_opcode(&mut self, addr: u16) -> MemResult<u16> {
+        self.get_opcode(addr)
+    }
+
+    fn peek_byte(&self, addr: u16) -> MemResult<u8> {
+        self.get_byte(addr)
+    }
+
+    fn size(&self) -> usize {
+        ChipMemory::size(self)
+    }
+
+    fn fetch_instruction(&mut self, addr: u16) -> MemResult<Result<Instruction, DecodeError>> {
+        ChipMemory::fetch_instruction(self, addr)
+    }
+
+    fn read_bytes(&mut self, addr: u16, len: u16) -> MemResult<Vec<u8>> {
+        self.get_nbytes(addr, len)
+    }
+
+    fn load_bytes(&mut self, rom: Vec<u8>) -> MemResult<()> {
+        ChipMemory::load_bytes(self, rom)
+    }
+
+    fn has_loaded(&self) -> bool {
+        ChipMemory::has_loaded(self)
+    }
+
+    fn dump(&self) -> Vec<u8> {
+        ChipMemory::dump(self)
+    }
+
+    fn restore(&mut self, bytes: &[u8]) -> MemResult<()> {
+        ChipMemory::restore(self, bytes)
+    }
+}
@@ -0,0 +1,654 @@
+// Standard Library Modules //
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Local Modules Use //
+use crate::bus::Bus;
+use crate::instruction::{DecodeError, Instruction};
+use crate::memory::{ChipMemory, MemResult};
+use crate::quirks::{IndexIncrement, Quirks};
+use crate::registers::ChipRegisters;
+use crate::rewind::Rewind;
+use crate::{ChipSystem, CpuState, ExError};
+
+/// A register that can be watched or used in a breakpoint condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    /// General purpose register V0-VF
+    V(u8),
+    /// Index register
+    I,
+    /// Delay timer
+    Dt,
+    /// Sound timer
+    St
+}
+
+impl Register {
+    /// Get the current value of the register
+    ///
+    /// # Arguments
+    ///
+    /// * `registers` - registers to read from
+    pub fn value(&self, registers: &ChipRegisters) -> u16 {
+        match *self {
+            Register::V(x) => registers.get_gp((x & 0xf) as usize) as u16,
+            Register::I => registers.get_i(),
+            Register::Dt => registers.get_d() as u16,
+            Register::St => registers.get_s() as u16
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => write!(f, "I"),
+            Register::Dt => write!(f, "DT"),
+            Register::St => write!(f, "ST")
+        }
+    }
+}
+
+/// Kind of access a watchpoint triggers on, or that triggered it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Data reads
+    Read,
+    /// Data writes
+    Write,
+    /// Both reads and writes, only used when setting a watchpoint
+    ReadWrite
+}
+
+impl Access {
+    /// Returns true if a watchpoint of this kind triggers on `access`
+    fn covers(self, access: Access) -> bool {
+        self == Access::ReadWrite || self == access
+    }
+}
+
+/// What a triggered watchpoint was watching
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchTarget {
+    /// A byte of memory
+    Memory(u16),
+    /// A register
+    Register(Register)
+}
+
+/// Comparison used by a breakpoint condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    /// Equal to
+    Eq,
+    /// Not equal to
+    Ne,
+    /// Less than
+    Lt,
+    /// Less than or equal to
+    Le,
+    /// Greater than
+    Gt,
+    /// Greater than or equal to
+    Ge
+}
+
+/// Condition a conditional breakpoint has to meet to stop,
+/// `register <compare> value`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    /// Register to compare
+    pub register: Register,
+    /// How to compare it
+    pub compare: Compare,
+    /// Value to compare against
+    pub value: u16
+}
+
+impl Condition {
+    /// Returns true if the condition holds for `registers`
+    ///
+    /// # Arguments
+    ///
+    /// * `registers` - registers to test
+    pub fn holds(&self, registers: &ChipRegisters) -> bool {
+        let reg = self.register.value(registers);
+        match self.compare {
+            Compare::Eq => reg == self.value,
+            Compare::Ne => reg != self.value,
+            Compare::Lt => reg < self.value,
+            Compare::Le => reg <= self.value,
+            Compare::Gt => reg > self.value,
+            Compare::Ge => reg >= self.value
+        }
+    }
+}
+
+/// Why the debugger stopped running the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The requested step, step over or step out finished
+    Done,
+    /// Reached a breakpoint, the instruction at the address hasn't run
+    Breakpoint(u16),
+    /// The instruction at `pc` accessed a watched byte or register
+    Watchpoint {
+        /// Address of the instruction that made the access
+        pc: u16,
+        /// What was accessed
+        target: WatchTarget,
+        /// How it was accessed
+        access: Access
+    },
+    /// The program waits for a key (`Fx0A`), press one to continue
+    AwaitingKey(u8),
+    /// The program exited (`00FD`)
+    Exited,
+    /// The instruction limit passed to `cont` was reached
+    LimitReached,
+    /// Step out was asked for at the top level, there is no return
+    /// to run to
+    NotInSubroutine,
+    /// Execution failed
+    Error(ExError)
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::Done => write!(f, "stopped"),
+            StopReason::Breakpoint(addr) => write!(f, "breakpoint at {:03x}", addr),
+            StopReason::Watchpoint {pc, target, access} => {
+                let kind = match access {
+                    Access::Read => "read",
+                    _ => "write"
+                };
+                match target {
+                    WatchTarget::Memory(addr) => write!(f, "{} of {:03x} at {:03x}", kind, addr, pc),
+                    WatchTarget::Register(reg) => write!(f, "{} of {} at {:03x}", kind, reg, pc)
+                }
+            },
+            StopReason::AwaitingKey(x) => write!(f, "waiting for a key into V{:X}", x),
+            StopReason::Exited => write!(f, "program exited"),
+            StopReason::LimitReached => write!(f, "instruction limit reached"),
+            StopReason::NotInSubroutine => write!(f, "not in a subroutine"),
+            StopReason::Error(e) => write!(f, "error: {}", e)
+        }
+    }
+}
+
+/// A bus that records data accesses to watched addresses
+///
+/// Instruction fetches and ROM loads are not recorded.
+pub struct WatchBus<B: Bus = ChipMemory> {
+    /// The wrapped bus
+    inner: B,
+    /// Watched addresses and the accesses to watch for
+    watches: BTreeMap<u16, Access>,
+    /// Watched accesses since the last `take_hits`
+    hits: Vec<(u16, Access)>
+}
+
+impl<B: Bus> WatchBus<B> {
+    /// Wrap a bus, nothing is watched
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - the bus to watch
+    pub fn init(inner: B) -> Self {
+        WatchBus {
+            inner,
+            watches: BTreeMap::new(),
+            hits: Vec::new()
+        }
+    }
+
+    /// Get the wrapped bus
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Get the wrapped bus mutably, accesses through it are not watched
+    pub fn inner_mut(&mut self) -> &mut B {
+        &mut self.inner
+    }
+
+    /// Unwrap the bus
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    /// Take the watched accesses made since the last call
+    pub fn take_hits(&mut self) -> Vec<(u16, Access)> {
+        std::mem::take(&mut self.hits)
+    }
+
+    /// Record an access if the address is watched for it
+    fn record(&mut self, addr: u16, access: Access) {
+        if let Some(watch) = self.watches.get(&addr) {
+            if watch.covers(access) {
+                self.hits.push((addr, access));
+            }
+        }
+    }
+}
+
+impl<B: Bus> Bus for WatchBus<B> {
+    fn read_byte(&mut self, addr: u16) -> MemResult<u8> {
+        self.record(addr, Access::Read);
+        self.inner.read_byte(addr)
+    }
+
+    fn write_byte(&mut self, addr: u16, val: u8) -> MemResult<()> {
+        self.record(addr, Access::Write);
+        self.inner.write_byte(addr, val)
+    }
+
+    fn fetch_opcode(&mut self, addr: u16) -> MemResult<u16> {
+        self.inner.fetch_opcode(addr)
+    }
+
+    fn peek_byte(&self, addr: u16) -> MemResult<u8> {
+        self.inner.peek_byte(addr)
+    }
+
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+
+    fn fetch_instruction(&mut self, addr: u16) -> MemResult<Result<Instruction, DecodeError>> {
+        self.inner.fetch_instruction(addr)
+    }
+
+    fn read_bytes(&mut self, addr: u16, len: u16) -> MemResult<Vec<u8>> {
+        let end = addr as usize + len as usize;
+        let watched: Vec<u16> = self.watches.range(addr..).take_while(|(&a, _)| (a as usize) < end).map(|(&a, _)| a).collect();
+        for a in watched {
+            self.record(a, Access::Read);
+        }
+        self.inner.read_bytes(addr, len)
+    }
+
+    fn load_bytes(&mut self, rom: Vec<u8>) -> MemResult<()> {
+        self.inner.load_bytes(rom)
+    }
+
+    fn has_loaded(&self) -> bool {
+        self.inner.has_loaded()
+    }
+
+    fn dump(&self) -> Vec<u8> {
+        self.inner.dump()
+    }
+
+    fn restore(&mut self, bytes: &[u8]) -> MemResult<()> {
+        self.inner.restore(bytes)
+    }
+}
+
+/// A debugger wrapped around a `ChipSystem`
+///
+/// Runs the program one instruction at a time, stopping at breakpoints
+/// (optionally conditional), watched memory and register accesses, key
+/// waits, errors and when the program exits. Breakpoints stop before
+/// the instruction runs, watchpoints stop after the accessing
+/// instruction has run. With rewind enabled every executed instruction
+/// can be stepped back over.
+pub struct Debugger<B: Bus = ChipMemory> {
+    /// The system being debugged
+    pub system: ChipSystem<WatchBus<B>>,
+    /// Breakpoint addresses with their optional condition
+    breakpoints: BTreeMap<u16, Option<Condition>>,
+    /// Watched registers and the accesses to watch for
+    register_watches: Vec<(Register, Access)>,
+    /// Snapshots taken after each instruction, if rewind is enabled
+    rewind: Option<Rewind>,
+    /// Address and register of a `Fx0A` waiting for a key, Vx is only
+    /// written once the wait ends
+    key_wait: Option<(u16, u8)>
+}
+
+impl<B: Bus> Debugger<B> {
+    /// Start debugging a system
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - the system to debug, with a program loaded
+    pub fn init(system: ChipSystem<B>) -> Self {
+        Debugger {
+            system: system.map_bus(WatchBus::init),
+            breakpoints: BTreeMap::new(),
+            register_watches: Vec::new(),
+            rewind: None,
+            key_wait: None
+        }
+    }
+
+    /// Stop debugging and get the system back
+    pub fn into_system(self) -> ChipSystem<B> {
+        self.system.map_bus(WatchBus::into_inner)
+    }
+
+    /// Set a breakpoint, replacing any breakpoint at the same address
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address of the instruction to stop before
+    /// * `condition` - only stop when this holds, None to always stop
+    pub fn set_breakpoint(&mut self, addr: u16, condition: Option<Condition>) {
+        self.breakpoints.insert(addr, condition);
+    }
+
+    /// Remove a breakpoint, returns true if there was one
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address of the breakpoint
+    pub fn clear_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr).is_some()
+    }
+
+    /// Get the breakpoints in address order
+    pub fn breakpoints(&self) -> Vec<(u16, Option<Condition>)> {
+        self.breakpoints.iter().map(|(&addr, &cond)| (addr, cond)).collect()
+    }
+
+    /// Watch a range of memory for data reads and/or writes
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - first address to watch
+    /// * `len` - number of bytes to watch
+    /// * `access` - accesses to stop on
+    pub fn watch_memory(&mut self, addr: u16, len: u16, access: Access) {
+        for offset in 0..len {
+            self.system.ram.watches.insert(addr.wrapping_add(offset), access);
+        }
+    }
+
+    /// Stop watching a range of memory
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - first address
+    /// * `len` - number of bytes
+    pub fn unwatch_memory(&mut self, addr: u16, len: u16) {
+        for offset in 0..len {
+            self.system.ram.watches.remove(&addr.wrapping_add(offset));
+        }
+    }
+
+    /// Watch a register for reads and/or writes by instructions
+    ///
+    /// # Arguments
+    ///
+    /// * `register` - the register to watch
+    /// * `access` - accesses to stop on
+    pub fn watch_register(&mut self, register: Register, access: Access) {
+        self.unwatch_register(register);
+        self.register_watches.push((register, access));
+    }
+
+    /// Stop watching a register
+    ///
+    /// # Arguments
+    ///
+    /// * `register` - the register to stop watching
+    pub fn unwatch_register(&mut self, register: Register) {
+        self.register_watches.retain(|(reg, _)| *reg != register);
+    }
+
+    /// Execute a single instruction
+    pub fn step_into(&mut self) -> StopReason {
+        self.run_until(None, |_| true)
+    }
+
+    /// Execute a single instruction, running a `2nnn` call until it
+    /// returns
+    pub fn step_over(&mut self) -> StopReason {
+        let pc = self.system.registers.get_pc();
+        match self.peek_instruction(pc) {
+            Some(Instruction::Call(_)) => {
+                let depth = self.system.registers.get_sp();
+                let ret = pc.wrapping_add(2);
+                self.run_until(None, move |regs| regs.get_pc() == ret && regs.get_sp() == depth)
+            },
+            _ => self.step_into()
+        }
+    }
+
+    /// Run until the current subroutine returns with its matching `00EE`,
+    /// nothing runs outside a subroutine
+    pub fn step_out(&mut self) -> StopReason {
+        let depth = self.system.registers.get_sp();
+        if depth == 0 {
+            return StopReason::NotInSubroutine;
+        }
+        self.run_until(None, move |regs| regs.get_sp() < depth)
+    }
+
+    /// Run until a breakpoint, watchpoint or other reason to stop
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - most instructions to execute, None for no limit
+    pub fn cont(&mut self, limit: Option<u64>) -> StopReason {
+        self.run_until(limit, |_| false)
+    }
+
+    /// Start recording a snapshot after every executed instruction so
+    /// they can be stepped back over, replacing any earlier history
+    ///
+    /// # Arguments
+    ///
+    /// * `budget` - most bytes of memory to use for snapshots
+    pub fn enable_rewind(&mut self, budget: usize) {
+        let mut rewind = Rewind::init(budget);
+        rewind.capture(&self.system);
+        self.rewind = Some(rewind);
+    }
+
+    /// Stop recording snapshots and drop the history
+    pub fn disable_rewind(&mut self) {
+        self.rewind = None;
+    }
+
+    /// Get the rewind buffer, None if rewind is disabled
+    pub fn get_rewind(&self) -> Option<&Rewind> {
+        self.rewind.as_ref()
+    }
+
+    /// Undo the last executed instruction, returns false if there is
+    /// no history to go back to
+    pub fn step_back(&mut self) -> bool {
+        self.rewind(1) == 1
+    }
+
+    /// Undo up to `count` executed instructions, returns how many were
+    /// undone
+    ///
+    /// Changes made by hand since the last instruction are undone too.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - number of instructions to undo
+    pub fn rewind(&mut self, count: usize) -> usize {
+        self.key_wait = None;
+        match &mut self.rewind {
+            Some(rewind) => rewind.rewind(&mut self.system, count).unwrap_or(0),
+            None => 0
+        }
+    }
+
+    /// Decode the instruction at `addr` without side effects
+    pub fn peek_instruction(&self, addr: u16) -> Option<Instruction> {
+        let ram = &self.system.ram;
+        let word = |loc: u16| -> Option<u16> {
+            let high = ram.peek_byte(loc).ok()?;
+            let low = ram.peek_byte(loc.wrapping_add(1)).ok()?;
+            Some(((high as u16) << 8) | low as u16)
+        };
+        let opcode = word(addr)?;
+        match opcode {
+            0xF000 => Instruction::decode_long(opcode, word(addr.wrapping_add(2))?).ok(),
+            _ => Instruction::decode(opcode).ok()
+        }
+    }
+
+    /// Run instructions until `done` returns true after one of them,
+    /// or something else stops execution
+    ///
+    /// The breakpoint at the starting address is skipped so a stopped
+    /// program can be resumed. Breakpoints are checked before the
+    /// instruction limit, so resuming after `LimitReached` never skips one.
+    fn run_until<F: Fn(&ChipRegisters) -> bool>(&mut self, limit: Option<u64>, done: F) -> StopReason {
+        let mut executed = 0;
+        loop {
+            match self.execute_one() {
+                Err(reason) => return reason,
+                Ok(false) => continue,
+                Ok(true) => executed += 1
+            }
+            if done(&self.system.registers) {
+                return StopReason::Done;
+            }
+            if let Some(reason) = self.check_breakpoint() {
+                return reason;
+            }
+            if limit == Some(executed) {
+                return StopReason::LimitReached;
+            }
+        }
+    }
+
+    /// Stop reason for a breakpoint at the program counter, if any
+    fn check_breakpoint(&self) -> Option<StopReason> {
+        let pc = self.system.registers.get_pc();
+        match self.breakpoints.get(&pc) {
+            Some(None) => Some(StopReason::Breakpoint(pc)),
+            Some(Some(cond)) if cond.holds(&self.system.registers) => Some(StopReason::Breakpoint(pc)),
+            _ => None
+        }
+    }
+
+    /// Run a single system step, returns true if an instruction executed
+    /// or the reason to stop
+    fn execute_one(&mut self) -> Result<bool, StopReason> {
+        match self.system.state() {
+            CpuState::AwaitingKey(x) => return Err(StopReason::AwaitingKey(x)),
+            CpuState::Exited => return Err(StopReason::Exited),
+            CpuState::Running => {}
+        }
+        // A key ended the `Fx0A` wait since the last step and wrote Vx
+        if let Some((pc, x)) = self.key_wait.take() {
+            if let Some(reason) = self.register_watch_hit(pc, &[], &[Register::V(x)]) {
+                return Err(reason);
+            }
+        }
+        let pc = self.system.registers.get_pc();
+        let instruction = self.peek_instruction(pc);
+        self.system.ram.take_hits();
+        let step = self.system.step(false).map_err(StopReason::Error)?;
+        if step.opcode.is_none() {
+            // A draw waiting for the vertical blank, it runs on a later step
+            return Ok(false);
+        }
+        if let Some(rewind) = &mut self.rewind {
+            rewind.capture(&self.system);
+        }
+        if let Some(&(addr, access)) = self.system.ram.take_hits().first() {
+            return Err(StopReason::Watchpoint {pc, target: WatchTarget::Memory(addr), access});
+        }
+        if let Some(instruction) = instruction {
+            if let (Instruction::LdVxK(x), CpuState::AwaitingKey(_)) = (instruction, self.system.state()) {
+                self.key_wait = Some((pc, x));
+            }
+            let (reads, writes) = register_accesses(&instruction, &self.system.quirks);
+            if let Some(reason) = self.register_watch_hit(pc, &reads, &writes) {
+                return Err(reason);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Stop reason for the first watched register among the accesses
+    /// of the instruction at `pc`, writes win over reads
+    fn register_watch_hit(&self, pc: u16, reads: &[Register], writes: &[Register]) -> Option<StopReason> {
+        for &(reg, watch) in &self.register_watches {
+            let access = match (watch.covers(Access::Write) && writes.contains(&reg), watch.covers(Access::Read) && reads.contains(&reg)) {
+                (true, _) => Access::Write,
+                (false, true) => Access::Read,
+                (false, false) => continue
+            };
+            return Some(StopReason::Watchpoint {pc, target: WatchTarget::Register(reg), access});
+        }
+        None
+    }
+}
+
+/// Registers an instruction reads and writes, in that order
+fn register_accesses(instruction: &Instruction, quirks: &Quirks) -> (Vec<Register>, Vec<Register>) {
+    use self::Register::*;
+    use crate::instruction::Instruction::*;
+    let range = |x: u8, y: u8| -> Vec<Register> {
+        let (low, high) = if x <= y { (x, y) } else { (y, x) };
+        (low..=high).map(V).collect()
+    };
+    // Register transfers move I past the registers unless the quirk says otherwise
+    let index = match quirks.index_increment {
+        IndexIncrement::None => vec![],
+        _ => vec![I]
+    };
+    match *instruction {
+        Se(x, _) | Sne(x, _) | Skp(x) | Sknp(x) | Pitch(x) => (vec![V(x)], vec![]),
+        SeReg(x, y) | SneReg(x, y) => (vec![V(x), V(y)], vec![]),
+        Ld(x, _) | Rnd(x, _) => (vec![], vec![V(x)]),
+        Add(x, _) => (vec![V(x)], vec![V(x)]),
+        LdReg(x, y) => (vec![V(y)], vec![V(x)]),
+        Or(x, y) | And(x, y) | Xor(x, y) => match quirks.vf_reset {
+            true => (vec![V(x), V(y)], vec![V(x), V(15)]),
+            false => (vec![V(x), V(y)], vec![V(x)])
+        },
+        AddReg(x, y) | Sub(x, y) | Subn(x, y) => (vec![V(x), V(y)], vec![V(x), V(15)]),
+        Shr(x, y) | Shl(x, y) => match quirks.shift_vy {
+            true => (vec![V(y)], vec![V(x), V(15)]),
+            false => (vec![V(x)], vec![V(x), V(15)])
+        },
+        LdI(_) | LdILong(_) => (vec![], vec![I]),
+        JpV0(addr) => match quirks.jump_vx {
+            true => (vec![V((addr >> 8) as u8)], vec![]),
+            false => (vec![V(0)], vec![])
+        },
+        Drw(x, y, _) => (vec![V(x), V(y), I], vec![V(15)]),
+        LdVxDt(x) => (vec![Dt], vec![V(x)]),
+        LdDt(x) => (vec![V(x)], vec![Dt]),
+        LdSt(x) => (vec![V(x)], vec![St]),
+        AddI(x) => (vec![I, V(x)], vec![I]),
+        LdF(x) | LdHf(x) => (vec![V(x)], vec![I]),
+        LdB(x) => (vec![V(x), I], vec![]),
+        StoreRegs(x) => {
+            let mut reads = range(0, x);
+            reads.push(I);
+            (reads, index)
+        },
+        LoadRegs(x) => {
+            let mut writes = range(0, x);
+            writes.extend(index);
+            (vec![I], writes)
+        },
+        StoreFlags(x) => (range(0, x), vec![]),
+        LoadFlags(x) => (vec![], range(0, x)),
+        StoreRange(x, y) => {
+            let mut reads = range(x, y);
+            reads.push(I);
+            (reads, vec![])
+        },
+        LoadRange(x, y) => (vec![I], range(x, y)),
+        Audio => (vec![I], vec![]),
+        // Fx0A writes Vx when the wait ends, see `Debugger::key_wait`
+        LdVxK(_) => (vec![], vec![]),
+        Sys(_) | Cls | Ret | Jp(_) | Call(_) | Scd(_) | Scu(_) | Scr | Scl | Exit | Low | High | Plane(_) => (vec![], vec![])
+    }
+}
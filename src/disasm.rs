@@ -0,0 +1,266 @@
+// Standard Library Modules //
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Local Modules Use //
+use crate::bus::Bus;
+use crate::instruction::Instruction;
+use crate::memory::{MemResult, OutOfRange, PROGRAM_START};
+
+/// Most data bytes shown on one line of a listing
+const DATA_PER_LINE: usize = 4;
+
+/// What a line of a listing holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    /// An instruction reachable from an entry point
+    Code(Instruction),
+    /// Bytes that are never executed (sprites, tables, padding, ...)
+    Data
+}
+
+/// A line of a listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    /// Address of the first byte
+    pub addr: u16,
+    /// Raw bytes of the instruction or data
+    pub bytes: Vec<u8>,
+    /// Instruction or data
+    pub kind: LineKind
+}
+
+/// Read-only view of a byte image placed at `origin`, decoding goes
+/// through `Bus::fetch_instruction` exactly like execution does
+struct Image {
+    origin: u16,
+    bytes: Vec<u8>
+}
+
+impl Bus for Image {
+    fn read_byte(&mut self, addr: u16) -> MemResult<u8> {
+        self.peek_byte(addr)
+    }
+
+    fn write_byte(&mut self, addr: u16, _val: u8) -> MemResult<()> {
+        Err(OutOfRange {addr: addr as usize})
+    }
+
+    fn fetch_opcode(&mut self, addr: u16) -> MemResult<u16> {
+        let high = self.peek_byte(addr)?;
+        let low = self.peek_byte(addr.wrapping_add(1))?;
+        Ok(((high as u16) << 8) | low as u16)
+    }
+
+    fn peek_byte(&self, addr: u16) -> MemResult<u8> {
+        (addr as usize)
+            .checked_sub(self.origin as usize)
+            .and_then(|index| self.bytes.get(index).copied())
+            .ok_or(OutOfRange {addr: addr as usize})
+    }
+
+    fn size(&self) -> usize {
+        self.origin as usize + self.bytes.len()
+    }
+}
+
+/// A labelled disassembly of a ROM or memory range
+///
+/// Code is found by recursive traversal from the entry points, following
+/// jumps, calls, skips and `Bnnn` (assuming `V0 = 0`), so sprites and
+/// tables between routines come out as data instead of nonsense
+/// instructions. Targets of `1nnn`, `2nnn` and `Bnnn` get `label_`,
+/// `sub_` and `table_` labels, which replace the addresses in operands.
+/// `Display` prints the listing with addresses and raw bytes.
+pub struct Listing {
+    /// Lines in address order
+    lines: Vec<Line>,
+    /// Labels by address
+    labels: BTreeMap<u16, String>
+}
+
+impl Listing {
+    /// Disassemble a ROM loaded at the program start location, with the
+    /// program start as entry point
+    ///
+    /// # Arguments
+    ///
+    /// * `rom` - program bytes
+    pub fn init(rom: Vec<u8>) -> Self {
+        Listing::init_with_entries(rom, PROGRAM_START, &[PROGRAM_START])
+    }
+
+    /// Disassemble bytes placed at any address
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - bytes to disassemble
+    /// * `origin` - address of the first byte
+    /// * `entries` - addresses execution can start at
+    pub fn init_with_entries(bytes: Vec<u8>, origin: u16, entries: &[u16]) -> Self {
+        let mut image = Image {origin, bytes};
+        let len = image.bytes.len();
+        let mut code: Vec<Option<Instruction>> = vec![None; len];
+        let mut covered = vec![false; len];
+        let mut labels = BTreeMap::new();
+        let mut pending = entries.to_vec();
+
+        while let Some(addr) = pending.pop() {
+            let index = match (addr as usize).checked_sub(origin as usize) {
+                Some(index) if index < len => index,
+                _ => continue
+            };
+            // Odd addresses can't execute, and each start is walked once
+            if !addr.is_multiple_of(2) || code[index].is_some() {
+                continue;
+            }
+            let instruction = match image.fetch_instruction(addr) {
+                Ok(Ok(instruction)) => instruction,
+                _ => continue
+            };
+            code[index] = Some(instruction);
+            let size = instruction.size() as usize;
+            covered[index..(index + size).min(len)].iter_mut().for_each(|byte| *byte = true);
+            let next = addr.wrapping_add(instruction.size());
+
+            use self::Instruction::*;
+            match instruction {
+                Jp(target) | Call(target) | JpV0(target) => {
+                    let name = match instruction {
+                        Call(_) => "sub",
+                        JpV0(_) => "table",
+                        _ => "label"
+                    };
+                    // A subroutine label wins over a jump label for the same address
+                    let label = labels.entry(target).or_insert_with(|| format!("{}_{:03X}", name, target));
+                    if name == "sub" {
+                        *label = format!("sub_{:03X}", target);
+                    }
+                    pending.push(target);
+                    if let Call(_) = instruction {
+                        pending.push(next);
+                    }
+                },
+                Ret | Exit => {},
+                Se(..) | Sne(..) | SeReg(..) | SneReg(..) | Skp(_) | Sknp(_) => {
+                    let skip = match image.fetch_opcode(next) {
+                        Ok(0xF000) => 4,
+                        _ => 2
+                    };
+                    pending.push(next);
+                    pending.push(next.wrapping_add(skip));
+                },
+                _ => pending.push(next)
+            }
+        }
+
+        let mut lines = Vec::new();
+        let mut index = 0;
+        while index < len {
+            let addr = origin.wrapping_add(index as u16);
+            if let Some(instruction) = code[index] {
+                let size = (instruction.size() as usize).min(len - index);
+                lines.push(Line {addr, bytes: image.bytes[index..index + size].to_vec(), kind: LineKind::Code(instruction)});
+                index += size;
+                continue;
+            }
+            // Data runs until a line is full, code starts or a label is reached
+            let mut end = index + 1;
+            while end < len && end - index < DATA_PER_LINE && code[end].is_none() && !covered[end]
+                && !labels.contains_key(&origin.wrapping_add(end as u16)) {
+                end += 1;
+            }
+            lines.push(Line {addr, bytes: image.bytes[index..end].to_vec(), kind: LineKind::Data});
+            index = end;
+        }
+
+        // Drop labels that don't start a line (outside the image or inside an instruction)
+        let starts: Vec<u16> = lines.iter().map(|line| line.addr).collect();
+        labels.retain(|addr, _| starts.binary_search(addr).is_ok());
+
+        Listing {lines, labels}
+    }
+
+    /// Disassemble a range of a memory bus, with `start` as entry point
+    ///
+    /// # Arguments
+    ///
+    /// * `ram` - memory to read, through `peek_byte`
+    /// * `start` - first address
+    /// * `end` - address after the last byte
+    pub fn from_bus<B: Bus>(ram: &B, start: u16, end: u16) -> Self {
+        let bytes = (start..end).map_while(|addr| ram.peek_byte(addr).ok()).collect();
+        Listing::init_with_entries(bytes, start, &[start])
+    }
+
+    /// Lines of the listing in address order
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// All labels by address
+    pub fn labels(&self) -> &BTreeMap<u16, String> {
+        &self.labels
+    }
+
+    /// Label for an address, if it has one
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - address to look up
+    pub fn label(&self, addr: u16) -> Option<&str> {
+        self.labels.get(&addr).map(String::as_str)
+    }
+
+    /// Mnemonic text of a line, with labels in place of jump and call
+    /// addresses and `DB` for data
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - line of this listing
+    pub fn text(&self, line: &Line) -> String {
+        let instruction = match line.kind {
+            LineKind::Code(instruction) => instruction,
+            LineKind::Data => {
+                let bytes: Vec<String> = line.bytes.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+                return format!("DB {}", bytes.join(", "));
+            }
+        };
+        match instruction {
+            Instruction::Jp(addr) => match self.label(addr) {
+                Some(label) => format!("JP {}", label),
+                None => instruction.to_string()
+            },
+            Instruction::Call(addr) => match self.label(addr) {
+                Some(label) => format!("CALL {}", label),
+                None => instruction.to_string()
+            },
+            Instruction::JpV0(addr) => match self.label(addr) {
+                Some(label) => format!("JP V0, {}", label),
+                None => instruction.to_string()
+            },
+            _ => instruction.to_string()
+        }
+    }
+}
+
+impl fmt::Display for Listing {
+    /// Format as `addr  raw  mnemonic` lines, labels on their own line
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            if let Some(label) = self.label(line.addr) {
+                writeln!(f, "{}:", label)?;
+            }
+            let raw = match line.kind {
+                LineKind::Code(_) => line.bytes
+                    .chunks(2)
+                    .map(|word| word.iter().map(|byte| format!("{:02X}", byte)).collect::<String>())
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                LineKind::Data => line.bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+            };
+            writeln!(f, "    {:03X}  {:<10}{}", line.addr, raw, self.text(line))?;
+        }
+        Ok(())
+    }
+}
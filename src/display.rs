@@ -1,10 +1,28 @@
-
 // Modules from crates.io //
 
+/// Width and height of the standard (lo-res) display
+const LORES: (usize, usize) = (64, 32);
+/// Width and height of the SUPER-CHIP extended (hi-res) display
+const HIRES: (usize, usize) = (128, 64);
+
+/// Number of XO-CHIP bit planes
+pub const PLANE_COUNT: usize = 2;
+
 /// A struct representing the chip8 display
+///
+/// The display has two XO-CHIP bit planes, programs that never select
+/// a plane only use the first one.
 pub struct ChipDisplay {
-    /// A boolean vector representing the display
-    display: Vec<bool>,
+    /// A boolean vector per bit plane representing the display
+    planes: Vec<Vec<bool>>,
+    /// Bit mask of the planes affected by drawing, clearing and scrolling
+    selected: u8,
+    /// Current width in pixels
+    width: usize,
+    /// Current height in pixels
+    height: usize,
+    /// If the display is in the 128x64 extended mode
+    hires: bool,
     /// String to divide display with
     divider: String,
     /// If display has been modified
@@ -14,105 +32,318 @@ pub struct ChipDisplay {
 impl ChipDisplay {
     /// Initialize the chip8 display struct
     pub fn init() -> Self {
-        let divider = match String::from_utf8(vec![b'-'; 64]) {
-            Ok(s) => s,
-            Err(_) => String::from("ERROR")
-        };
+        let (width, height) = LORES;
         ChipDisplay {
-            display: vec![false; 2048],
-            divider,
+            planes: vec![vec![false; width * height]; PLANE_COUNT],
+            selected: 1,
+            width,
+            height,
+            hires: false,
+            divider: ChipDisplay::make_divider(width),
             modified: false
         }
     }
 
+    /// Build the divider string for a display width
+    fn make_divider(width: usize) -> String {
+        match String::from_utf8(vec![b'-'; width]) {
+            Ok(s) => s,
+            Err(_) => String::from("ERROR")
+        }
+    }
+
     /// Check if the display has been modified
     pub fn mod_check(&mut self) -> bool {
-        let ret = match self.modified {
-            true => {
-                self.modified = false;
-                true
-            }
-            false => false
-        };
+        let ret = self.modified;
+        self.modified = false;
         ret
     }
 
-    /// Get a copy of the display vector
+    /// Get a copy of the display vector, a pixel is set when
+    /// it is set in any plane
+    ///
+    /// The vector is `width() * height()` long, row major
     pub fn get_display(&self) -> Vec<bool> {
-        self.display.clone()
+        self.get_pixels().iter().map(|&pixel| pixel != 0).collect()
+    }
+
+    /// Get the display as colour indices, bit 0 is the first plane and
+    /// bit 1 the second plane
+    ///
+    /// The vector is `width() * height()` long, row major
+    pub fn get_pixels(&self) -> Vec<u8> {
+        let mut pixels = vec![0; self.width * self.height];
+        for (bit, plane) in self.planes.iter().enumerate() {
+            for (pixel, &set) in pixels.iter_mut().zip(plane.iter()) {
+                if set {
+                    *pixel |= 1 << bit;
+                }
+            }
+        }
+        pixels
+    }
+
+    /// Get a copy of a single bit plane
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - which plane (0 or 1)
+    pub fn get_plane(&self, index: usize) -> Vec<bool> {
+        self.planes[index].clone()
+    }
+
+    /// Replace the contents of a single bit plane
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - which plane (0 or 1)
+    /// * `pixels` - `width() * height()` pixels, missing pixels are cleared
+    pub fn set_plane(&mut self, index: usize, pixels: &[bool]) {
+        for (n, pixel) in self.planes[index].iter_mut().enumerate() {
+            *pixel = *pixels.get(n).unwrap_or(&false);
+        }
+        self.modified = true;
+    }
+
+    /// Select which planes are affected by drawing, clearing and scrolling
+    ///
+    /// # Arguments
+    ///
+    /// * `mask` - bit mask of planes (0-3)
+    pub fn select_planes(&mut self, mask: u8) {
+        self.selected = mask & 0x3;
+    }
+
+    /// Get the bit mask of selected planes
+    pub fn selected_planes(&self) -> u8 {
+        self.selected
+    }
+
+    /// Indices of the currently selected planes
+    fn selected_indices(&self) -> Vec<usize> {
+        (0..PLANE_COUNT).filter(|i| self.selected & (1 << i) != 0).collect()
     }
 
+    /// Current width of the display in pixels
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Current height of the display in pixels
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns true if the display is in 128x64 mode
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Switch between the 64x32 and 128x64 modes, the display is cleared
+    ///
+    /// # Arguments
+    ///
+    /// * `hires` - true for 128x64, false for 64x32
+    pub fn set_hires(&mut self, hires: bool) {
+        let (width, height) = match hires {
+            true => HIRES,
+            false => LORES
+        };
+        self.hires = hires;
+        self.width = width;
+        self.height = height;
+        self.planes = vec![vec![false; width * height]; PLANE_COUNT];
+        self.divider = ChipDisplay::make_divider(width);
+        self.modified = true;
+    }
 
     /// Draw a sprite into the chip8 display buffer, returns true if
     /// a cell has a deletion, false otherwise
-    /// 
+    ///
+    /// When several planes are selected the sprite holds the rows for
+    /// each selected plane one after another, lowest plane first.
+    ///
     /// # Arguments
-    /// 
+    ///
     /// * `x_loc` - x starting position
     /// * `y_loc` - y starting position
     /// * `sprite` - a vector of bytes representing the sprite
-    /// 
-    pub fn draw_sprite(&mut self, x_loc: u16, y_loc: u16, sprite: Vec<u8>) -> bool {
-        let row_count = sprite.len();
+    /// * `clip` - clip the sprite at the screen edges instead of wrapping
+    ///
+    pub fn draw_sprite(&mut self, x_loc: u16, y_loc: u16, sprite: Vec<u8>, clip: bool) -> bool {
+        let rows: Vec<u16> = sprite.iter().map(|&byte| (byte as u16) << 8).collect();
+        self.draw_planes(x_loc, y_loc, &rows, 8, clip)
+    }
+
+    /// Draw a 16x16 SUPER-CHIP sprite, returns true if a cell has a
+    /// deletion, false otherwise
+    ///
+    /// # Arguments
+    ///
+    /// * `x_loc` - x starting position
+    /// * `y_loc` - y starting position
+    /// * `sprite` - 32 bytes per selected plane, two per row (left half first)
+    /// * `clip` - clip the sprite at the screen edges instead of wrapping
+    ///
+    pub fn draw_sprite_16(&mut self, x_loc: u16, y_loc: u16, sprite: Vec<u8>, clip: bool) -> bool {
+        let rows: Vec<u16> = sprite
+            .chunks(2)
+            .map(|pair| ((pair[0] as u16) << 8) | *pair.get(1).unwrap_or(&0) as u16)
+            .collect();
+        self.draw_planes(x_loc, y_loc, &rows, 16, clip)
+    }
+
+    /// Split sprite rows between the selected planes and draw them
+    fn draw_planes(&mut self, x_loc: u16, y_loc: u16, rows: &[u16], width: u16, clip: bool) -> bool {
+        let planes = self.selected_indices();
+        let mut ret = false;
+        if planes.is_empty() {
+            return ret;
+        }
+        let height = rows.len() / planes.len();
+        for (n, &plane) in planes.iter().enumerate() {
+            let plane_rows = &rows[n * height..(n + 1) * height];
+            ret |= self.draw_rows(plane, x_loc, y_loc, plane_rows, width, clip);
+        }
+        ret
+    }
+
+    /// XOR rows of up to 16 pixels (MSB first) into a plane
+    fn draw_rows(&mut self, plane: usize, x_loc: u16, y_loc: u16, rows: &[u16], width: u16, clip: bool) -> bool {
+        let disp_w = self.width as u16;
+        let disp_h = self.height as u16;
+        // The starting position always wraps onto the screen
+        let x_loc = x_loc % disp_w;
+        let y_loc = y_loc % disp_h;
         let mut pos;
-        // let mut index;
-        let mut mask;
+        let mut mask: u16;
         let mut init_val;
         let mut ret = false;
-        // println!("Sprite: {:#?}", sprite);
-        // for i in 0..sprite.len() {
-            // println!("{:#010b}", sprite[i]);
-        // }
-        for row in 0..row_count {
-            // index = row * 8;
-            mask = 0x80;
-            for i in 0..8 {
+        for (row, bits) in rows.iter().enumerate() {
+            let y = y_loc + row as u16;
+            if clip && y >= disp_h {
+                break;
+            }
+            mask = 0x8000;
+            for i in 0..width {
+                let x = x_loc + i;
+                if clip && x >= disp_w {
+                    break;
+                }
                 // Calculate bit position with wrap around
-                pos = (((y_loc + row as u16) % 32) * 64) + ((x_loc + i) % 64);
-                // println!("Pixel Index: {}", pos);
-                init_val = self.display[pos as usize];
-                match sprite[row] & mask == mask {
-                    true => self.display[pos as usize] ^= true,
-                    false => self.display[pos as usize] ^= false
+                pos = (((y % disp_h) * disp_w) + (x % disp_w)) as usize;
+                init_val = self.planes[plane][pos];
+                if bits & mask == mask {
+                    self.planes[plane][pos] ^= true;
                 }
                 // Check if deletion occured
-                if ret == false && init_val == true && self.display[pos as usize] == false {
+                if !ret && init_val && !self.planes[plane][pos] {
                     ret = true
                 }
                 mask >>= 1;
             }
         }
         self.modified = true;
-        // self.draw_display();
-        return ret
+        ret
     }
 
-    /// Clear the display array
-    pub fn clear_display(&mut self) {
-        for y in 0..32 {
-            for x in 0..64 {
-                let pos: usize = y * 64 + x;
-                self.display[pos] = false;
+    /// Scroll the selected planes down, new rows are blank
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - number of pixel rows to scroll by
+    pub fn scroll_down(&mut self, rows: usize) {
+        let shift = rows.min(self.height) * self.width;
+        for plane in self.selected_indices() {
+            let display = &mut self.planes[plane];
+            let len = display.len();
+            display.copy_within(0..len - shift, shift);
+            for pixel in &mut display[..shift] {
+                *pixel = false;
             }
         }
+        self.modified = true;
     }
 
-    /// Draw the chip8 display in the terminal
-    pub fn draw_display(&self) {
-        println!("|{}|", self.divider);
-        for x in 0..32 {
-            print!("|");
-            for y in 0..64 {
-                let pos: usize = x * 64 + y;
-                if self.display[pos] == true {
-                    print!("#")
+    /// Scroll the selected planes up, new rows are blank
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - number of pixel rows to scroll by
+    pub fn scroll_up(&mut self, rows: usize) {
+        let shift = rows.min(self.height) * self.width;
+        for plane in self.selected_indices() {
+            let display = &mut self.planes[plane];
+            let len = display.len();
+            display.copy_within(shift..len, 0);
+            for pixel in &mut display[len - shift..] {
+                *pixel = false;
+            }
+        }
+        self.modified = true;
+    }
+
+    /// Scroll the selected planes right by 4 pixels, new columns are blank
+    pub fn scroll_right(&mut self) {
+        let width = self.width;
+        for plane in self.selected_indices() {
+            for row in self.planes[plane].chunks_mut(width) {
+                row.rotate_right(4);
+                for pixel in &mut row[..4] {
+                    *pixel = false;
                 }
-                else {
-                    print!(" ")
+            }
+        }
+        self.modified = true;
+    }
+
+    /// Scroll the selected planes left by 4 pixels, new columns are blank
+    pub fn scroll_left(&mut self) {
+        let width = self.width;
+        for plane in self.selected_indices() {
+            for row in self.planes[plane].chunks_mut(width) {
+                row.rotate_left(4);
+                for pixel in &mut row[width - 4..] {
+                    *pixel = false;
                 }
             }
-            println!("|");
         }
-        println!("|{}|", self.divider);
+        self.modified = true;
+    }
+
+    /// Clear the selected planes
+    pub fn clear_display(&mut self) {
+        for plane in self.selected_indices() {
+            for pixel in self.planes[plane].iter_mut() {
+                *pixel = false;
+            }
+        }
+        self.modified = true;
+    }
+
+    /// Render the display as text, framed by a border
+    ///
+    /// Pixels only in the first plane are `#`, only in the second
+    /// plane `+` and in both planes `@`
+    pub fn to_ascii(&self) -> String {
+        let pixels = self.get_pixels();
+        let mut out = format!("|{}|\n", self.divider);
+        for row in pixels.chunks(self.width) {
+            out.push('|');
+            out.extend(row.iter().map(|&pixel| match pixel {
+                0 => ' ',
+                1 => '#',
+                2 => '+',
+                _ => '@'
+            }));
+            out.push_str("|\n");
+        }
+        out.push_str(&format!("|{}|\n", self.divider));
+        out
+    }
+
+    /// Draw the chip8 display in the terminal, as `to_ascii` renders it
+    pub fn draw_display(&self) {
+        print!("{}", self.to_ascii());
     }
 }
@@ -0,0 +1,431 @@
+// Standard Library Modules //
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+// Local Modules Use //
+use crate::bus::Bus;
+use crate::debugger::{Access, Debugger, StopReason, WatchTarget};
+use crate::memory::ChipMemory;
+
+/// Number of registers in the register set: V0-VF, I, PC, SP, DT, ST
+pub const REGISTER_COUNT: usize = 21;
+/// Instructions run between checks for an interrupt from the client
+const RUN_CHUNK: u64 = 10_000;
+/// Largest packet the stub accepts, reported in `qSupported`
+const PACKET_SIZE: usize = 0x1000;
+
+/// Size in bytes of register `n` in `g` packets, 16 bit registers
+/// are sent little endian
+fn register_size(n: usize) -> usize {
+    match n {
+        16 | 17 => 2,
+        _ => 1
+    }
+}
+
+/// Target description sent to the client, naming the registers
+fn target_xml() -> String {
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\"?>",
+        "<!DOCTYPE target SYSTEM \"gdb-target.dtd\">",
+        "<target version=\"1.0\"><feature name=\"org.chip8.core\">"
+    ));
+    for x in 0..16 {
+        xml.push_str(&format!("<reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\" regnum=\"{}\"/>", x, x));
+    }
+    xml.push_str(concat!(
+        "<reg name=\"i\" bitsize=\"16\" type=\"data_ptr\"/>",
+        "<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>",
+        "<reg name=\"sp\" bitsize=\"8\" type=\"uint8\"/>",
+        "<reg name=\"dt\" bitsize=\"8\" type=\"uint8\"/>",
+        "<reg name=\"st\" bitsize=\"8\" type=\"uint8\"/>",
+        "</feature></target>"
+    ));
+    xml
+}
+
+/// Encode bytes as lowercase hex
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decode hex into bytes, None if it isn't valid hex
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|n| u8::from_str_radix(text.get(n..n + 2)?, 16).ok())
+        .collect()
+}
+
+/// Parse a hex number
+fn parse_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+/// Parse `addr,len` (as used by `m`, `M`, `Z` and `z`)
+fn parse_addr_len(text: &str) -> Option<(usize, usize)> {
+    let (addr, len) = text.split_once(',')?;
+    Some((parse_hex(addr)?, parse_hex(len)?))
+}
+
+/// Returns true if `len` bytes from `addr` fit in memory of `size`
+/// bytes, the client picks both so the sum may overflow
+fn in_memory(addr: usize, len: usize, size: usize) -> bool {
+    addr.checked_add(len).is_some_and(|end| end <= size)
+}
+
+/// A GDB Remote Serial Protocol stub serving a single client
+///
+/// The register set is V0-VF (numbers 0-15), I (16), PC (17), SP (18),
+/// DT (19) and ST (20), and the target's ram is exposed as memory.
+/// Breakpoints (`Z0`), write, read and access watchpoints (`Z2`-`Z4`),
+/// continue, single step and interrupting a running program with
+/// Ctrl-C are supported, as is `reverse-stepi` when the debugger has
+/// rewind enabled.
+pub struct GdbStub<'a, B: Bus = ChipMemory> {
+    /// The debugger controlling the system
+    debugger: &'a mut Debugger<B>,
+    /// Connection to the client
+    stream: TcpStream,
+    /// Bytes received but not yet handled
+    pending: Vec<u8>,
+    /// True once the client turned off acknowledgements
+    no_ack: bool
+}
+
+impl<'a, B: Bus> GdbStub<'a, B> {
+    /// Create a stub for a connected client
+    ///
+    /// # Arguments
+    ///
+    /// * `debugger` - debugger for the system to expose
+    /// * `stream` - connection to the client
+    pub fn init(debugger: &'a mut Debugger<B>, stream: TcpStream) -> Self {
+        GdbStub {
+            debugger,
+            stream,
+            pending: Vec::new(),
+            no_ack: false
+        }
+    }
+
+    /// Wait for a client on `addr` (e.g. `127.0.0.1:1234`) and serve it
+    /// until it detaches or disconnects
+    ///
+    /// # Arguments
+    ///
+    /// * `debugger` - debugger for the system to expose
+    /// * `addr` - address to listen on
+    pub fn listen<A: ToSocketAddrs>(debugger: &'a mut Debugger<B>, addr: A) -> io::Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        let (stream, _) = listener.accept()?;
+        GdbStub::init(debugger, stream).run()
+    }
+
+    /// Handle packets until the client detaches, kills the target or
+    /// disconnects
+    pub fn run(&mut self) -> io::Result<()> {
+        while let Some(packet) = self.read_packet()? {
+            let reply = match self.handle(&packet)? {
+                Some(reply) => reply,
+                None => return Ok(())
+            };
+            self.send_packet(&reply)?;
+        }
+        Ok(())
+    }
+
+    /// Read the next packet, acknowledging it, None if the client
+    /// disconnected
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // Drop acknowledgements, interrupts while stopped and noise before a packet
+            let start = self.pending.iter().position(|&byte| byte == b'$');
+            if let Some(start) = start {
+                let end = self.pending[start..].iter().position(|&byte| byte == b'#').map(|end| start + end);
+                if let Some(end) = end.filter(|end| end + 2 < self.pending.len()) {
+                    let data = String::from_utf8_lossy(&self.pending[start + 1..end]).into_owned();
+                    let checksum = std::str::from_utf8(&self.pending[end + 1..end + 3]).ok().and_then(parse_hex);
+                    self.pending.drain(..end + 3);
+                    let valid = checksum == Some(GdbStub::<B>::checksum(&data) as usize);
+                    if !self.no_ack {
+                        self.stream.write_all(if valid { b"+" } else { b"-" })?;
+                    }
+                    if valid {
+                        return Ok(Some(data));
+                    }
+                    continue;
+                }
+            } else {
+                self.pending.clear();
+            }
+            let mut buf = [0; 1024];
+            match self.stream.read(&mut buf)? {
+                0 => return Ok(None),
+                n => self.pending.extend_from_slice(&buf[..n])
+            }
+        }
+    }
+
+    /// Checksum of packet data
+    fn checksum(data: &str) -> u8 {
+        data.bytes().fold(0, |sum: u8, byte| sum.wrapping_add(byte))
+    }
+
+    /// Send a packet, escaping the characters the protocol reserves
+    fn send_packet(&mut self, data: &str) -> io::Result<()> {
+        let mut escaped = String::with_capacity(data.len());
+        for c in data.chars() {
+            match c {
+                '$' | '#' | '}' | '*' => {
+                    escaped.push('}');
+                    escaped.push((c as u8 ^ 0x20) as char);
+                },
+                _ => escaped.push(c)
+            }
+        }
+        let packet = format!("${}#{:02x}", escaped, GdbStub::<B>::checksum(&escaped));
+        self.stream.write_all(packet.as_bytes())?;
+        self.stream.flush()
+    }
+
+    /// Handle a packet, returns the reply or None to end the session
+    fn handle(&mut self, packet: &str) -> io::Result<Option<String>> {
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => String::from("S05"),
+            "g" => self.read_registers(),
+            "G" => self.write_registers(args),
+            "p" => match parse_hex(args).filter(|&n| n < REGISTER_COUNT) {
+                Some(n) => to_hex(&self.register_bytes(n)),
+                None => String::from("E01")
+            },
+            "P" => self.write_register(args),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "c" | "s" => {
+                if let Some(addr) = parse_hex(args) {
+                    self.debugger.system.registers.set_pc(addr as u16);
+                }
+                let reason = match command {
+                    "s" => self.debugger.step_into(),
+                    _ => match self.run_interruptible()? {
+                        Some(reason) => reason,
+                        None => return Ok(Some(String::from("S02")))
+                    }
+                };
+                GdbStub::<B>::stop_reply(reason)
+            },
+            // Reverse step, at the start of the history GDB expects `replaylog:begin`
+            "b" if args == "s" => match self.debugger.step_back() {
+                true => String::from("S05"),
+                false => String::from("T05replaylog:begin;")
+            },
+            "Z" | "z" => self.set_point(command == "Z", args),
+            "H" | "T" => String::from("OK"),
+            "D" => {
+                self.send_packet("OK")?;
+                return Ok(None);
+            },
+            "k" => return Ok(None),
+            "q" | "Q" => self.query(packet),
+            _ => String::new()
+        };
+        Ok(Some(reply))
+    }
+
+    /// Continue until the debugger stops or the client sends an
+    /// interrupt (0x03), None if interrupted
+    fn run_interruptible(&mut self) -> io::Result<Option<StopReason>> {
+        loop {
+            let reason = self.debugger.cont(Some(RUN_CHUNK));
+            if reason != StopReason::LimitReached {
+                return Ok(Some(reason));
+            }
+            self.stream.set_nonblocking(true)?;
+            let mut buf = [0; 64];
+            let read = self.stream.read(&mut buf);
+            self.stream.set_nonblocking(false)?;
+            match read {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "client disconnected")),
+                Ok(n) => {
+                    if buf[..n].contains(&0x03) {
+                        return Ok(None);
+                    }
+                    self.pending.extend_from_slice(&buf[..n]);
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {},
+                Err(e) => return Err(e)
+            }
+        }
+    }
+
+    /// Stop reply packet for a stop reason
+    fn stop_reply(reason: StopReason) -> String {
+        match reason {
+            StopReason::Exited => String::from("W00"),
+            StopReason::Error(_) => String::from("S04"),
+            StopReason::Watchpoint {target: WatchTarget::Memory(addr), access, ..} => {
+                let kind = match access {
+                    Access::Read => "rwatch",
+                    Access::Write => "watch",
+                    Access::ReadWrite => "awatch"
+                };
+                format!("T05{}:{:x};", kind, addr)
+            },
+            _ => String::from("S05")
+        }
+    }
+
+    /// Bytes of register `n` as sent in `g` and `p` packets
+    fn register_bytes(&self, n: usize) -> Vec<u8> {
+        let regs = &self.debugger.system.registers;
+        match n {
+            0..=15 => vec![regs.get_gp(n)],
+            16 => regs.get_i().to_le_bytes().to_vec(),
+            17 => regs.get_pc().to_le_bytes().to_vec(),
+            18 => vec![regs.get_sp() as u8],
+            19 => vec![regs.get_d()],
+            _ => vec![regs.get_s()]
+        }
+    }
+
+    /// Set register `n` from its bytes
+    fn set_register(&mut self, n: usize, bytes: &[u8]) {
+        let regs = &mut self.debugger.system.registers;
+        let wide = || u16::from_le_bytes([bytes[0], *bytes.get(1).unwrap_or(&0)]);
+        match n {
+            0..=15 => regs.set_gp(n, bytes[0]),
+            16 => regs.set_i(wide()),
+            17 => regs.set_pc(wide()),
+            18 => regs.set_sp(bytes[0] as usize),
+            19 => regs.set_d(bytes[0]),
+            _ => regs.set_s(bytes[0])
+        }
+    }
+
+    /// Reply to `g`, all registers in order
+    fn read_registers(&self) -> String {
+        (0..REGISTER_COUNT).map(|n| to_hex(&self.register_bytes(n))).collect()
+    }
+
+    /// Handle `G`, set all registers
+    fn write_registers(&mut self, args: &str) -> String {
+        let bytes = match from_hex(args) {
+            Some(bytes) if bytes.len() == (0..REGISTER_COUNT).map(register_size).sum::<usize>() => bytes,
+            _ => return String::from("E01")
+        };
+        let mut offset = 0;
+        for n in 0..REGISTER_COUNT {
+            let size = register_size(n);
+            self.set_register(n, &bytes[offset..offset + size]);
+            offset += size;
+        }
+        String::from("OK")
+    }
+
+    /// Handle `P n=value`, set a single register
+    fn write_register(&mut self, args: &str) -> String {
+        let parsed = args.split_once('=').and_then(|(n, value)| Some((parse_hex(n)?, from_hex(value)?)));
+        match parsed {
+            Some((n, bytes)) if n < REGISTER_COUNT && bytes.len() == register_size(n) => {
+                self.set_register(n, &bytes);
+                String::from("OK")
+            },
+            _ => String::from("E01")
+        }
+    }
+
+    /// Handle `m addr,len`, read memory
+    fn read_memory(&self, args: &str) -> String {
+        let ram = &self.debugger.system.ram;
+        match parse_addr_len(args) {
+            Some((addr, len)) if in_memory(addr, len, ram.size()) => {
+                let bytes: Vec<u8> = (addr..addr + len).map(|loc| ram.peek_byte(loc as u16).unwrap_or(0)).collect();
+                to_hex(&bytes)
+            },
+            _ => String::from("E01")
+        }
+    }
+
+    /// Handle `M addr,len:data`, write memory
+    fn write_memory(&mut self, args: &str) -> String {
+        let parsed = args.split_once(':').and_then(|(range, data)| Some((parse_addr_len(range)?, from_hex(data)?)));
+        let ram = self.debugger.system.ram.inner_mut();
+        match parsed {
+            Some(((addr, len), bytes)) if bytes.len() == len && in_memory(addr, len, ram.size()) => {
+                for (offset, byte) in bytes.into_iter().enumerate() {
+                    if ram.write_byte((addr + offset) as u16, byte).is_err() {
+                        return String::from("E01");
+                    }
+                }
+                String::from("OK")
+            },
+            _ => String::from("E01")
+        }
+    }
+
+    /// Handle `Z`/`z type,addr,kind`, set or remove a breakpoint or
+    /// watchpoint
+    fn set_point(&mut self, insert: bool, args: &str) -> String {
+        let mut parts = args.splitn(2, ',');
+        let kind = parts.next();
+        let (addr, len) = match parts.next().and_then(parse_addr_len) {
+            Some((addr, len)) if addr <= u16::MAX as usize => (addr as u16, len as u16),
+            _ => return String::from("E01")
+        };
+        let access = match kind {
+            Some("0") | Some("1") => {
+                match insert {
+                    true => self.debugger.set_breakpoint(addr, None),
+                    false => {
+                        self.debugger.clear_breakpoint(addr);
+                    }
+                }
+                return String::from("OK");
+            },
+            Some("2") => Access::Write,
+            Some("3") => Access::Read,
+            Some("4") => Access::ReadWrite,
+            _ => return String::new()
+        };
+        match insert {
+            true => self.debugger.watch_memory(addr, len.max(1), access),
+            false => self.debugger.unwatch_memory(addr, len.max(1))
+        }
+        String::from("OK")
+    }
+
+    /// Handle general queries
+    fn query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return format!("PacketSize={:x};qXfer:features:read+;QStartNoAckMode+;ReverseStep+", PACKET_SIZE);
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let xml = target_xml();
+            return match parse_addr_len(range) {
+                Some((offset, len)) if offset < xml.len() => {
+                    let end = offset.saturating_add(len).min(xml.len());
+                    let more = if end < xml.len() { 'm' } else { 'l' };
+                    format!("{}{}", more, &xml[offset..end])
+                },
+                Some(_) => String::from("l"),
+                None => String::from("E01")
+            };
+        }
+        match packet {
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                String::from("OK")
+            },
+            "qAttached" => String::from("1"),
+            "qC" => String::from("QC1"),
+            "qfThreadInfo" => String::from("m1"),
+            "qsThreadInfo" => String::from("l"),
+            _ => String::new()
+        }
+    }
+}
@@ -0,0 +1,381 @@
+// Standard Library Modules //
+use std::error;
+use std::fmt;
+
+/// Error returned when a word isn't a known instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    /// The word that failed to decode
+    pub opcode: u16
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown opcode: {:04x}", self.opcode)
+    }
+}
+
+impl error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+/// A decoded Chip8 instruction
+///
+/// Register operands are register indices (0-15), addresses are 12 bit
+/// unless noted otherwise. Opcodes that depend on `Quirks` decode to a
+/// single variant, the quirk is applied at execution time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0nnn - Machine code routine, ignored
+    Sys(u16),
+    /// 00E0 - Clear display
+    Cls,
+    /// 00EE - Return from subroutine
+    Ret,
+    /// 1nnn - Jump to address
+    Jp(u16),
+    /// 2nnn - Call subroutine at address
+    Call(u16),
+    /// 3xkk - Skip next instruction when `Vx == kk`
+    Se(u8, u8),
+    /// 4xkk - Skip next instruction when `Vx != kk`
+    Sne(u8, u8),
+    /// 5xy0 - Skip next instruction when `Vx == Vy`
+    SeReg(u8, u8),
+    /// 6xkk - `Vx = kk`
+    Ld(u8, u8),
+    /// 7xkk - `Vx = Vx + kk`, no carry flag
+    Add(u8, u8),
+    /// 8xy0 - `Vx = Vy`
+    LdReg(u8, u8),
+    /// 8xy1 - `Vx = Vx | Vy`
+    Or(u8, u8),
+    /// 8xy2 - `Vx = Vx & Vy`
+    And(u8, u8),
+    /// 8xy3 - `Vx = Vx ^ Vy`
+    Xor(u8, u8),
+    /// 8xy4 - `Vx = Vx + Vy`, `Vf` is the carry
+    AddReg(u8, u8),
+    /// 8xy5 - `Vx = Vx - Vy`, `Vf` is not borrow
+    Sub(u8, u8),
+    /// 8xy6 - Shift right, `Vf` is the LSB
+    Shr(u8, u8),
+    /// 8xy7 - `Vx = Vy - Vx`, `Vf` is not borrow
+    Subn(u8, u8),
+    /// 8xyE - Shift left, `Vf` is the MSB
+    Shl(u8, u8),
+    /// 9xy0 - Skip next instruction when `Vx != Vy`
+    SneReg(u8, u8),
+    /// Annn - `I = nnn`
+    LdI(u16),
+    /// Bnnn - Jump to `nnn + V0`
+    JpV0(u16),
+    /// Cxkk - `Vx = rand & kk`
+    Rnd(u8, u8),
+    /// Dxyn - Draw sprite at `(Vx, Vy)` with height `n`
+    Drw(u8, u8, u8),
+    /// Ex9E - Skip next instruction if the key in `Vx` is pressed
+    Skp(u8),
+    /// ExA1 - Skip next instruction if the key in `Vx` is not pressed
+    Sknp(u8),
+    /// Fx07 - `Vx = DT`
+    LdVxDt(u8),
+    /// Fx0A - Wait for a key, store it in `Vx`
+    LdVxK(u8),
+    /// Fx15 - `DT = Vx`
+    LdDt(u8),
+    /// Fx18 - `ST = Vx`
+    LdSt(u8),
+    /// Fx1E - `I = I + Vx`
+    AddI(u8),
+    /// Fx29 - Point I at the font sprite for `Vx`
+    LdF(u8),
+    /// Fx33 - Store BCD of `Vx` at I
+    LdB(u8),
+    /// Fx55 - Store `V0 -> Vx` at I
+    StoreRegs(u8),
+    /// Fx65 - Retrieve `V0 -> Vx` from I
+    LoadRegs(u8),
+    /// 00Cn - Scroll display down (SUPER-CHIP)
+    Scd(u8),
+    /// 00FB - Scroll display right 4 pixels (SUPER-CHIP)
+    Scr,
+    /// 00FC - Scroll display left 4 pixels (SUPER-CHIP)
+    Scl,
+    /// 00FD - Exit the interpreter (SUPER-CHIP)
+    Exit,
+    /// 00FE - 64x32 display mode (SUPER-CHIP)
+    Low,
+    /// 00FF - 128x64 display mode (SUPER-CHIP)
+    High,
+    /// Fx30 - Point I at the big font sprite for `Vx` (SUPER-CHIP)
+    LdHf(u8),
+    /// Fx75 - Store `V0 -> Vx` in the RPL flags (SUPER-CHIP)
+    StoreFlags(u8),
+    /// Fx85 - Retrieve `V0 -> Vx` from the RPL flags (SUPER-CHIP)
+    LoadFlags(u8),
+    /// 00Dn - Scroll display up (XO-CHIP)
+    Scu(u8),
+    /// 5xy2 - Store `Vx -> Vy` at I (XO-CHIP)
+    StoreRange(u8, u8),
+    /// 5xy3 - Retrieve `Vx -> Vy` from I (XO-CHIP)
+    LoadRange(u8, u8),
+    /// F000 nnnn - `I = nnnn`, 16 bit address in the following word (XO-CHIP)
+    LdILong(u16),
+    /// Fn01 - Select drawing planes (XO-CHIP)
+    Plane(u8),
+    /// F002 - Load the audio pattern from I (XO-CHIP)
+    Audio,
+    /// Fx3A - Set the audio pitch to `Vx` (XO-CHIP)
+    Pitch(u8),
+}
+
+impl Instruction {
+    /// Decode a two byte opcode
+    ///
+    /// `F000` is the first half of a four byte instruction and fails to
+    /// decode on its own, use `decode_long` when the following word is known.
+    ///
+    /// # Arguments
+    ///
+    /// * `opcode` - two byte opcode to decode
+    pub fn decode(opcode: u16) -> Result<Instruction, DecodeError> {
+        use self::Instruction::*;
+        let x = ((opcode >> 8) & 0xf) as u8;
+        let y = ((opcode >> 4) & 0xf) as u8;
+        let n = (opcode & 0xf) as u8;
+        let kk = (opcode & 0xff) as u8;
+        let nnn = opcode & 0xfff;
+        let err = Err(DecodeError {opcode});
+        let instruction = match opcode >> 12 {
+            0x0 => match nnn {
+                0x0E0 => Cls,
+                0x0EE => Ret,
+                0x0C0..=0x0CF => Scd(n),
+                0x0D0..=0x0DF => Scu(n),
+                0x0FB => Scr,
+                0x0FC => Scl,
+                0x0FD => Exit,
+                0x0FE => Low,
+                0x0FF => High,
+                _ => Sys(nnn)
+            },
+            0x1 => Jp(nnn),
+            0x2 => Call(nnn),
+            0x3 => Se(x, kk),
+            0x4 => Sne(x, kk),
+            0x5 => match n {
+                0x0 => SeReg(x, y),
+                0x2 => StoreRange(x, y),
+                0x3 => LoadRange(x, y),
+                _ => return err
+            },
+            0x6 => Ld(x, kk),
+            0x7 => Add(x, kk),
+            0x8 => match n {
+                0x0 => LdReg(x, y),
+                0x1 => Or(x, y),
+                0x2 => And(x, y),
+                0x3 => Xor(x, y),
+                0x4 => AddReg(x, y),
+                0x5 => Sub(x, y),
+                0x6 => Shr(x, y),
+                0x7 => Subn(x, y),
+                0xE => Shl(x, y),
+                _ => return err
+            },
+            0x9 => match n {
+                0x0 => SneReg(x, y),
+                _ => return err
+            },
+            0xA => LdI(nnn),
+            0xB => JpV0(nnn),
+            0xC => Rnd(x, kk),
+            0xD => Drw(x, y, n),
+            0xE => match kk {
+                0x9E => Skp(x),
+                0xA1 => Sknp(x),
+                _ => return err
+            },
+            _ => match kk {
+                0x01 => Plane(x),
+                0x02 if x == 0 => Audio,
+                0x07 => LdVxDt(x),
+                0x0A => LdVxK(x),
+                0x15 => LdDt(x),
+                0x18 => LdSt(x),
+                0x1E => AddI(x),
+                0x29 => LdF(x),
+                0x30 => LdHf(x),
+                0x33 => LdB(x),
+                0x3A => Pitch(x),
+                0x55 => StoreRegs(x),
+                0x65 => LoadRegs(x),
+                0x75 => StoreFlags(x),
+                0x85 => LoadFlags(x),
+                _ => return err
+            }
+        };
+        Ok(instruction)
+    }
+
+    /// Decode an opcode together with the word that follows it,
+    /// needed for the four byte XO-CHIP `F000 nnnn` instruction
+    ///
+    /// # Arguments
+    ///
+    /// * `opcode` - two byte opcode to decode
+    /// * `next` - the two bytes following the opcode in memory
+    pub fn decode_long(opcode: u16, next: u16) -> Result<Instruction, DecodeError> {
+        match opcode {
+            0xF000 => Ok(Instruction::LdILong(next)),
+            _ => Instruction::decode(opcode)
+        }
+    }
+
+    /// Encode the instruction back into its (first) opcode word
+    ///
+    /// `decode(op)` followed by `encode()` always returns `op`. The
+    /// address of `LdILong` isn't part of the first word, use `to_bytes`
+    /// for the complete encoding.
+    pub fn encode(&self) -> u16 {
+        use self::Instruction::*;
+        let xy = |h: u16, x: u8, y: u8, n: u16| (h << 12) | ((x as u16 & 0xf) << 8) | ((y as u16 & 0xf) << 4) | n;
+        let xkk = |h: u16, x: u8, kk: u8| (h << 12) | ((x as u16 & 0xf) << 8) | kk as u16;
+        let nnn = |h: u16, addr: u16| (h << 12) | (addr & 0xfff);
+        match *self {
+            Sys(addr) => nnn(0x0, addr),
+            Cls => 0x00E0,
+            Ret => 0x00EE,
+            Jp(addr) => nnn(0x1, addr),
+            Call(addr) => nnn(0x2, addr),
+            Se(x, kk) => xkk(0x3, x, kk),
+            Sne(x, kk) => xkk(0x4, x, kk),
+            SeReg(x, y) => xy(0x5, x, y, 0x0),
+            Ld(x, kk) => xkk(0x6, x, kk),
+            Add(x, kk) => xkk(0x7, x, kk),
+            LdReg(x, y) => xy(0x8, x, y, 0x0),
+            Or(x, y) => xy(0x8, x, y, 0x1),
+            And(x, y) => xy(0x8, x, y, 0x2),
+            Xor(x, y) => xy(0x8, x, y, 0x3),
+            AddReg(x, y) => xy(0x8, x, y, 0x4),
+            Sub(x, y) => xy(0x8, x, y, 0x5),
+            Shr(x, y) => xy(0x8, x, y, 0x6),
+            Subn(x, y) => xy(0x8, x, y, 0x7),
+            Shl(x, y) => xy(0x8, x, y, 0xE),
+            SneReg(x, y) => xy(0x9, x, y, 0x0),
+            LdI(addr) => nnn(0xA, addr),
+            JpV0(addr) => nnn(0xB, addr),
+            Rnd(x, kk) => xkk(0xC, x, kk),
+            Drw(x, y, n) => xy(0xD, x, y, n as u16 & 0xf),
+            Skp(x) => xkk(0xE, x, 0x9E),
+            Sknp(x) => xkk(0xE, x, 0xA1),
+            LdVxDt(x) => xkk(0xF, x, 0x07),
+            LdVxK(x) => xkk(0xF, x, 0x0A),
+            LdDt(x) => xkk(0xF, x, 0x15),
+            LdSt(x) => xkk(0xF, x, 0x18),
+            AddI(x) => xkk(0xF, x, 0x1E),
+            LdF(x) => xkk(0xF, x, 0x29),
+            LdB(x) => xkk(0xF, x, 0x33),
+            StoreRegs(x) => xkk(0xF, x, 0x55),
+            LoadRegs(x) => xkk(0xF, x, 0x65),
+            Scd(n) => 0x00C0 | (n as u16 & 0xf),
+            Scr => 0x00FB,
+            Scl => 0x00FC,
+            Exit => 0x00FD,
+            Low => 0x00FE,
+            High => 0x00FF,
+            LdHf(x) => xkk(0xF, x, 0x30),
+            StoreFlags(x) => xkk(0xF, x, 0x75),
+            LoadFlags(x) => xkk(0xF, x, 0x85),
+            Scu(n) => 0x00D0 | (n as u16 & 0xf),
+            StoreRange(x, y) => xy(0x5, x, y, 0x2),
+            LoadRange(x, y) => xy(0x5, x, y, 0x3),
+            LdILong(_) => 0xF000,
+            Plane(n) => xkk(0xF, n, 0x01),
+            Audio => 0xF002,
+            Pitch(x) => xkk(0xF, x, 0x3A),
+        }
+    }
+
+    /// Size of the instruction in bytes (2, or 4 for `LdILong`)
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::LdILong(_) => 4,
+            _ => 2
+        }
+    }
+
+    /// Encode the instruction into big endian bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let opcode = self.encode();
+        let mut bytes = vec![(opcode >> 8) as u8, opcode as u8];
+        if let Instruction::LdILong(addr) = self {
+            bytes.push((addr >> 8) as u8);
+            bytes.push(*addr as u8);
+        }
+        bytes
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// Format the instruction as a standard mnemonic, e.g. `LD V3, 0x10`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Instruction::*;
+        match *self {
+            Sys(addr) => write!(f, "SYS 0x{:03X}", addr),
+            Cls => write!(f, "CLS"),
+            Ret => write!(f, "RET"),
+            Jp(addr) => write!(f, "JP 0x{:03X}", addr),
+            Call(addr) => write!(f, "CALL 0x{:03X}", addr),
+            Se(x, kk) => write!(f, "SE V{:X}, 0x{:02X}", x, kk),
+            Sne(x, kk) => write!(f, "SNE V{:X}, 0x{:02X}", x, kk),
+            SeReg(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Ld(x, kk) => write!(f, "LD V{:X}, 0x{:02X}", x, kk),
+            Add(x, kk) => write!(f, "ADD V{:X}, 0x{:02X}", x, kk),
+            LdReg(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            AddReg(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Shr(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Subn(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Shl(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SneReg(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            LdI(addr) => write!(f, "LD I, 0x{:03X}", addr),
+            JpV0(addr) => write!(f, "JP V0, 0x{:03X}", addr),
+            Rnd(x, kk) => write!(f, "RND V{:X}, 0x{:02X}", x, kk),
+            Drw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Skp(x) => write!(f, "SKP V{:X}", x),
+            Sknp(x) => write!(f, "SKNP V{:X}", x),
+            LdVxDt(x) => write!(f, "LD V{:X}, DT", x),
+            LdVxK(x) => write!(f, "LD V{:X}, K", x),
+            LdDt(x) => write!(f, "LD DT, V{:X}", x),
+            LdSt(x) => write!(f, "LD ST, V{:X}", x),
+            AddI(x) => write!(f, "ADD I, V{:X}", x),
+            LdF(x) => write!(f, "LD F, V{:X}", x),
+            LdB(x) => write!(f, "LD B, V{:X}", x),
+            StoreRegs(x) => write!(f, "LD [I], V{:X}", x),
+            LoadRegs(x) => write!(f, "LD V{:X}, [I]", x),
+            Scd(n) => write!(f, "SCD {}", n),
+            Scr => write!(f, "SCR"),
+            Scl => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            Low => write!(f, "LOW"),
+            High => write!(f, "HIGH"),
+            LdHf(x) => write!(f, "LD HF, V{:X}", x),
+            StoreFlags(x) => write!(f, "LD R, V{:X}", x),
+            LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+            Scu(n) => write!(f, "SCU {}", n),
+            StoreRange(x, y) => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            LoadRange(x, y) => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            LdILong(addr) => write!(f, "LD I, LONG 0x{:04X}", addr),
+            Plane(n) => write!(f, "PLANE {}", n),
+            Audio => write!(f, "AUDIO"),
+            Pitch(x) => write!(f, "PITCH V{:X}", x),
+        }
+    }
+}
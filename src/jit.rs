@@ -0,0 +1,409 @@
+// Standard Library Modules //
+use std::mem;
+
+// Modules From Crates.io //
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{types, AbiParam, InstBuilder, MemFlagsData, UserFuncName, Value};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_codegen::Context;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Linkage, Module};
+
+// Local Modules Use //
+use crate::instruction::Instruction;
+use crate::bus::Bus;
+use crate::quirks::Quirks;
+use crate::registers::ChipRegisters;
+
+/// Times a block start has to be reached before it is compiled
+pub const HOT_THRESHOLD: u16 = 16;
+/// Most instructions compiled into a single block
+pub const MAX_BLOCK_LEN: usize = 64;
+/// Times a block may be invalidated by writes before its address is
+/// left to the interpreter for good
+const MAX_INVALIDATIONS: u8 = 4;
+
+/// Register state shared with compiled blocks
+#[repr(C)]
+#[derive(Default)]
+struct JitState {
+    /// General purpose registers V0-VF
+    v: [u8; 16],
+    /// Index register
+    i: u16,
+    /// Program counter
+    pc: u16,
+    /// Delay timer
+    dt: u8
+}
+
+/// Offset of a field of `JitState` as a cranelift memory offset
+macro_rules! offset {
+    ($field:ident) => {
+        mem::offset_of!(JitState, $field) as i32
+    };
+}
+
+/// A compiled basic block
+struct Block {
+    /// Native code, takes a pointer to a `JitState`
+    code: extern "C" fn(*mut JitState),
+    /// Bytes of ram the block was compiled from
+    bytes: Vec<u8>,
+    /// Number of instructions in the block
+    len: u32,
+    /// Quirks the block was compiled with
+    quirks: Quirks
+}
+
+/// What the JIT knows about a block start address
+#[derive(Clone, Copy)]
+enum Entry {
+    /// Not compiled yet, with the number of times it was reached
+    Cold(u16),
+    /// Compiled to native code, with the index of the block
+    Compiled(u32),
+    /// Too short or too often rewritten, always interpreted
+    Interpreted
+}
+
+/// Compiles hot basic blocks of register and arithmetic instructions
+/// to native code with Cranelift
+///
+/// Only straight-line code is compiled: loads, arithmetic and logic on
+/// `Vx`, `I` loads and additions, delay timer reads, ending at the first
+/// other instruction or after a `1nnn` jump. Everything that touches the
+/// display, keyboard, memory, stack or random source is left to the
+/// interpreter, so a block always produces exactly the register state
+/// interpreting it would.
+///
+/// Before a block runs its bytes are compared with ram, a block that
+/// was overwritten is dropped and the interpreter takes over. Code of
+/// dropped blocks is only freed with the `Jit`.
+pub struct Jit {
+    /// Module holding the generated code
+    module: JITModule,
+    /// Reusable code generation context
+    ctx: Context,
+    /// Reusable function builder context
+    builder_ctx: FunctionBuilderContext,
+    /// State of each address, grown to the size of ram on first use
+    entries: Vec<Entry>,
+    /// Number of times the block at each address was invalidated
+    invalidations: Vec<u8>,
+    /// Every block compiled so far, including invalidated ones
+    blocks: Vec<Block>
+}
+
+impl Jit {
+    /// Create a JIT for the host machine, returns None if Cranelift
+    /// doesn't support the host
+    pub fn init() -> Option<Self> {
+        let mut flags = settings::builder();
+        flags.set("opt_level", "speed").ok()?;
+        let isa = cranelift_native::builder().ok()?
+            .finish(settings::Flags::new(flags)).ok()?;
+        let module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+        Some(Jit {
+            ctx: module.make_context(),
+            module,
+            builder_ctx: FunctionBuilderContext::new(),
+            entries: Vec::new(),
+            invalidations: Vec::new(),
+            blocks: Vec::new()
+        })
+    }
+
+    /// Number of blocks compiled so far
+    pub fn compiled_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Run the compiled block at the program counter, compiling it once
+    /// it is hot
+    ///
+    /// Returns the number of instructions executed, or None if the
+    /// instruction at the program counter has to be interpreted
+    ///
+    /// # Arguments
+    ///
+    /// * `ram` - bus holding the program
+    /// * `registers` - registers the block reads and updates
+    /// * `quirks` - quirks the interpreter is running with
+    /// * `budget` - most instructions the block may execute
+    pub fn run_block<B: Bus>(&mut self, ram: &mut B, registers: &mut ChipRegisters, quirks: &Quirks, budget: u32) -> Option<u32> {
+        let pc = registers.get_pc();
+        if self.entries.len() < ram.size() {
+            self.entries.resize(ram.size(), Entry::Cold(0));
+            self.invalidations.resize(ram.size(), 0);
+        }
+        let index = match self.entries.get(pc as usize) {
+            Some(&Entry::Compiled(index)) => index as usize,
+            Some(&Entry::Cold(hits)) if hits + 1 >= HOT_THRESHOLD => {
+                let entry = match self.compile(ram, pc, quirks) {
+                    Some(block) => {
+                        self.blocks.push(block);
+                        Entry::Compiled(self.blocks.len() as u32 - 1)
+                    },
+                    None => Entry::Interpreted
+                };
+                self.entries[pc as usize] = entry;
+                match entry {
+                    Entry::Compiled(index) => index as usize,
+                    _ => return None
+                }
+            },
+            Some(&Entry::Cold(hits)) => {
+                self.entries[pc as usize] = Entry::Cold(hits + 1);
+                return None;
+            },
+            _ => return None
+        };
+        let block = &self.blocks[index];
+        if block.len > budget {
+            return None;
+        }
+        if block.quirks != *quirks || !Jit::unchanged(ram, pc, &block.bytes) {
+            self.invalidate(pc);
+            return None;
+        }
+        let mut state = JitState::default();
+        for x in 0..16 {
+            state.v[x] = registers.get_gp(x);
+        }
+        state.i = registers.get_i();
+        state.pc = pc;
+        state.dt = registers.get_d();
+        (block.code)(&mut state);
+        for x in 0..16 {
+            registers.set_gp(x, state.v[x]);
+        }
+        registers.set_i(state.i);
+        registers.set_pc(state.pc);
+        Some(block.len)
+    }
+
+    /// Drop the block at `addr`, giving up on it after repeated rewrites
+    fn invalidate(&mut self, addr: u16) {
+        let count = &mut self.invalidations[addr as usize];
+        *count += 1;
+        self.entries[addr as usize] = match *count >= MAX_INVALIDATIONS {
+            true => Entry::Interpreted,
+            false => Entry::Cold(0)
+        };
+    }
+
+    /// Returns true if ram still holds the bytes a block was compiled from
+    fn unchanged<B: Bus>(ram: &B, addr: u16, bytes: &[u8]) -> bool {
+        bytes.iter().enumerate().all(|(n, &byte)| ram.peek_byte(addr.wrapping_add(n as u16)) == Ok(byte))
+    }
+
+    /// Returns true if an instruction can be part of a compiled block
+    fn supported(instruction: &Instruction) -> bool {
+        use crate::instruction::Instruction::*;
+        matches!(instruction,
+            Ld(..) | Add(..) | LdReg(..) | Or(..) | And(..) | Xor(..) | AddReg(..) | Sub(..) |
+            Shr(..) | Subn(..) | Shl(..) | LdI(_) | AddI(_) | LdVxDt(_) | Jp(_))
+    }
+
+    /// Find the basic block starting at `addr`
+    fn scan<B: Bus>(ram: &mut B, addr: u16) -> Vec<Instruction> {
+        let mut block = Vec::new();
+        let mut loc = addr;
+        while block.len() < MAX_BLOCK_LEN {
+            let instruction = match ram.fetch_instruction(loc) {
+                Ok(Ok(instruction)) if Jit::supported(&instruction) => instruction,
+                _ => break
+            };
+            block.push(instruction);
+            if let Instruction::Jp(_) = instruction {
+                break;
+            }
+            loc = loc.wrapping_add(2);
+        }
+        block
+    }
+
+    /// Compile the block starting at `addr`, returns None if it is
+    /// too short to be worth compiling or code generation failed
+    fn compile<B: Bus>(&mut self, ram: &mut B, addr: u16, quirks: &Quirks) -> Option<Block> {
+        let instructions = Jit::scan(ram, addr);
+        if instructions.len() < 2 {
+            return None;
+        }
+        let bytes = (0..2 * instructions.len() as u16)
+            .map(|n| ram.peek_byte(addr.wrapping_add(n)))
+            .collect::<Result<Vec<u8>, _>>().ok()?;
+
+        let config = self.module.target_config();
+        let ptr_type = config.pointer_type();
+        let mut sig = self.module.make_signature();
+        sig.params.push(AbiParam::new(ptr_type));
+        let name = format!("block_{}", self.blocks.len());
+        let id = self.module.declare_function(&name, Linkage::Local, &sig).ok()?;
+        self.ctx.func.signature = sig;
+        self.ctx.func.name = UserFuncName::user(0, id.as_u32());
+        {
+            let mut builder = FunctionBuilder::new(&mut self.ctx.func, &mut self.builder_ctx);
+            let entry = builder.create_block();
+            builder.append_block_params_for_function_params(entry);
+            builder.switch_to_block(entry);
+            let state = builder.block_params(entry)[0];
+            let mut emitter = Emitter {builder: &mut builder, state, quirks};
+            let mut next_pc = addr;
+            for instruction in &instructions {
+                next_pc = match *instruction {
+                    Instruction::Jp(target) => target,
+                    _ => next_pc.wrapping_add(2)
+                };
+                emitter.emit(instruction);
+            }
+            emitter.store_pc(next_pc);
+            builder.ins().return_(&[]);
+            builder.seal_all_blocks();
+            builder.finalize(config);
+        }
+        let defined = self.module.define_function(id, &mut self.ctx);
+        self.module.clear_context(&mut self.ctx);
+        defined.ok()?;
+        self.module.finalize_definitions().ok()?;
+        let code = self.module.get_finalized_function(id);
+        // Safety: the function was generated with the `fn(*mut JitState)` signature
+        let code = unsafe { mem::transmute::<*const u8, extern "C" fn(*mut JitState)>(code) };
+        Some(Block {
+            code,
+            bytes,
+            len: instructions.len() as u32,
+            quirks: *quirks
+        })
+    }
+}
+
+/// Emits the native code of single instructions
+struct Emitter<'a, 'b> {
+    /// Builder of the block function
+    builder: &'a mut FunctionBuilder<'b>,
+    /// Pointer to the `JitState`
+    state: Value,
+    /// Quirks to compile with
+    quirks: &'a Quirks
+}
+
+impl Emitter<'_, '_> {
+    /// Load register `Vx`
+    fn load_v(&mut self, x: u8) -> Value {
+        let offset = offset!(v) + (x & 0xf) as i32;
+        self.builder.ins().load(types::I8, MemFlagsData::trusted(), self.state, offset)
+    }
+
+    /// Store a value in register `Vx`
+    fn store_v(&mut self, x: u8, value: Value) {
+        let offset = offset!(v) + (x & 0xf) as i32;
+        self.builder.ins().store(MemFlagsData::trusted(), value, self.state, offset);
+    }
+
+    /// Store a constant in register `Vx`
+    fn store_v_const(&mut self, x: u8, value: u8) {
+        let value = self.builder.ins().iconst(types::I8, value as i64);
+        self.store_v(x, value);
+    }
+
+    /// Store the program counter the block ends at
+    fn store_pc(&mut self, pc: u16) {
+        let value = self.builder.ins().iconst(types::I16, pc as i64);
+        self.builder.ins().store(MemFlagsData::trusted(), value, self.state, offset!(pc));
+    }
+
+    /// Emit a single instruction, in the same order of register writes
+    /// as the interpreter
+    fn emit(&mut self, instruction: &Instruction) {
+        match *instruction {
+            Instruction::Ld(x, byte) => self.store_v_const(x, byte),
+            Instruction::Add(x, byte) => {
+                let vx = self.load_v(x);
+                let sum = self.builder.ins().iadd_imm_u(vx, byte as i64);
+                self.store_v(x, sum);
+            },
+            Instruction::LdReg(x, y) => {
+                let vy = self.load_v(y);
+                self.store_v(x, vy);
+            },
+            Instruction::Or(x, y) | Instruction::And(x, y) | Instruction::Xor(x, y) => {
+                let vx = self.load_v(x);
+                let vy = self.load_v(y);
+                let result = match instruction {
+                    Instruction::Or(..) => self.builder.ins().bor(vx, vy),
+                    Instruction::And(..) => self.builder.ins().band(vx, vy),
+                    _ => self.builder.ins().bxor(vx, vy)
+                };
+                self.store_v(x, result);
+                if self.quirks.vf_reset {
+                    self.store_v_const(15, 0);
+                }
+            },
+            Instruction::AddReg(x, y) => {
+                let vx = self.load_v(x);
+                let vy = self.load_v(y);
+                let wide_x = self.builder.ins().uextend(types::I16, vx);
+                let wide_y = self.builder.ins().uextend(types::I16, vy);
+                let wide_sum = self.builder.ins().iadd(wide_x, wide_y);
+                let carry = self.builder.ins().ushr_imm_u(wide_sum, 8);
+                let carry = self.builder.ins().ireduce(types::I8, carry);
+                let sum = self.builder.ins().iadd(vx, vy);
+                // The flag is stored last so it wins when x is F
+                self.store_v(x, sum);
+                self.store_v(15, carry);
+            },
+            Instruction::Sub(x, y) => {
+                let vx = self.load_v(x);
+                let vy = self.load_v(y);
+                let no_borrow = self.builder.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, vx, vy);
+                let diff = self.builder.ins().isub(vx, vy);
+                self.store_v(x, diff);
+                self.store_v(15, no_borrow);
+            },
+            Instruction::Subn(x, y) => {
+                let vx = self.load_v(x);
+                let vy = self.load_v(y);
+                let no_borrow = self.builder.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, vy, vx);
+                let diff = self.builder.ins().isub(vy, vx);
+                self.store_v(x, diff);
+                self.store_v(15, no_borrow);
+            },
+            Instruction::Shr(x, y) => {
+                let src = if self.quirks.shift_vy { y } else { x };
+                let value = self.load_v(src);
+                let flag = self.builder.ins().band_imm_u(value, 0x01);
+                let shifted = self.builder.ins().ushr_imm_u(value, 1);
+                self.store_v(x, shifted);
+                self.store_v(15, flag);
+            },
+            Instruction::Shl(x, y) => {
+                let src = if self.quirks.shift_vy { y } else { x };
+                let value = self.load_v(src);
+                let flag = self.builder.ins().ushr_imm_u(value, 7);
+                let shifted = self.builder.ins().ishl_imm_u(value, 1);
+                self.store_v(x, shifted);
+                self.store_v(15, flag);
+            },
+            Instruction::LdI(addr) => {
+                let value = self.builder.ins().iconst(types::I16, addr as i64);
+                self.builder.ins().store(MemFlagsData::trusted(), value, self.state, offset!(i));
+            },
+            Instruction::AddI(x) => {
+                let vx = self.load_v(x);
+                let vx = self.builder.ins().uextend(types::I16, vx);
+                let i = self.builder.ins().load(types::I16, MemFlagsData::trusted(), self.state, offset!(i));
+                let sum = self.builder.ins().iadd(i, vx);
+                self.builder.ins().store(MemFlagsData::trusted(), sum, self.state, offset!(i));
+            },
+            Instruction::LdVxDt(x) => {
+                let dt = self.builder.ins().load(types::I8, MemFlagsData::trusted(), self.state, offset!(dt));
+                self.store_v(x, dt);
+            },
+            // Jumps only end the block, see `Jit::compile`
+            _ => {}
+        }
+    }
+}
@@ -4,10 +4,38 @@ use std::io;
 use std::fs::File;
 use std::io::prelude::*;
 
+// Local Modules Use //
+use crate::instruction::{DecodeError, Instruction};
+
+/// Address of the 5 row hex font
+pub const FONT_ADDR: u16 = 0x050;
+/// Address of the 10 row SUPER-CHIP hex font
+pub const BIG_FONT_ADDR: u16 = 0x0A0;
+/// Address programs are loaded at and start executing from
+pub const PROGRAM_START: u16 = 0x200;
+
+/// Error returned when an access falls outside of ram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfRange {
+    /// The first address that was out of range
+    pub addr: usize
+}
+
+/// Result of a memory access
+pub type MemResult<T> = std::result::Result<T, OutOfRange>;
+
 /// A representation of chip8 ram
+///
+/// Decoded instructions are cached by address, writes through
+/// `set_byte` and `load_bytes` drop every cached instruction they
+/// overlap so self-modifying programs keep working.
 pub struct ChipMemory {
     /// a vector representing the ram
     ram: Vec<u8>,
+    /// decoded instruction starting at each address, if cached
+    cache: Vec<Option<Instruction>>,
+    /// true if `fetch_instruction` uses the cache
+    cache_enabled: bool,
     /// true if a rom has been loaded
     loaded: bool, 
     /// program start location
@@ -17,12 +45,23 @@ pub struct ChipMemory {
 impl ChipMemory {
     /// Init a chip8 memory structure 
     pub fn init() -> Self {
-        // let ram: Vec<u8> = vec![0; 4096];
-        let ram = ChipMemory::load_symbols(vec![0; 4096]);
+        ChipMemory::init_with_size(4096)
+    }
+
+    /// Init a chip8 memory structure with a custom amount of ram,
+    /// XO-CHIP programs expect 65536 bytes
+    /// 
+    /// # Arguments
+    /// 
+    /// * `size` - size of ram in bytes (at least 512)
+    pub fn init_with_size(size: usize) -> Self {
+        let ram = ChipMemory::load_symbols(vec![0; size.max(512)]);
         ChipMemory {
+            cache: vec![None; ram.len()],
+            cache_enabled: true,
             ram, // Size of chip8 ram
             loaded: false,
-            start: 512
+            start: PROGRAM_START as usize
         }
     }
 
@@ -45,12 +84,36 @@ impl ChipMemory {
             0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
             0xF0, 0x80, 0xF0, 0x80, 0x80  // F
         ];
-        for i in 0..hex_chars.len() {
-            ram[i + 0x050] = hex_chars[i];
-        }
+        let big_hex_chars = [
+            0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+            0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+            0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+            0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+            0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+            0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+            0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+            0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+            0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+            0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+            0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+            0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0  // F
+        ];
+        let font = FONT_ADDR as usize;
+        let big_font = BIG_FONT_ADDR as usize;
+        ram[font..font + hex_chars.len()].copy_from_slice(&hex_chars);
+        ram[big_font..big_font + big_hex_chars.len()].copy_from_slice(&big_hex_chars);
         ram
     }
 
+    /// Size of ram in bytes
+    pub fn size(&self) -> usize {
+        self.ram.len()
+    }
+
     /// Returns true if a ROM has been loaded, false otherwise
     pub fn has_loaded(&self) -> bool {
         self.loaded
@@ -61,19 +124,91 @@ impl ChipMemory {
     /// # Arguments
     /// 
     /// * `index` - index where opcode starts
-    pub fn get_opcode(&self, index: u16) -> u16 {
-        ((self.get_byte(index) as u16) << 8) | self.get_byte(index + 1) as u16
+    pub fn get_opcode(&self, index: u16) -> MemResult<u16> {
+        let high = self.read(index as usize)?;
+        let low = self.read(index as usize + 1)?;
+        Ok(((high as u16) << 8) | low as u16)
+    }
+
+    /// Fetch and decode the instruction starting at `addr`, using the
+    /// decoded instruction cache when it is enabled
+    /// 
+    /// Fails with `OutOfRange` if the instruction runs past the end of
+    /// ram, the inner result holds the decoded instruction or the
+    /// opcode that could not be decoded.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `addr` - address of the instruction
+    pub fn fetch_instruction(&mut self, addr: u16) -> MemResult<Result<Instruction, DecodeError>> {
+        if let Some(Some(instruction)) = self.cache.get(addr as usize) {
+            return Ok(Ok(*instruction));
+        }
+        let opcode = self.get_opcode(addr)?;
+        // Only the XO-CHIP long load reads past the opcode
+        let decoded = match opcode {
+            0xF000 => Instruction::decode_long(opcode, self.get_opcode(addr.wrapping_add(2))?),
+            _ => Instruction::decode(opcode)
+        };
+        if let (true, Ok(instruction)) = (self.cache_enabled, decoded) {
+            // A long load wrapping around the end of the address space isn't cached
+            if addr as usize + instruction.size() as usize <= self.ram.len() {
+                self.cache[addr as usize] = Some(instruction);
+            }
+        }
+        Ok(decoded)
+    }
+
+    /// Enable or disable the decoded instruction cache, disabling it
+    /// also empties it
+    /// 
+    /// # Arguments
+    /// 
+    /// * `enabled` - true to cache decoded instructions
+    pub fn set_cache_enabled(&mut self, enabled: bool) {
+        self.cache_enabled = enabled;
+        if !enabled {
+            self.cache.iter_mut().for_each(|entry| *entry = None);
+        }
     }
 
-    /// Load a binary into 
+    /// Returns true if the decoded instruction cache is enabled
+    pub fn cache_enabled(&self) -> bool {
+        self.cache_enabled
+    }
+
+    /// Drop cached instructions overlapping the addresses `start..end`,
+    /// an instruction is at most 4 bytes long so it can start up to
+    /// 3 bytes before the first written address
+    fn invalidate(&mut self, start: usize, end: usize) {
+        let end = end.min(self.cache.len());
+        for entry in &mut self.cache[start.saturating_sub(3)..end] {
+            *entry = None;
+        }
+    }
+
+    /// Read a byte at any address, failing past the end of ram
+    fn read(&self, loc: usize) -> MemResult<u8> {
+        match self.ram.get(loc) {
+            Some(byte) => Ok(*byte),
+            None => Err(OutOfRange {addr: loc})
+        }
+    }
+
+    /// Load a binary into ram at the program start location, fails
+    /// without writing anything if the binary doesn't fit
     /// 
     /// # Arguments
     /// 
     /// * `rom` - a Vec<u8> holding rom contents
-    pub fn load_bytes(&mut self, rom: Vec<u8>) {
-        let len = rom.len();
-        for i in 0..len {
-            self.ram[i + self.start] = rom[i]; 
+    pub fn load_bytes(&mut self, rom: Vec<u8>) -> MemResult<()> {
+        let end = self.start + rom.len();
+        if end > self.ram.len() {
+            return Err(OutOfRange {addr: self.ram.len()});
+        }
+        self.invalidate(self.start, end);
+        for (i, byte) in rom.into_iter().enumerate() {
+            self.ram[i + self.start] = byte;
             
             // { // Flip bit order
             //     let mut switched: u8 = 0;
@@ -92,6 +227,27 @@ impl ChipMemory {
             //     switched
             // }
         }
+        Ok(())
+    }
+
+    /// Get a copy of all of ram
+    pub fn dump(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    /// Replace all of ram, fails without writing anything if the
+    /// length doesn't match the size of ram
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - new ram contents
+    pub fn restore(&mut self, bytes: &[u8]) -> MemResult<()> {
+        if bytes.len() != self.ram.len() {
+            return Err(OutOfRange {addr: bytes.len()});
+        }
+        self.ram.copy_from_slice(bytes);
+        self.invalidate(0, self.ram.len());
+        Ok(())
     }
 
     /// Set a byte in ram to a passed value
@@ -100,8 +256,15 @@ impl ChipMemory {
     /// 
     /// * `loc` - location to set
     /// * `val` - value to set with
-    pub fn set_byte(&mut self, loc: u16, val: u8) {
-        self.ram[loc as usize] = val;
+    pub fn set_byte(&mut self, loc: u16, val: u8) -> MemResult<()> {
+        match self.ram.get_mut(loc as usize) {
+            Some(byte) => {
+                *byte = val;
+                self.invalidate(loc as usize, loc as usize + 1);
+                Ok(())
+            },
+            None => Err(OutOfRange {addr: loc as usize})
+        }
     }
 
     /// Get a byte at `loc`
@@ -109,8 +272,8 @@ impl ChipMemory {
     /// # Arguments
     /// 
     /// * `loc` - location of byte
-    pub fn get_byte(&self, loc: u16) -> u8 {
-        self.ram[loc as usize]
+    pub fn get_byte(&self, loc: u16) -> MemResult<u8> {
+        self.read(loc as usize)
     }
 
     /// Get a range of bytes
@@ -119,12 +282,8 @@ impl ChipMemory {
     /// 
     /// * `loc` - start location of bytes
     /// * `nbytes` - how many bytes
-    pub fn get_nbytes(&self, loc: u16, nbytes: u16) -> Vec<u8> {
-        let mut out_bytes: Vec<u8> = vec![0; nbytes as usize];
-        for i in 0..nbytes as usize {
-            out_bytes[i] = self.get_byte(loc + i as u16);
-        }
-        out_bytes
+    pub fn get_nbytes(&self, loc: u16, nbytes: u16) -> MemResult<Vec<u8>> {
+        (0..nbytes as usize).map(|i| self.read(loc as usize + i)).collect()
     }
 
     /// Dump the Chip8 memory into the console as
@@ -136,11 +295,11 @@ impl ChipMemory {
                 print!(" ");
             }
             if i % 32 == 0 {
-                println!("");
+                println!();
             }
             print!("{:02x}", self.ram[i]);
         }
-        println!("");
+        println!();
     }
 
     /// Load a file from disk and write its bytes into 
@@ -154,14 +313,16 @@ impl ChipMemory {
         let mut file = File::open(rom_file)?;
 
         // Create vector to hold rom
-        // Capacity of 3584 is max size of Chip8 rom
-        let mut rom: Vec<u8> = Vec::with_capacity(3584);
+        // Capacity of the ram past the program start is the max size of the rom
+        let mut rom: Vec<u8> = Vec::with_capacity(self.ram.len() - self.start);
 
         // Read rom into vector
         file.read_to_end(&mut rom)?;
 
         // Load bytes into chip8 ram
-        self.load_bytes(rom);
+        if self.load_bytes(rom).is_err() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "ROM is too large for ram"));
+        }
         self.loaded = true;
         Ok(())
     }
@@ -0,0 +1,379 @@
+// Standard Library Modules //
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Local Modules Use //
+use crate::binary::{self, crc32, FormatError, Reader};
+use crate::memory::OutOfRange;
+use crate::quirks::Quirks;
+use crate::timing::TimingMode;
+use crate::{ChipSystem, ExResult, Frame, Speed};
+
+/// First four bytes of every movie
+pub const MAGIC: [u8; 4] = *b"C8MV";
+/// Major version of the format, movies with another major version
+/// can't be played
+pub const FORMAT_MAJOR: u16 = 1;
+/// Minor version of the format, raised when header fields are added
+pub const FORMAT_MINOR: u16 = 0;
+
+/// Error returned when a movie can't be read or played
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieError {
+    /// The data doesn't start with the movie magic
+    BadMagic,
+    /// The movie was written with an incompatible major version
+    UnsupportedVersion { major: u16, minor: u16 },
+    /// The checksum doesn't match, the data is corrupted
+    Checksum,
+    /// The data ends in the middle of the header or an event
+    Truncated,
+    /// A header field or event holds a value that isn't valid
+    Invalid(&'static str),
+    /// The ROM isn't the one the movie was recorded with
+    RomMismatch { expected: u32, found: u32 },
+    /// The ROM doesn't fit in memory
+    Memory(OutOfRange),
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::BadMagic => write!(f, "not a movie"),
+            MovieError::UnsupportedVersion { major, minor } =>
+                write!(f, "unsupported movie version {}.{}", major, minor),
+            MovieError::Checksum => write!(f, "movie checksum mismatch"),
+            MovieError::Truncated => write!(f, "movie is truncated"),
+            MovieError::Invalid(field) => write!(f, "movie has an invalid {}", field),
+            MovieError::RomMismatch { expected, found } =>
+                write!(f, "movie was recorded with ROM {:08x}, this ROM is {:08x}", expected, found),
+            MovieError::Memory(e) => write!(f, "ROM doesn't fit in memory at {:#x}", e.addr),
+        }
+    }
+}
+
+impl error::Error for MovieError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+impl From<FormatError> for MovieError {
+    fn from(e: FormatError) -> Self {
+        match e {
+            FormatError::BadMagic => MovieError::BadMagic,
+            FormatError::UnsupportedVersion { major, minor } => MovieError::UnsupportedVersion { major, minor },
+            FormatError::Checksum => MovieError::Checksum,
+            FormatError::Truncated => MovieError::Truncated,
+        }
+    }
+}
+
+/// A key press or release, applied before the frame it is tagged with runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// Number of frames run before the event
+    pub frame: u64,
+    /// The key (0-15)
+    pub key: u8,
+    /// True for a press, false for a release
+    pub pressed: bool
+}
+
+/// A recording of a run: everything needed to set up the system and
+/// every key event, tagged with its frame
+///
+/// Replaying the events into a system built from the same ROM, seed,
+/// quirks, speed and timing reproduces the run exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    /// CRC-32 of the ROM
+    pub rom_hash: u32,
+    /// Seed of the random generator used by `Cxkk`
+    pub seed: u64,
+    /// Quirks the ROM ran with
+    pub quirks: Quirks,
+    /// Emulation speed
+    pub speed: Speed,
+    /// How instructions were paced against the timers
+    pub timing: TimingMode,
+    /// Number of frames the recording lasts
+    pub frames: u64,
+    /// Key events in the order they happened
+    pub events: Vec<KeyEvent>
+}
+
+impl Movie {
+    /// Build the system at power on for this movie, with the ROM loaded
+    ///
+    /// # Arguments
+    ///
+    /// * `rom` - the ROM the movie was recorded with
+    pub fn system(&self, rom: Vec<u8>) -> Result<ChipSystem, MovieError> {
+        let found = crc32(&rom);
+        if found != self.rom_hash {
+            return Err(MovieError::RomMismatch {expected: self.rom_hash, found});
+        }
+        let mut system = ChipSystem::init_with_quirks(self.quirks);
+        system.seed_rng(self.seed);
+        system.set_speed(self.speed);
+        system.set_timing(self.timing);
+        system.load_rom(rom).map_err(MovieError::Memory)?;
+        Ok(system)
+    }
+
+    /// Encode the movie
+    ///
+    /// The format is little endian:
+    ///
+    /// * 4 bytes magic `C8MV`
+    /// * 2 bytes major and 2 bytes minor format version
+    /// * 4 byte header length, then the header: ROM CRC-32 (4), seed (8),
+    ///   speed kind and value (1 + 4), timing mode (1), frame count (8),
+    ///   quirks length (1) and quirks. Later minor versions append fields.
+    /// * 4 byte event count, then the events: frame (8) and a byte with
+    ///   the key in the low nibble and bit 7 set for a press
+    /// * CRC-32 of everything before it
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut header = self.rom_hash.to_le_bytes().to_vec();
+        header.extend(self.seed.to_le_bytes());
+        let (kind, value) = match self.speed {
+            Speed::InstructionsPerFrame(n) => (0, n),
+            Speed::InstructionsPerSecond(n) => (1, n)
+        };
+        header.push(kind);
+        header.extend(value.to_le_bytes());
+        header.push(match self.timing {
+            TimingMode::Instructions => 0,
+            TimingMode::Vip => 1
+        });
+        header.extend(self.frames.to_le_bytes());
+        let quirks = self.quirks.to_bytes();
+        header.push(quirks.len() as u8);
+        header.extend(quirks);
+
+        let mut out = binary::header(MAGIC, FORMAT_MAJOR, FORMAT_MINOR);
+        out.extend((header.len() as u32).to_le_bytes());
+        out.extend(header);
+        out.extend((self.events.len() as u32).to_le_bytes());
+        for event in &self.events {
+            out.extend(event.frame.to_le_bytes());
+            out.push(event.key | ((event.pressed as u8) << 7));
+        }
+        binary::seal(&mut out);
+        out
+    }
+
+    /// Decode a movie written by `to_bytes`, key events have to be in
+    /// frame order
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - the encoded movie
+    pub fn from_bytes(data: &[u8]) -> Result<Self, MovieError> {
+        let (mut cursor, _) = binary::open(data, MAGIC, FORMAT_MAJOR)?;
+
+        let header_len = cursor.uint(4)? as usize;
+        let mut header = Reader::init(cursor.take(header_len)?);
+        let rom_hash = header.uint(4)? as u32;
+        let seed = header.uint(8)?;
+        let speed = match (header.uint(1)?, header.uint(4)? as u32) {
+            (0, n) => Speed::InstructionsPerFrame(n),
+            (1, n) => Speed::InstructionsPerSecond(n),
+            _ => return Err(MovieError::Invalid("speed"))
+        };
+        let timing = match header.uint(1)? {
+            0 => TimingMode::Instructions,
+            1 => TimingMode::Vip,
+            _ => return Err(MovieError::Invalid("timing mode"))
+        };
+        let frames = header.uint(8)?;
+        let quirks_len = header.uint(1)? as usize;
+        let quirks = Quirks::from_bytes(header.take(quirks_len)?).ok_or(MovieError::Invalid("quirks"))?;
+
+        let count = cursor.uint(4)?;
+        let mut events = Vec::new();
+        for _ in 0..count {
+            let frame = cursor.uint(8)?;
+            let byte = cursor.uint(1)? as u8;
+            // Events are played in order, a frame before the last one would never be reached
+            let earlier = events.last().is_some_and(|last: &KeyEvent| frame < last.frame);
+            if byte & 0x70 != 0 || earlier {
+                return Err(MovieError::Invalid("key event"));
+            }
+            events.push(KeyEvent {frame, key: byte & 0xF, pressed: byte & 0x80 != 0});
+        }
+        Ok(Movie {rom_hash, seed, quirks, speed, timing, frames, events})
+    }
+
+    /// Write the movie to a file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the file to write
+    pub fn save_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    /// Read a movie from a file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the file to read
+    pub fn load_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        Movie::from_bytes(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Records a run into a `Movie`
+///
+/// Keys have to be pressed and released through the recorder, and
+/// frames run through it, for them to be recorded. Set the speed and
+/// timing of `system` before the first frame.
+pub struct Recorder {
+    /// The system being recorded
+    pub system: ChipSystem,
+    /// The recording so far
+    movie: Movie
+}
+
+impl Recorder {
+    /// Start recording a ROM from power on
+    ///
+    /// # Arguments
+    ///
+    /// * `rom` - the ROM to run
+    /// * `quirks` - quirks to run it with
+    /// * `seed` - seed for the random generator
+    pub fn init(rom: Vec<u8>, quirks: Quirks, seed: u64) -> Result<Self, MovieError> {
+        let movie = Movie {
+            rom_hash: crc32(&rom),
+            seed,
+            quirks,
+            speed: Speed::default(),
+            timing: TimingMode::Instructions,
+            frames: 0,
+            events: Vec::new()
+        };
+        let system = movie.system(rom)?;
+        Ok(Recorder {system, movie})
+    }
+
+    /// Number of frames recorded so far
+    pub fn frame(&self) -> u64 {
+        self.movie.frames
+    }
+
+    /// Press a key (0-15) and record it, keys above 15 are ignored
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - which key was pressed
+    pub fn press(&mut self, key: u8) {
+        self.record(key, true);
+    }
+
+    /// Release a key (0-15) and record it, keys above 15 are ignored
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - which key was released
+    pub fn release(&mut self, key: u8) {
+        self.record(key, false);
+    }
+
+    /// Run and record a frame
+    pub fn run_frame(&mut self) -> ExResult<Frame> {
+        if self.movie.frames == 0 {
+            self.movie.speed = self.system.speed();
+            self.movie.timing = self.system.timing();
+        }
+        self.movie.frames += 1;
+        self.system.run_frame()
+    }
+
+    /// Stop recording and get the movie
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+
+    /// Apply a key event and add it to the movie
+    fn record(&mut self, key: u8, pressed: bool) {
+        if key > 0xF {
+            return;
+        }
+        match pressed {
+            true => self.system.press(key),
+            false => self.system.release(key)
+        }
+        self.movie.events.push(KeyEvent {frame: self.movie.frames, key, pressed});
+    }
+}
+
+/// Plays a `Movie` back, feeding its key events to the system
+pub struct Player {
+    /// The system playing the movie
+    pub system: ChipSystem,
+    /// The movie being played
+    movie: Movie,
+    /// Index of the next event to apply
+    next: usize,
+    /// Number of frames run
+    frame: u64
+}
+
+impl Player {
+    /// Set up a system to play a movie
+    ///
+    /// # Arguments
+    ///
+    /// * `movie` - the movie to play
+    /// * `rom` - the ROM it was recorded with
+    pub fn init(movie: Movie, rom: Vec<u8>) -> Result<Self, MovieError> {
+        let system = movie.system(rom)?;
+        Ok(Player {system, movie, next: 0, frame: 0})
+    }
+
+    /// Get the movie being played
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    /// Number of frames run so far
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Returns true once every recorded frame has run
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.frames
+    }
+
+    /// Apply the key events for the next frame and run it
+    ///
+    /// Frames can be run past the end of the movie, no more keys
+    /// change then.
+    pub fn run_frame(&mut self) -> ExResult<Frame> {
+        while let Some(event) = self.movie.events.get(self.next).filter(|e| e.frame <= self.frame) {
+            match event.pressed {
+                true => self.system.press(event.key),
+                false => self.system.release(event.key)
+            }
+            self.next += 1;
+        }
+        self.frame += 1;
+        self.system.run_frame()
+    }
+
+    /// Play the rest of the movie, returns the first error
+    pub fn run_to_end(&mut self) -> ExResult<()> {
+        while !self.is_finished() {
+            self.run_frame()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,940 @@
+// Standard Library Modules //
+use std::collections::{BTreeMap, HashMap};
+
+// Local Modules Use //
+use crate::asm::{AsmError, AsmResult, Assembly};
+use crate::instruction::Instruction;
+use crate::memory::PROGRAM_START;
+
+/// Most macro expansions in one program, catches macros expanding themselves
+const MAX_EXPANSIONS: usize = 100_000;
+
+/// Compile Octo source into a ROM image loaded at the program start
+/// location
+///
+/// Supports labels (`: name`), `:const`, `:alias`, `:macro`, `:calc`,
+/// `:unpack`, `:next`, `:org`, `:byte`, `:call`, `loop`/`while`/`again`,
+/// `if ... then` and `if ... begin ... else ... end` (including the
+/// `<`, `>`, `<=` and `>=` comparisons, which use `vf`), sprite and
+/// `i :=` forms and the SUPER-CHIP/XO-CHIP statements. As in Octo, the
+/// image starts with a jump to the `main` label and any other name in
+/// statement position calls the label of that name.
+///
+/// # Arguments
+///
+/// * `source` - Octo source
+pub fn compile(source: &str) -> AsmResult<Assembly> {
+    let mut compiler = Compiler::init(tokenize(source));
+    compiler.run()?;
+    compiler.finish()
+}
+
+/// A whitespace separated word of the source
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+    column: usize
+}
+
+/// Split source into words, dropping `#` comments
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (n, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut column = 0;
+        for word in line.split_whitespace() {
+            // Columns count characters, find the word after the last one
+            let offset = line[column..].find(word).unwrap_or(0) + column;
+            tokens.push(Token {text: word.to_string(), line: n + 1, column: line[..offset].chars().count() + 1});
+            column = offset + word.len();
+        }
+    }
+    tokens
+}
+
+/// How a reference to a label is patched once the label is known
+#[derive(Debug, Clone, Copy)]
+enum Patch {
+    /// The low 12 bits of the word at the address (`jump`, `i :=`, ...)
+    Nnn,
+    /// The 16 bit word after the `F000` at the address (`i := long`)
+    Long,
+    /// The bytes of `v0 := ..` and `v1 := ..` from `:unpack`, with the
+    /// nibble to put above the address, None for `long`
+    Unpack(Option<u8>)
+}
+
+/// A forward reference to a label
+struct Fixup {
+    addr: u16,
+    label: Token,
+    patch: Patch
+}
+
+/// Comparison in a condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Key,
+    NotKey
+}
+
+/// Right hand side of a comparison or an assignment
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Reg(u8),
+    Imm(u8)
+}
+
+/// A parsed condition, `vx <compare> [rhs]`
+struct Condition {
+    /// Instructions computing the flag for ordering comparisons
+    setup: Vec<Instruction>,
+    x: u8,
+    compare: Compare,
+    rhs: Option<Operand>
+}
+
+/// An open `if ... begin` or `else` waiting for its end
+struct Branch {
+    /// Address of the jump to patch
+    jump: u16,
+    token: Token
+}
+
+/// An open `loop` waiting for `again`
+struct Loop {
+    start: u16,
+    /// Jumps out of the loop from `while`
+    exits: Vec<u16>,
+    token: Token
+}
+
+/// A `:macro` definition
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>
+}
+
+/// Compiler state
+struct Compiler {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Image from the program start location
+    rom: Vec<u8>,
+    /// Address of the next byte
+    here: u16,
+    labels: BTreeMap<String, u16>,
+    consts: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    branches: Vec<Branch>,
+    loops: Vec<Loop>,
+    /// Label from `:next` for the operand of the next instruction
+    next: Option<Token>,
+    expansions: usize
+}
+
+/// Statement words that can't be label names
+const KEYWORDS: [&str; 46] = [
+    ":=", "+=", "-=", "=-", "|=", "&=", "^=", ">>=", "<<=", "==", "!=", "<", ">", "<=", ">=",
+    "key", "-key", "hex", "bighex", "random", "delay", "buzzer", "long", "clear", "return", ";",
+    "bcd", "save", "load", "sprite", "jump", "jump0", "native", "loop", "again", "while", "if",
+    "then", "begin", "else", "end", "hires", "lores", "exit", "audio", "pitch"
+];
+
+/// Parse an Octo number literal (decimal, `0x` hex or `0b` binary,
+/// optionally negative)
+fn parse_number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text)
+    };
+    let value = match (digits.strip_prefix("0x"), digits.strip_prefix("0b")) {
+        (Some(hex), _) => i64::from_str_radix(hex, 16).ok()?,
+        (_, Some(bin)) => i64::from_str_radix(bin, 2).ok()?,
+        _ if digits.starts_with(|c: char| c.is_ascii_digit()) => return digits.parse::<f64>().ok().map(|v| if negative { -v } else { v }),
+        _ => return None
+    };
+    Some(if negative { -(value as f64) } else { value as f64 })
+}
+
+impl Compiler {
+    /// Create a compiler for a token stream
+    fn init(tokens: Vec<Token>) -> Self {
+        Compiler {
+            tokens,
+            pos: 0,
+            rom: Vec::new(),
+            here: PROGRAM_START,
+            labels: BTreeMap::new(),
+            consts: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            branches: Vec::new(),
+            loops: Vec::new(),
+            next: None,
+            expansions: 0
+        }
+    }
+
+    /// Build an error at a token
+    fn error(token: &Token, message: String) -> AsmError {
+        AsmError {
+            file: String::from("<input>"),
+            line: token.line,
+            column: token.column,
+            message
+        }
+    }
+
+    /// Error for running out of tokens
+    fn end_error(&self, expected: &str) -> AsmError {
+        let (line, column) = self.tokens.last().map_or((0, 0), |token| (token.line, token.column + token.text.chars().count()));
+        AsmError {
+            file: String::from("<input>"),
+            line,
+            column,
+            message: format!("expected {}, found end of file", expected)
+        }
+    }
+
+    /// Take the next token
+    fn next_token(&mut self, expected: &str) -> AsmResult<Token> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| self.end_error(expected))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    /// Take the next token, which must be `text`
+    fn expect(&mut self, text: &str) -> AsmResult<()> {
+        let token = self.next_token(&format!("`{}`", text))?;
+        match token.text == text {
+            true => Ok(()),
+            false => Err(Compiler::error(&token, format!("expected `{}`, found `{}`", text, token.text)))
+        }
+    }
+
+    /// Returns true if the next token is `text`, taking it if so
+    fn accept(&mut self, text: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(token) if token.text == text => {
+                self.pos += 1;
+                true
+            },
+            _ => false
+        }
+    }
+
+    /// Register named by a token (`v0`-`vf` or an alias)
+    fn register_of(&self, token: &Token) -> Option<u8> {
+        if let Some(&x) = self.aliases.get(&token.text) {
+            return Some(x);
+        }
+        let mut chars = token.text.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some('v'), Some(x), None) | (Some('V'), Some(x), None) => x.to_digit(16).map(|x| x as u8),
+            _ => None
+        }
+    }
+
+    /// Take a register
+    fn register(&mut self) -> AsmResult<u8> {
+        let token = self.next_token("a register")?;
+        self.register_of(&token).ok_or_else(|| Compiler::error(&token, format!("expected a register, found `{}`", token.text)))
+    }
+
+    /// Numeric value of a token: a number, constant or defined label
+    fn number_of(&self, token: &Token) -> Option<f64> {
+        parse_number(&token.text)
+            .or_else(|| self.consts.get(&token.text).copied())
+            .or_else(|| self.labels.get(&token.text).map(|&addr| addr as f64))
+    }
+
+    /// Take a value that has to fit in `min..=max`
+    fn value(&mut self, min: i64, max: i64, what: &str) -> AsmResult<i64> {
+        let token = self.next_token(what)?;
+        let value = self.number_of(&token)
+            .ok_or_else(|| Compiler::error(&token, format!("expected {}, found `{}`", what, token.text)))? as i64;
+        match (min..=max).contains(&value) {
+            true => Ok(value),
+            false => Err(Compiler::error(&token, format!("{} doesn't fit in {}", value, what)))
+        }
+    }
+
+    /// Take a byte value, negative values wrap
+    fn byte(&mut self) -> AsmResult<u8> {
+        self.value(-128, 0xFF, "a byte").map(|value| value as u8)
+    }
+
+    /// Take a 4 bit value
+    fn nibble(&mut self) -> AsmResult<u8> {
+        self.value(0, 0xF, "a nibble").map(|value| value as u8)
+    }
+
+    /// Take a register or a byte value
+    fn operand(&mut self) -> AsmResult<Operand> {
+        let reg = self.tokens.get(self.pos).and_then(|token| self.register_of(token));
+        match reg {
+            Some(x) => {
+                self.pos += 1;
+                Ok(Operand::Reg(x))
+            },
+            None => self.byte().map(Operand::Imm)
+        }
+    }
+
+    /// Write a byte at the current address
+    fn emit_byte(&mut self, byte: u8) -> AsmResult<()> {
+        let index = (self.here - PROGRAM_START) as usize;
+        if index >= self.rom.len() {
+            self.rom.resize(index + 1, 0);
+        }
+        self.rom[index] = byte;
+        self.here = self.here.checked_add(1).ok_or_else(|| {
+            let token = &self.tokens[self.pos.saturating_sub(1)];
+            Compiler::error(token, String::from("program runs past the end of the address space"))
+        })?;
+        Ok(())
+    }
+
+    /// Write an instruction, defining a pending `:next` label
+    fn emit(&mut self, instruction: Instruction) -> AsmResult<()> {
+        if let Some(token) = self.next.take() {
+            self.define_label(&token, self.here.wrapping_add(1))?;
+        }
+        for byte in instruction.to_bytes() {
+            self.emit_byte(byte)?;
+        }
+        Ok(())
+    }
+
+    /// Write an instruction whose address is patched once `label` is
+    /// known (right away if it already is)
+    fn emit_ref(&mut self, instruction: Instruction, label: Token, patch: Patch) -> AsmResult<()> {
+        let addr = self.here;
+        self.emit(instruction)?;
+        self.fixups.push(Fixup {addr, label, patch});
+        Ok(())
+    }
+
+    /// Define a label, names can only be defined once
+    fn define_label(&mut self, token: &Token, addr: u16) -> AsmResult<()> {
+        if KEYWORDS.contains(&token.text.as_str()) || self.register_of(token).is_some() || parse_number(&token.text).is_some() {
+            return Err(Compiler::error(token, format!("`{}` can't be used as a name", token.text)));
+        }
+        if self.labels.insert(token.text.clone(), addr).is_some() || self.consts.contains_key(&token.text) {
+            return Err(Compiler::error(token, format!("`{}` is already defined", token.text)));
+        }
+        Ok(())
+    }
+
+    /// Compile every statement
+    fn run(&mut self) -> AsmResult<()> {
+        // Execution starts with a jump to main
+        let main = Token {text: String::from("main"), line: 0, column: 0};
+        self.emit_ref(Instruction::Jp(0), main, Patch::Nnn)?;
+        while self.pos < self.tokens.len() {
+            self.statement()?;
+        }
+        if let Some(branch) = self.branches.last() {
+            return Err(Compiler::error(&branch.token, String::from("`begin` without `end`")));
+        }
+        if let Some(open) = self.loops.last() {
+            return Err(Compiler::error(&open.token, String::from("`loop` without `again`")));
+        }
+        if let Some(token) = &self.next {
+            return Err(Compiler::error(token, String::from("`:next` without a following instruction")));
+        }
+        Ok(())
+    }
+
+    /// Compile a statement
+    fn statement(&mut self) -> AsmResult<()> {
+        use self::Instruction::*;
+        let token = self.next_token("a statement")?;
+        if let Some(x) = self.register_of(&token) {
+            return self.register_statement(x);
+        }
+        if let Some(value) = parse_number(&token.text) {
+            return match (-128.0..=255.0).contains(&value) {
+                true => self.emit_byte(value as i64 as u8),
+                false => Err(Compiler::error(&token, format!("{} doesn't fit in a byte", value)))
+            };
+        }
+        match token.text.as_str() {
+            ":" => {
+                let name = self.next_token("a label name")?;
+                self.define_label(&name, self.here)?;
+            },
+            ":const" => {
+                let name = self.next_token("a constant name")?;
+                let value = self.next_token("a value")?;
+                let value = self.number_of(&value)
+                    .ok_or_else(|| Compiler::error(&value, format!("expected a value, found `{}`", value.text)))?;
+                self.define_const(&name, value)?;
+            },
+            ":alias" => {
+                let name = self.next_token("an alias name")?;
+                let x = self.register()?;
+                self.aliases.insert(name.text, x);
+            },
+            ":macro" => self.define_macro()?,
+            ":calc" => {
+                let name = self.next_token("a constant name")?;
+                let value = self.calc_block()?;
+                self.define_const(&name, value)?;
+            },
+            ":unpack" => {
+                let nibble = match self.accept("long") {
+                    true => None,
+                    false => Some(self.nibble()?)
+                };
+                let label = self.next_token("a label")?;
+                let addr = self.here;
+                self.emit(Ld(0, 0))?;
+                self.emit(Ld(1, 0))?;
+                self.fixups.push(Fixup {addr, label, patch: Patch::Unpack(nibble)});
+            },
+            ":next" => {
+                let name = self.next_token("a label name")?;
+                self.next = Some(name);
+            },
+            ":org" => {
+                let addr = match self.peek_is("{") {
+                    true => self.calc_block()? as i64,
+                    false => self.value(PROGRAM_START as i64, 0xFFFF, "an address")?
+                };
+                if addr < PROGRAM_START as i64 || addr > 0xFFFF {
+                    return Err(Compiler::error(&token, format!("`:org` address {} is out of range", addr)));
+                }
+                self.here = addr as u16;
+            },
+            ":byte" => {
+                let byte = match self.peek_is("{") {
+                    true => self.calc_block()? as i64 as u8,
+                    false => self.byte()?
+                };
+                self.emit_byte(byte)?;
+            },
+            ":call" => {
+                let label = self.next_token("a label")?;
+                self.emit_ref(Call(0), label, Patch::Nnn)?;
+            },
+            "clear" => self.emit(Cls)?,
+            "return" | ";" => self.emit(Ret)?,
+            "hires" => self.emit(High)?,
+            "lores" => self.emit(Low)?,
+            "exit" => self.emit(Exit)?,
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(Scd(n))?;
+            },
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit(Scu(n))?;
+            },
+            "scroll-left" => self.emit(Scl)?,
+            "scroll-right" => self.emit(Scr)?,
+            "audio" => self.emit(Audio)?,
+            "plane" => {
+                let n = self.nibble()?;
+                self.emit(Plane(n))?;
+            },
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(LdB(x))?;
+            },
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit(StoreFlags(x))?;
+            },
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit(LoadFlags(x))?;
+            },
+            "save" | "load" => {
+                let x = self.register()?;
+                let range = match self.accept("-") {
+                    true => Some(self.register()?),
+                    false => None
+                };
+                let instruction = match (token.text == "save", range) {
+                    (true, None) => StoreRegs(x),
+                    (true, Some(y)) => StoreRange(x, y),
+                    (false, None) => LoadRegs(x),
+                    (false, Some(y)) => LoadRange(x, y)
+                };
+                self.emit(instruction)?;
+            },
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(Drw(x, y, n))?;
+            },
+            "jump" | "jump0" | "native" => {
+                let label = self.next_token("a label")?;
+                let instruction = match token.text.as_str() {
+                    "jump" => Jp(0),
+                    "jump0" => JpV0(0),
+                    _ => Sys(0)
+                };
+                self.emit_ref(instruction, label, Patch::Nnn)?;
+            },
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let instruction = match token.text.as_str() {
+                    "delay" => LdDt(x),
+                    "buzzer" => LdSt(x),
+                    _ => Pitch(x)
+                };
+                self.emit(instruction)?;
+            },
+            "i" => self.i_statement()?,
+            "loop" => self.loops.push(Loop {start: self.here, exits: Vec::new(), token}),
+            "while" => {
+                if self.loops.is_empty() {
+                    return Err(Compiler::error(&token, String::from("`while` outside of a loop")));
+                }
+                self.condition(true)?;
+                let jump = self.here;
+                self.emit(Jp(0))?;
+                if let Some(open) = self.loops.last_mut() {
+                    open.exits.push(jump);
+                }
+            },
+            "again" => {
+                let open = self.loops.pop().ok_or_else(|| Compiler::error(&token, String::from("`again` without `loop`")))?;
+                self.emit(Jp(open.start))?;
+                for exit in open.exits {
+                    self.patch(exit, self.here, Patch::Nnn, &token)?;
+                }
+            },
+            "if" => {
+                let condition = self.parse_condition()?;
+                let form = self.next_token("`then` or `begin`")?;
+                match form.text.as_str() {
+                    "then" => self.emit_condition(&condition, false)?,
+                    "begin" => {
+                        self.emit_condition(&condition, true)?;
+                        let jump = self.here;
+                        self.emit(Jp(0))?;
+                        self.branches.push(Branch {jump, token});
+                    },
+                    _ => return Err(Compiler::error(&form, format!("expected `then` or `begin`, found `{}`", form.text)))
+                }
+            },
+            "else" => {
+                let branch = self.branches.pop().ok_or_else(|| Compiler::error(&token, String::from("`else` without `begin`")))?;
+                let jump = self.here;
+                self.emit(Jp(0))?;
+                self.patch(branch.jump, self.here, Patch::Nnn, &token)?;
+                self.branches.push(Branch {jump, token});
+            },
+            "end" => {
+                let branch = self.branches.pop().ok_or_else(|| Compiler::error(&token, String::from("`end` without `begin`")))?;
+                self.patch(branch.jump, self.here, Patch::Nnn, &token)?;
+            },
+            name if self.macros.contains_key(name) => self.expand_macro(&token)?,
+            name if name.starts_with(':') => {
+                return Err(Compiler::error(&token, format!("unsupported directive `{}`", name)));
+            },
+            name if KEYWORDS.contains(&name) => {
+                return Err(Compiler::error(&token, format!("unexpected `{}`", name)));
+            },
+            // Any other name calls the label
+            _ => self.emit_ref(Call(0), token, Patch::Nnn)?
+        }
+        Ok(())
+    }
+
+    /// Returns true if the next token is `text`
+    fn peek_is(&self, text: &str) -> bool {
+        self.tokens.get(self.pos).is_some_and(|token| token.text == text)
+    }
+
+    /// Define a constant
+    fn define_const(&mut self, name: &Token, value: f64) -> AsmResult<()> {
+        if KEYWORDS.contains(&name.text.as_str()) || self.register_of(name).is_some() || parse_number(&name.text).is_some() {
+            return Err(Compiler::error(name, format!("`{}` can't be used as a name", name.text)));
+        }
+        if self.labels.contains_key(&name.text) {
+            return Err(Compiler::error(name, format!("`{}` is already defined", name.text)));
+        }
+        self.consts.insert(name.text.clone(), value);
+        Ok(())
+    }
+
+    /// Compile `vx <op> <rhs>`
+    fn register_statement(&mut self, x: u8) -> AsmResult<()> {
+        use self::Instruction::*;
+        let op = self.next_token("an operator")?;
+        let instruction = match op.text.as_str() {
+            ":=" => {
+                if self.accept("delay") {
+                    LdVxDt(x)
+                } else if self.accept("key") {
+                    LdVxK(x)
+                } else if self.accept("random") {
+                    Rnd(x, self.byte()?)
+                } else {
+                    match self.operand()? {
+                        Operand::Reg(y) => LdReg(x, y),
+                        Operand::Imm(kk) => Ld(x, kk)
+                    }
+                }
+            },
+            "+=" => match self.operand()? {
+                Operand::Reg(y) => AddReg(x, y),
+                Operand::Imm(kk) => Add(x, kk)
+            },
+            "-=" => match self.operand()? {
+                Operand::Reg(y) => Sub(x, y),
+                Operand::Imm(kk) => Add(x, kk.wrapping_neg())
+            },
+            "=-" => Subn(x, self.register()?),
+            "|=" => Or(x, self.register()?),
+            "&=" => And(x, self.register()?),
+            "^=" => Xor(x, self.register()?),
+            ">>=" => Shr(x, self.register()?),
+            "<<=" => Shl(x, self.register()?),
+            _ => return Err(Compiler::error(&op, format!("expected an operator, found `{}`", op.text)))
+        };
+        self.emit(instruction)
+    }
+
+    /// Compile `i := ...` and `i += vx`
+    fn i_statement(&mut self) -> AsmResult<()> {
+        use self::Instruction::*;
+        let op = self.next_token("`:=` or `+=`")?;
+        match op.text.as_str() {
+            "+=" => {
+                let x = self.register()?;
+                self.emit(AddI(x))
+            },
+            ":=" => {
+                if self.accept("hex") {
+                    let x = self.register()?;
+                    return self.emit(LdF(x));
+                }
+                if self.accept("bighex") {
+                    let x = self.register()?;
+                    return self.emit(LdHf(x));
+                }
+                let long = self.accept("long");
+                let target = self.next_token("an address")?;
+                match (parse_number(&target.text).or_else(|| self.consts.get(&target.text).copied()), long) {
+                    (Some(addr), false) if (0.0..=4095.0).contains(&addr) => self.emit(LdI(addr as u16)),
+                    (Some(addr), true) if (0.0..=65535.0).contains(&addr) => self.emit(LdILong(addr as u16)),
+                    (Some(addr), _) => Err(Compiler::error(&target, format!("address {} is out of range", addr))),
+                    (None, false) => self.emit_ref(LdI(0), target, Patch::Nnn),
+                    (None, true) => self.emit_ref(LdILong(0), target, Patch::Long)
+                }
+            },
+            _ => Err(Compiler::error(&op, format!("expected `:=` or `+=`, found `{}`", op.text)))
+        }
+    }
+
+    /// Parse `vx <compare> [rhs]`
+    fn parse_condition(&mut self) -> AsmResult<Condition> {
+        let x = self.register()?;
+        let op = self.next_token("a comparison")?;
+        let compare = match op.text.as_str() {
+            "==" => Compare::Eq,
+            "!=" => Compare::Ne,
+            "<" => Compare::Lt,
+            ">" => Compare::Gt,
+            "<=" => Compare::Le,
+            ">=" => Compare::Ge,
+            "key" => Compare::Key,
+            "-key" => Compare::NotKey,
+            _ => return Err(Compiler::error(&op, format!("expected a comparison, found `{}`", op.text)))
+        };
+        let rhs = match compare {
+            Compare::Key | Compare::NotKey => None,
+            _ => Some(self.operand()?)
+        };
+        // Ordering comparisons subtract into vf and test the borrow flag
+        let setup = match (compare, rhs) {
+            (Compare::Lt, Some(rhs)) | (Compare::Ge, Some(rhs)) | (Compare::Gt, Some(rhs)) | (Compare::Le, Some(rhs)) => {
+                let load = match rhs {
+                    Operand::Reg(y) => Instruction::LdReg(0xF, y),
+                    Operand::Imm(kk) => Instruction::Ld(0xF, kk)
+                };
+                let subtract = match compare {
+                    // vf = x >= rhs
+                    Compare::Lt | Compare::Ge => Instruction::Subn(0xF, x),
+                    // vf = rhs >= x
+                    _ => Instruction::Sub(0xF, x)
+                };
+                vec![load, subtract]
+            },
+            _ => Vec::new()
+        };
+        Ok(Condition {setup, x, compare, rhs})
+    }
+
+    /// Compile a condition, the emitted skip skips the next instruction
+    /// when the condition is false (or true when `negate` is set)
+    fn condition(&mut self, negate: bool) -> AsmResult<()> {
+        let condition = self.parse_condition()?;
+        self.emit_condition(&condition, negate)
+    }
+
+    /// Emit the setup and skip instruction of a parsed condition
+    fn emit_condition(&mut self, condition: &Condition, negate: bool) -> AsmResult<()> {
+        use self::Instruction::*;
+        for instruction in &condition.setup {
+            self.emit(*instruction)?;
+        }
+        let (x, compare, rhs) = (condition.x, condition.compare, condition.rhs);
+        // Each comparison is a test that skips on a match, and whether the
+        // condition holds on a match or on a mismatch
+        let (test, holds_on_match) = match (compare, rhs) {
+            (Compare::Key, _) => (Skp(x), true),
+            (Compare::NotKey, _) => (Skp(x), false),
+            (Compare::Eq, Some(Operand::Reg(y))) => (SeReg(x, y), true),
+            (Compare::Ne, Some(Operand::Reg(y))) => (SeReg(x, y), false),
+            (Compare::Eq, Some(Operand::Imm(kk))) => (Se(x, kk), true),
+            (Compare::Ne, Some(Operand::Imm(kk))) => (Se(x, kk), false),
+            // Lt and Gt hold when the flag is 0, Le and Ge when it is 1
+            (Compare::Lt, _) | (Compare::Gt, _) => (Se(0xF, 0), true),
+            _ => (Se(0xF, 1), true)
+        };
+        // Skip the next instruction when the condition is false, or true when negated
+        let instruction = match holds_on_match == negate {
+            true => test,
+            false => match test {
+                Skp(x) => Sknp(x),
+                SeReg(x, y) => SneReg(x, y),
+                Se(x, kk) => Sne(x, kk),
+                other => other
+            }
+        };
+        self.emit(instruction)
+    }
+
+    /// Read `:macro name args { body }`
+    fn define_macro(&mut self) -> AsmResult<()> {
+        let name = self.next_token("a macro name")?;
+        let mut args = Vec::new();
+        loop {
+            let token = self.next_token("`{`")?;
+            if token.text == "{" {
+                break;
+            }
+            args.push(token.text);
+        }
+        let body = self.block()?;
+        self.macros.insert(name.text, Macro {args, body});
+        Ok(())
+    }
+
+    /// Read tokens up to the `}` matching an already taken `{`
+    fn block(&mut self) -> AsmResult<Vec<Token>> {
+        let mut depth = 1;
+        let mut body = Vec::new();
+        loop {
+            let token = self.next_token("`}`")?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(body);
+                    }
+                },
+                _ => {}
+            }
+            body.push(token);
+        }
+    }
+
+    /// Replace a macro invocation with its body
+    fn expand_macro(&mut self, token: &Token) -> AsmResult<()> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(Compiler::error(token, String::from("too many macro expansions, is a macro expanding itself?")));
+        }
+        let (arg_names, body) = match self.macros.get(&token.text) {
+            Some(definition) => (definition.args.clone(), definition.body.clone()),
+            None => return Err(Compiler::error(token, format!("unknown macro `{}`", token.text)))
+        };
+        let mut args = HashMap::new();
+        for name in arg_names {
+            let value = self.next_token(&format!("macro argument `{}`", name))?;
+            args.insert(name, value.text);
+        }
+        let expanded: Vec<Token> = body
+            .into_iter()
+            .map(|body_token| match args.get(&body_token.text) {
+                Some(arg) => Token {text: arg.clone(), ..body_token},
+                None => body_token
+            })
+            .collect();
+        self.tokens.splice(self.pos..self.pos, expanded);
+        Ok(())
+    }
+
+    /// Read and evaluate `{ expression }`
+    fn calc_block(&mut self) -> AsmResult<f64> {
+        self.expect("{")?;
+        let open = self.tokens[self.pos - 1].clone();
+        let body = self.block()?;
+        let mut pos = 0;
+        let value = self.calc(&body, &mut pos, &open)?;
+        match body.get(pos) {
+            Some(token) => Err(Compiler::error(token, format!("unexpected `{}` in expression", token.text))),
+            None => Ok(value)
+        }
+    }
+
+    /// Evaluate a `:calc` expression, binary operators have equal
+    /// precedence and group to the right as in Octo
+    fn calc(&self, tokens: &[Token], pos: &mut usize, open: &Token) -> AsmResult<f64> {
+        let lhs = self.calc_term(tokens, pos, open)?;
+        let op = match tokens.get(*pos) {
+            Some(token) if token.text != ")" => token.clone(),
+            _ => return Ok(lhs)
+        };
+        *pos += 1;
+        let rhs = self.calc(tokens, pos, open)?;
+        let truth = |holds: bool| if holds { 1.0 } else { 0.0 };
+        let int = |value: f64| value as i64;
+        let value = match op.text.as_str() {
+            "+" => lhs + rhs,
+            "-" => lhs - rhs,
+            "*" => lhs * rhs,
+            "/" => lhs / rhs,
+            "%" => lhs % rhs,
+            "pow" => lhs.powf(rhs),
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            "&" => (int(lhs) & int(rhs)) as f64,
+            "|" => (int(lhs) | int(rhs)) as f64,
+            "^" => (int(lhs) ^ int(rhs)) as f64,
+            "<<" => int(lhs).checked_shl(int(rhs) as u32).unwrap_or(0) as f64,
+            ">>" => int(lhs).checked_shr(int(rhs) as u32).unwrap_or(0) as f64,
+            "<" => truth(lhs < rhs),
+            ">" => truth(lhs > rhs),
+            "<=" => truth(lhs <= rhs),
+            ">=" => truth(lhs >= rhs),
+            "==" => truth(lhs == rhs),
+            "!=" => truth(lhs != rhs),
+            _ => return Err(Compiler::error(&op, format!("unknown operator `{}`", op.text)))
+        };
+        if !value.is_finite() {
+            return Err(Compiler::error(&op, String::from("expression result isn't a finite number")));
+        }
+        Ok(value)
+    }
+
+    /// Evaluate a `:calc` term: a value, a unary operator applied to a
+    /// term or a parenthesized expression
+    fn calc_term(&self, tokens: &[Token], pos: &mut usize, open: &Token) -> AsmResult<f64> {
+        let token = tokens.get(*pos).cloned().ok_or_else(|| Compiler::error(open, String::from("expression is missing a value")))?;
+        *pos += 1;
+        let unary: Option<fn(f64) -> f64> = match token.text.as_str() {
+            "-" => Some(|v| -v),
+            "~" => Some(|v| !(v as i64) as f64),
+            "!" => Some(|v| if v == 0.0 { 1.0 } else { 0.0 }),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "exp" => Some(f64::exp),
+            "log" => Some(f64::ln),
+            "abs" => Some(f64::abs),
+            "sqrt" => Some(f64::sqrt),
+            "sign" => Some(f64::signum),
+            "ceil" => Some(f64::ceil),
+            "floor" => Some(f64::floor),
+            _ => None
+        };
+        if let Some(apply) = unary {
+            return Ok(apply(self.calc_term(tokens, pos, open)?));
+        }
+        match token.text.as_str() {
+            "(" => {
+                let value = self.calc(tokens, pos, open)?;
+                match tokens.get(*pos) {
+                    Some(close) if close.text == ")" => {
+                        *pos += 1;
+                        Ok(value)
+                    },
+                    _ => Err(Compiler::error(&token, String::from("`(` without `)`")))
+                }
+            },
+            "@" => {
+                let addr = self.calc_term(tokens, pos, open)? as i64;
+                let byte = match addr >= PROGRAM_START as i64 {
+                    true => self.rom.get((addr - PROGRAM_START as i64) as usize).copied(),
+                    false => None
+                };
+                Ok(byte.unwrap_or(0) as f64)
+            },
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            _ => self.number_of(&token).ok_or_else(|| Compiler::error(&token, format!("undefined name `{}`", token.text)))
+        }
+    }
+
+    /// Patch a reference at `addr` to point at `target`
+    fn patch(&mut self, addr: u16, target: u16, patch: Patch, token: &Token) -> AsmResult<()> {
+        let index = (addr - PROGRAM_START) as usize;
+        match patch {
+            Patch::Nnn => {
+                if target > 0xFFF {
+                    return Err(Compiler::error(token, format!("address {:04X} doesn't fit in 12 bits, use `i := long`", target)));
+                }
+                self.rom[index] = (self.rom[index] & 0xF0) | (target >> 8) as u8;
+                self.rom[index + 1] = target as u8;
+            },
+            Patch::Long => {
+                self.rom[index + 2] = (target >> 8) as u8;
+                self.rom[index + 3] = target as u8;
+            },
+            Patch::Unpack(nibble) => {
+                let high = match nibble {
+                    Some(nibble) if target <= 0xFFF => (nibble << 4) | (target >> 8) as u8,
+                    Some(_) => return Err(Compiler::error(token, format!("address {:04X} doesn't fit in 12 bits, use `:unpack long`", target))),
+                    None => (target >> 8) as u8
+                };
+                self.rom[index + 1] = high;
+                self.rom[index + 3] = target as u8;
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve every label reference and build the image
+    fn finish(mut self) -> AsmResult<Assembly> {
+        if !self.labels.contains_key("main") {
+            return Err(AsmError {
+                file: String::from("<input>"),
+                line: 0,
+                column: 0,
+                message: String::from("program has no `main` label")
+            });
+        }
+        let fixups = std::mem::take(&mut self.fixups);
+        for fixup in fixups {
+            let target = match self.labels.get(&fixup.label.text) {
+                Some(&target) => target,
+                None => return Err(Compiler::error(&fixup.label, format!("undefined label `{}`", fixup.label.text)))
+            };
+            self.patch(fixup.addr, target, fixup.patch, &fixup.label)?;
+        }
+        Ok(Assembly {origin: PROGRAM_START, rom: self.rom, labels: self.labels})
+    }
+}
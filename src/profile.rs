@@ -0,0 +1,269 @@
+// Standard Library Modules //
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+// Local Modules Use //
+use crate::bus::Bus;
+use crate::instruction::Instruction;
+use crate::{ChipSystem, ExResult, Frame, Step};
+
+/// Number of hot addresses listed by `Profiler::report`
+pub const REPORT_HOTSPOTS: usize = 20;
+
+/// Time spent in a subroutine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Routine {
+    /// Entry address, the target of the `2nnn` calls or the address
+    /// profiling started at for the top level
+    pub entry: u16,
+    /// Number of times the routine was called
+    pub calls: u64,
+    /// Instructions executed in the routine itself
+    pub exclusive: u64,
+    /// Instructions executed in the routine and everything it called,
+    /// recursive calls are only counted once
+    pub inclusive: u64
+}
+
+/// Calls from one subroutine to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallEdge {
+    /// Entry address of the calling routine
+    pub caller: u16,
+    /// Entry address of the called routine
+    pub callee: u16,
+    /// Number of calls
+    pub calls: u64,
+    /// Instructions executed during these calls, the callee included
+    pub inclusive: u64
+}
+
+/// Counts where a ROM spends its instructions
+///
+/// Run the system through `step` or `run_frame` on the profiler. Every
+/// executed instruction is counted at its address and charged to the
+/// subroutine on top of a shadow call stack, which `2nnn` pushes and
+/// `00EE` pops. From this the profiler builds a call graph with
+/// exclusive and inclusive totals, a text report and folded stacks.
+pub struct Profiler {
+    /// Executions and instruction of every executed address
+    counts: BTreeMap<u16, (u64, Instruction)>,
+    /// Stats of every routine by entry address
+    routines: BTreeMap<u16, Routine>,
+    /// Call graph edges by caller and callee
+    edges: BTreeMap<(u16, u16), CallEdge>,
+    /// Instructions executed with each call stack, outermost first
+    stacks: BTreeMap<Vec<u16>, u64>,
+    /// Entry addresses of the routines being executed, outermost first
+    stack: Vec<u16>,
+    /// Names for routine entry addresses
+    labels: BTreeMap<u16, String>,
+    /// Instructions executed while profiling
+    total: u64
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::init()
+    }
+}
+
+impl Profiler {
+    /// Create an empty profile, the top level routine is the address
+    /// of the first profiled instruction
+    pub fn init() -> Self {
+        Profiler {
+            counts: BTreeMap::new(),
+            routines: BTreeMap::new(),
+            edges: BTreeMap::new(),
+            stacks: BTreeMap::new(),
+            stack: Vec::new(),
+            labels: BTreeMap::new(),
+            total: 0
+        }
+    }
+
+    /// Name routines in the report and folded stacks, usually with the
+    /// labels of an `asm::Assembly` or a `disasm::Listing`
+    ///
+    /// # Arguments
+    ///
+    /// * `labels` - names by address, addresses without one are shown in hex
+    pub fn set_labels(&mut self, labels: &BTreeMap<u16, String>) {
+        self.labels = labels.clone();
+    }
+
+    /// Drop the profile, the next instruction starts a new top level
+    pub fn reset(&mut self) {
+        self.counts.clear();
+        self.routines.clear();
+        self.edges.clear();
+        self.stacks.clear();
+        self.stack.clear();
+        self.total = 0;
+    }
+
+    /// Instructions executed while profiling
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Number of times the instruction at an address was executed
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - the address
+    pub fn count(&self, addr: u16) -> u64 {
+        self.counts.get(&addr).map_or(0, |&(count, _)| count)
+    }
+
+    /// Executed addresses with their counts, most executed first
+    pub fn hotspots(&self) -> Vec<(u16, u64)> {
+        let mut hotspots: Vec<(u16, u64)> = self.counts.iter().map(|(&addr, &(count, _))| (addr, count)).collect();
+        hotspots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hotspots
+    }
+
+    /// Every routine seen, highest inclusive total first
+    pub fn routines(&self) -> Vec<Routine> {
+        let mut routines: Vec<Routine> = self.routines.values().copied().collect();
+        routines.sort_by(|a, b| b.inclusive.cmp(&a.inclusive).then(a.entry.cmp(&b.entry)));
+        routines
+    }
+
+    /// Every call graph edge, highest inclusive total first
+    pub fn calls(&self) -> Vec<CallEdge> {
+        let mut edges: Vec<CallEdge> = self.edges.values().copied().collect();
+        edges.sort_by(|a, b| b.inclusive.cmp(&a.inclusive).then((a.caller, a.callee).cmp(&(b.caller, b.callee))));
+        edges
+    }
+
+    /// Run a single system step and profile it
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - the system to step
+    pub fn step<B: Bus>(&mut self, system: &mut ChipSystem<B>) -> ExResult<Step> {
+        let pc = system.registers.get_pc();
+        let instruction = match system.ram.fetch_instruction(pc) {
+            Ok(Ok(instruction)) => Some(instruction),
+            _ => None
+        };
+        let step = system.step(false)?;
+        if let (Some(instruction), Some(_)) = (instruction, step.opcode) {
+            self.record(pc, instruction);
+        }
+        Ok(step)
+    }
+
+    /// Run the rest of the current frame one step at a time, profiling
+    /// every instruction, as `ChipSystem::run_frame` (without the JIT)
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - the system to run
+    pub fn run_frame<B: Bus>(&mut self, system: &mut ChipSystem<B>) -> ExResult<Frame> {
+        system.run_frame_with(|system| self.step(system))
+    }
+
+    /// Charge an executed instruction to the current call stack
+    fn record(&mut self, pc: u16, instruction: Instruction) {
+        if self.stack.is_empty() {
+            self.stack.push(pc);
+            self.routine(pc);
+        }
+        self.total += 1;
+        self.counts.entry(pc).or_insert((0, instruction)).0 += 1;
+        *self.stacks.entry(self.stack.clone()).or_insert(0) += 1;
+
+        let current = self.stack[self.stack.len() - 1];
+        self.routine(current).exclusive += 1;
+        let entries: BTreeSet<u16> = self.stack.iter().copied().collect();
+        for entry in entries {
+            self.routine(entry).inclusive += 1;
+        }
+        let edges: BTreeSet<(u16, u16)> = self.stack.windows(2).map(|pair| (pair[0], pair[1])).collect();
+        for edge in edges {
+            if let Some(stats) = self.edges.get_mut(&edge) {
+                stats.inclusive += 1;
+            }
+        }
+
+        match instruction {
+            Instruction::Call(addr) => {
+                self.routine(addr).calls += 1;
+                self.edges.entry((current, addr))
+                    .or_insert(CallEdge {caller: current, callee: addr, calls: 0, inclusive: 0})
+                    .calls += 1;
+                self.stack.push(addr);
+            },
+            // A return from the top level has nothing to pop
+            Instruction::Ret if self.stack.len() > 1 => {
+                self.stack.pop();
+            },
+            _ => ()
+        }
+    }
+
+    /// Stats of a routine, created on first use
+    fn routine(&mut self, entry: u16) -> &mut Routine {
+        self.routines.entry(entry).or_insert(Routine {entry, calls: 0, exclusive: 0, inclusive: 0})
+    }
+
+    /// Name of a routine, its label or hex address
+    fn name(&self, entry: u16) -> String {
+        match self.labels.get(&entry) {
+            Some(label) => label.clone(),
+            None => format!("0x{:04X}", entry)
+        }
+    }
+
+    /// Folded stacks for flamegraph tools such as `flamegraph.pl` or
+    /// `inferno-flamegraph`
+    ///
+    /// One `outer;inner;innermost count` line per call stack, with the
+    /// number of instructions executed in the innermost routine.
+    pub fn folded(&self) -> String {
+        let mut folded = String::new();
+        for (stack, count) in &self.stacks {
+            let names: Vec<String> = stack.iter().map(|&entry| self.name(entry)).collect();
+            let _ = writeln!(folded, "{} {}", names.join(";"), count);
+        }
+        folded
+    }
+
+    /// A text report of the hottest addresses, every routine and the
+    /// call graph
+    pub fn report(&self) -> String {
+        let percent = |count: u64| match self.total {
+            0 => 0.0,
+            total => count as f64 * 100.0 / total as f64
+        };
+        let mut report = String::new();
+        let _ = writeln!(report, "{} instructions, {} addresses, {} routines", self.total, self.counts.len(), self.routines.len());
+
+        let _ = writeln!(report, "\nHotspots\n  addr       count       %  instruction");
+        for (addr, count) in self.hotspots().into_iter().take(REPORT_HOTSPOTS) {
+            let instruction = self.counts[&addr].1;
+            let _ = writeln!(report, "  0x{:04X} {:>10} {:>6.2}%  {}", addr, count, percent(count), instruction);
+        }
+
+        let _ = writeln!(report, "\nRoutines\n       calls   exclusive       %   inclusive       %  routine");
+        for routine in self.routines() {
+            let _ = writeln!(
+                report, "  {:>10} {:>11} {:>6.2}% {:>11} {:>6.2}%  {}",
+                routine.calls, routine.exclusive, percent(routine.exclusive),
+                routine.inclusive, percent(routine.inclusive), self.name(routine.entry)
+            );
+        }
+
+        let _ = writeln!(report, "\nCall graph\n       calls   inclusive       %  caller -> callee");
+        for edge in self.calls() {
+            let _ = writeln!(
+                report, "  {:>10} {:>11} {:>6.2}%  {} -> {}",
+                edge.calls, edge.inclusive, percent(edge.inclusive), self.name(edge.caller), self.name(edge.callee)
+            );
+        }
+        report
+    }
+}
@@ -0,0 +1,164 @@
+/// How `Fx55`/`Fx65` leave the `I` register after a transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexIncrement {
+    /// `I` is left untouched (SUPER-CHIP 1.1)
+    None,
+    /// `I = I + x` (CHIP-48)
+    X,
+    /// `I = I + x + 1` (COSMAC VIP, XO-CHIP)
+    XPlusOne,
+}
+
+/// When `Fx0A` completes its wait for a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyWait {
+    /// As soon as a key is pressed
+    Press,
+    /// When a key pressed during the wait is released (COSMAC VIP)
+    Release,
+}
+
+/// A set of interpretations for the Chip8 opcodes that behave
+/// differently between interpreters.
+///
+/// Use one of the named presets, or start from one and change
+/// individual fields to match a specific ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8xy6`/`8xyE` shift `Vy` into `Vx`, otherwise `Vx` is shifted in place
+    pub shift_vy: bool,
+    /// How `Fx55`/`Fx65` modify the `I` register
+    pub index_increment: IndexIncrement,
+    /// `Bnnn` jumps to `nnn + Vx` (x being the high nibble of `nnn`),
+    /// otherwise it jumps to `nnn + V0`
+    pub jump_vx: bool,
+    /// `8xy1`/`8xy2`/`8xy3` reset `Vf` to 0
+    pub vf_reset: bool,
+    /// Sprites are clipped at the screen edges, otherwise they wrap around
+    pub clip_sprites: bool,
+    /// `Dxyn` waits for the next vertical blank before drawing
+    pub display_wait: bool,
+    /// Amount of ram in bytes, XO-CHIP programs use 65536
+    pub memory_size: usize,
+    /// When `Fx0A` stops waiting for a key
+    pub key_wait: KeyWait,
+}
+
+impl Quirks {
+    /// Behaviour of the original interpreter on the RCA COSMAC VIP
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            shift_vy: true,
+            index_increment: IndexIncrement::XPlusOne,
+            jump_vx: false,
+            vf_reset: true,
+            clip_sprites: true,
+            display_wait: true,
+            memory_size: 4096,
+            key_wait: KeyWait::Release,
+        }
+    }
+
+    /// Behaviour of CHIP-48 on the HP-48 calculators
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_vy: false,
+            index_increment: IndexIncrement::X,
+            jump_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+            display_wait: false,
+            memory_size: 4096,
+            key_wait: KeyWait::Press,
+        }
+    }
+
+    /// Behaviour of SUPER-CHIP 1.1 on the HP-48 calculators
+    pub fn superchip() -> Self {
+        Quirks {
+            shift_vy: false,
+            index_increment: IndexIncrement::None,
+            jump_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+            display_wait: false,
+            memory_size: 4096,
+            key_wait: KeyWait::Press,
+        }
+    }
+
+    /// Behaviour of the modern Octo interpreter, with the 64K of ram
+    /// used by XO-CHIP programs
+    pub fn octo() -> Self {
+        Quirks {
+            shift_vy: true,
+            index_increment: IndexIncrement::XPlusOne,
+            jump_vx: false,
+            vf_reset: false,
+            clip_sprites: false,
+            display_wait: false,
+            memory_size: 65536,
+            key_wait: KeyWait::Release,
+        }
+    }
+
+    /// Pack the quirks into bytes, as stored in save states and movies
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![
+            self.shift_vy as u8,
+            match self.index_increment {
+                IndexIncrement::None => 0,
+                IndexIncrement::X => 1,
+                IndexIncrement::XPlusOne => 2
+            },
+            self.jump_vx as u8,
+            self.vf_reset as u8,
+            self.clip_sprites as u8,
+            self.display_wait as u8,
+            match self.key_wait {
+                KeyWait::Press => 0,
+                KeyWait::Release => 1
+            }
+        ];
+        bytes.extend((self.memory_size as u32).to_le_bytes());
+        bytes
+    }
+
+    /// Unpack quirks packed by `to_bytes`, None if the bytes are too
+    /// short or hold an invalid value, bytes after them are ignored
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - the packed quirks
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let flag = |n: usize| bytes.get(n).map(|&byte| byte != 0);
+        let mut size = [0; 4];
+        size.copy_from_slice(bytes.get(7..11)?);
+        Some(Quirks {
+            shift_vy: flag(0)?,
+            index_increment: match bytes.get(1)? {
+                0 => IndexIncrement::None,
+                1 => IndexIncrement::X,
+                2 => IndexIncrement::XPlusOne,
+                _ => return None
+            },
+            jump_vx: flag(2)?,
+            vf_reset: flag(3)?,
+            clip_sprites: flag(4)?,
+            display_wait: flag(5)?,
+            key_wait: match bytes.get(6)? {
+                0 => KeyWait::Press,
+                1 => KeyWait::Release,
+                _ => return None
+            },
+            memory_size: u32::from_le_bytes(size) as usize
+        })
+    }
+}
+
+impl Default for Quirks {
+    /// Defaults to the original COSMAC VIP behaviour
+    fn default() -> Self {
+        Quirks::cosmac_vip()
+    }
+}
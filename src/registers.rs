@@ -1,4 +1,13 @@
 
+/// Error returned by the call stack operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackError {
+    /// Push onto a full stack
+    Overflow,
+    /// Pop from an empty stack
+    Underflow
+}
+
 /// A struct representing the chip8 registers
 pub struct ChipRegisters {
     /// General purpose registers
@@ -15,6 +24,8 @@ pub struct ChipRegisters {
     pc_reg: u16,     
     /// Stack pointer
     sp_reg: usize,      
+    /// SUPER-CHIP RPL user flags
+    rpl_reg: Vec<u8>,
 }
 
 impl ChipRegisters {
@@ -30,6 +41,7 @@ impl ChipRegisters {
             s_reg: 0,
             pc_reg: 512,
             sp_reg: 0,
+            rpl_reg: vec![0; 16],
         }
     }
 
@@ -94,7 +106,7 @@ impl ChipRegisters {
 
     /// Increment the value of the pc register by 2
     pub fn incr_pc(&mut self) {
-        self.pc_reg += 2;
+        self.pc_reg = self.pc_reg.wrapping_add(2);
     }
 
     /// Get the value of the pc register
@@ -144,21 +156,65 @@ impl ChipRegisters {
         }
     }
 
+    /// Set a SUPER-CHIP RPL user flag
+    /// 
+    /// # Arguments
+    /// 
+    /// * `index` - which flag (0-15)
+    /// * `value` - value to store in the flag
+    pub fn set_rpl(&mut self, index: usize, value: u8) {
+        self.rpl_reg[index] = value;
+    }
+
+    /// Get the value of a SUPER-CHIP RPL user flag
+    /// 
+    /// # Arguments
+    /// 
+    /// * `index` - which flag (0-15)
+    pub fn get_rpl(&self, index: usize) -> u8 {
+        self.rpl_reg[index]
+    }
+
     /// Push a address onto the stack, increment stack pointer
     /// 
     /// # Arguments
     /// 
     /// * `addr` - address to push to the stack
-    pub fn push_stack(&mut self, addr: u16) {
-        self.stack[self.sp_reg as usize] = addr;
+    pub fn push_stack(&mut self, addr: u16) -> Result<(), StackError> {
+        if self.sp_reg >= self.stack.len() {
+            return Err(StackError::Overflow);
+        }
+        self.stack[self.sp_reg] = addr;
         self.sp_reg += 1;
+        Ok(())
     }
 
     /// Pop an address from the stack, decrementing sp
-    pub fn pop_stack(&mut self) -> u16 {
+    pub fn pop_stack(&mut self) -> Result<u16, StackError> {
+        if self.sp_reg == 0 {
+            return Err(StackError::Underflow);
+        }
         self.sp_reg -= 1;
-        let addr = self.stack[self.sp_reg];
-        return addr;
+        Ok(self.stack[self.sp_reg])
+    }
+
+    /// Get the stack pointer, the number of addresses on the stack
+    pub fn get_sp(&self) -> usize {
+        self.sp_reg
+    }
+
+    /// Set the stack pointer, limited to the size of the stack
+    /// 
+    /// # Arguments
+    /// 
+    /// * `value` - number of addresses on the stack
+    pub fn set_sp(&mut self, value: usize) {
+        self.sp_reg = value.min(self.stack.len());
+    }
+
+    /// Get a copy of the addresses on the stack, oldest first
+    pub fn get_stack(&self) -> Vec<u16> {
+        self.stack[..self.sp_reg].to_vec()
     }
 
     /// print information on all registers
@@ -0,0 +1,264 @@
+// Standard Library Modules //
+use std::collections::VecDeque;
+
+// Local Modules Use //
+use crate::bus::Bus;
+use crate::state::StateError;
+use crate::ChipSystem;
+
+/// Default memory budget, enough for several minutes of typical games
+pub const DEFAULT_BUDGET: usize = 8 * 1024 * 1024;
+
+/// Zero bytes that end a literal run in a delta, shorter gaps are
+/// cheaper to copy than to encode as a new run
+const MIN_ZERO_RUN: usize = 3;
+
+/// Append a LEB128 varint
+fn put_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Read a LEB128 varint, None if the data ends first
+fn get_varint(bytes: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Encode the delta that turns `new` back into `old`
+///
+/// The two states are XORed (the shorter one padded with zeros) and
+/// the result is stored as the length of `old` followed by runs of
+/// `(zero count, literal count, literal bytes)`. Consecutive frames
+/// differ in a few registers, timers and sprite rows, so most of the
+/// XOR is zero and the delta is a few dozen bytes.
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let len = old.len().max(new.len());
+    let xor = |n: usize| old.get(n).unwrap_or(&0) ^ new.get(n).unwrap_or(&0);
+    let mut out = Vec::new();
+    put_varint(&mut out, old.len());
+    let mut pos = 0;
+    while pos < len {
+        let start = pos;
+        while pos < len && xor(pos) == 0 {
+            pos += 1;
+        }
+        if pos == len {
+            break;
+        }
+        let zeros = pos - start;
+        let literal_start = pos;
+        // A literal run ends at the end or at enough zeros for a new run
+        while pos < len && !(pos + MIN_ZERO_RUN <= len && (pos..pos + MIN_ZERO_RUN).all(|n| xor(n) == 0)) {
+            pos += 1;
+        }
+        put_varint(&mut out, zeros);
+        put_varint(&mut out, pos - literal_start);
+        out.extend((literal_start..pos).map(xor));
+    }
+    out
+}
+
+/// Apply a delta from `encode_delta` to `new`, giving `old`
+fn apply_delta(new: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = 0;
+    let old_len = get_varint(delta, &mut pos)?;
+    let mut state = new.to_vec();
+    state.resize(old_len.max(new.len()), 0);
+    let mut at = 0;
+    while pos < delta.len() {
+        at += get_varint(delta, &mut pos)?;
+        let literal = get_varint(delta, &mut pos)?;
+        let bytes = delta.get(pos..pos + literal)?;
+        for (dst, &byte) in state.get_mut(at..at + literal)?.iter_mut().zip(bytes) {
+            *dst ^= byte;
+        }
+        pos += literal;
+        at += literal;
+    }
+    state.truncate(old_len);
+    Some(state)
+}
+
+/// A ring buffer of system snapshots for rewinding
+///
+/// Call `capture` once per frame (or per instruction when debugging).
+/// The newest snapshot is kept as a full save state and each older one
+/// as a delta against the snapshot after it, so a frame usually costs
+/// tens of bytes. When the memory budget is exceeded the oldest
+/// snapshots are dropped, the newest is always kept.
+pub struct Rewind {
+    /// Most bytes the buffer may use
+    budget: usize,
+    /// The newest snapshot
+    latest: Option<Vec<u8>>,
+    /// Deltas, oldest first, each turns the snapshot after it into its own
+    deltas: VecDeque<Vec<u8>>,
+    /// Total length of the deltas
+    delta_bytes: usize
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Rewind::init(DEFAULT_BUDGET)
+    }
+}
+
+impl Rewind {
+    /// Create an empty buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `budget` - most bytes of memory to use for snapshots
+    pub fn init(budget: usize) -> Self {
+        Rewind {
+            budget,
+            latest: None,
+            deltas: VecDeque::new(),
+            delta_bytes: 0
+        }
+    }
+
+    /// Change the memory budget, dropping old snapshots if needed
+    ///
+    /// # Arguments
+    ///
+    /// * `budget` - most bytes of memory to use for snapshots
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.trim();
+    }
+
+    /// Get the memory budget
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Bytes currently used by snapshots
+    pub fn memory_used(&self) -> usize {
+        self.latest.as_ref().map_or(0, Vec::len) + self.delta_bytes
+    }
+
+    /// Number of snapshots that can be rewound to from the newest one
+    pub fn available(&self) -> usize {
+        self.deltas.len()
+    }
+
+    /// Drop every snapshot
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.delta_bytes = 0;
+    }
+
+    /// Record a snapshot of the system
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - the system to snapshot
+    pub fn capture<B: Bus>(&mut self, system: &ChipSystem<B>) {
+        let state = system.save_state();
+        if let Some(previous) = self.latest.take() {
+            let delta = encode_delta(&previous, &state);
+            self.delta_bytes += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.latest = Some(state);
+        self.trim();
+    }
+
+    /// Restore the snapshot taken `frames` captures before the newest
+    /// one, going back as far as the buffer allows, and returns the
+    /// number of snapshots actually rewound
+    ///
+    /// The restored snapshot becomes the newest, later ones are dropped.
+    /// `rewind(system, 0)` undoes any change made since the last capture.
+    /// On an error the buffer and the system are left as they were.
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - the system to restore
+    /// * `frames` - number of snapshots to go back
+    pub fn rewind<B: Bus>(&mut self, system: &mut ChipSystem<B>, frames: usize) -> Result<usize, StateError> {
+        let mut state = match &self.latest {
+            Some(state) => state.clone(),
+            None => return Ok(0)
+        };
+        let rewound = frames.min(self.deltas.len());
+        let kept = self.deltas.len() - rewound;
+        for delta in self.deltas.range(kept..).rev() {
+            state = apply_delta(&state, delta).ok_or(StateError::Truncated)?;
+        }
+        system.load_state(&state)?;
+        // Only drop the newer snapshots once the older one is restored
+        for delta in self.deltas.drain(kept..) {
+            self.delta_bytes -= delta.len();
+        }
+        self.latest = Some(state);
+        Ok(rewound)
+    }
+
+    /// Go back a single snapshot, returns false if there is none
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - the system to restore
+    pub fn step_back<B: Bus>(&mut self, system: &mut ChipSystem<B>) -> Result<bool, StateError> {
+        self.rewind(system, 1).map(|rewound| rewound == 1)
+    }
+
+    /// Drop the oldest snapshots until the buffer fits the budget
+    fn trim(&mut self) {
+        while self.memory_used() > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.delta_bytes -= delta.len(),
+                None => break
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_delta_leaves_buffer_and_system() {
+        // loop: ADD V0, 1 / JP loop
+        let mut system: ChipSystem = ChipSystem::init();
+        system.load_rom(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut rewind = Rewind::default();
+        for _ in 0..4 {
+            system.run_frame().unwrap();
+            rewind.capture(&system);
+        }
+        let current = system.save_state();
+        let used = rewind.memory_used();
+
+        // Cut the last literal run of the newest delta short
+        let delta = rewind.deltas.back_mut().unwrap();
+        delta.pop();
+        rewind.delta_bytes -= 1;
+        assert_eq!(rewind.rewind(&mut system, 2), Err(StateError::Truncated));
+        assert_eq!(rewind.available(), 3);
+        assert_eq!(rewind.memory_used(), used - 1);
+        assert_eq!(system.save_state(), current);
+
+        // The newest snapshot is still there to go back to
+        system.run_frame().unwrap();
+        assert_eq!(rewind.rewind(&mut system, 0), Ok(0));
+        assert_eq!(system.save_state(), current);
+    }
+}
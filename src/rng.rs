@@ -0,0 +1,223 @@
+// Modules From Crates.io //
+use rand::Rng;
+
+/// A source of random bytes for the `Cxkk` opcode
+pub trait RngSource {
+    /// Return the next random byte
+    fn next_byte(&mut self) -> u8;
+
+    /// Called once per 60 Hz frame, for sources that depend on
+    /// interrupt timing
+    fn vblank(&mut self) {}
+
+    /// Serialize the generator state for save states, None if the
+    /// source can't be saved
+    fn save_state(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Restore a state written by `save_state`, returns false (leaving
+    /// the source unchanged) if it was written by another kind of source
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - bytes returned by `save_state`
+    fn load_state(&mut self, _state: &[u8]) -> bool {
+        false
+    }
+}
+
+/// A seedable xorshift64* generator, the default random source
+///
+/// The same seed always produces the same sequence on every platform.
+pub struct SeededRng {
+    /// Seed the generator was created with
+    seed: u64,
+    /// Current generator state, never 0
+    state: u64
+}
+
+impl SeededRng {
+    /// Create a generator from a seed
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - any value, including 0
+    pub fn new(seed: u64) -> Self {
+        // Mix the seed (splitmix64) so nearby seeds diverge quickly
+        let mut state = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        state ^= state >> 31;
+        SeededRng {
+            seed,
+            state: if state == 0 { 1 } else { state }
+        }
+    }
+
+    /// Create a generator with a random seed
+    pub fn from_entropy() -> Self {
+        SeededRng::new(rand::thread_rng().gen())
+    }
+
+    /// Get the seed the generator was created with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RngSource for SeededRng {
+    fn next_byte(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    fn save_state(&self) -> Option<Vec<u8>> {
+        let mut state = vec![b'S'];
+        state.extend(self.seed.to_le_bytes());
+        state.extend(self.state.to_le_bytes());
+        Some(state)
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        match state {
+            [b'S', rest @ ..] if rest.len() == 16 => {
+                let word = |range: std::ops::Range<usize>| {
+                    let mut bytes = [0; 8];
+                    bytes.copy_from_slice(&rest[range]);
+                    u64::from_le_bytes(bytes)
+                };
+                self.seed = word(0..8);
+                self.state = word(8..16).max(1);
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+/// Returns a fixed sequence of bytes, repeating it once exhausted
+///
+/// Useful for tests that need to know which values `Cxkk` produces.
+pub struct FixedRng {
+    /// Bytes to return in order
+    bytes: Vec<u8>,
+    /// Index of the next byte
+    pos: usize
+}
+
+impl FixedRng {
+    /// Create a source from a sequence, an empty sequence always returns 0
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - values to return in order
+    pub fn new(bytes: Vec<u8>) -> Self {
+        FixedRng {
+            bytes,
+            pos: 0
+        }
+    }
+}
+
+impl RngSource for FixedRng {
+    fn next_byte(&mut self) -> u8 {
+        if self.bytes.is_empty() {
+            return 0;
+        }
+        let byte = self.bytes[self.pos];
+        self.pos = (self.pos + 1) % self.bytes.len();
+        byte
+    }
+
+    fn save_state(&self) -> Option<Vec<u8>> {
+        let mut state = vec![b'F'];
+        state.extend((self.pos as u32).to_le_bytes());
+        state.extend(&self.bytes);
+        Some(state)
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        match state {
+            [b'F', p0, p1, p2, p3, bytes @ ..] => {
+                let pos = u32::from_le_bytes([*p0, *p1, *p2, *p3]) as usize;
+                if pos >= bytes.len().max(1) {
+                    return false;
+                }
+                self.bytes = bytes.to_vec();
+                self.pos = pos;
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+/// Emulation of the random routine of the COSMAC VIP interpreter
+///
+/// The VIP interpreter has no real generator. It walks a pointer through
+/// its own code page, adding the byte found there to a running value,
+/// and the pointer is also advanced by the display interrupt so results
+/// depend on how many frames have passed. Pass the interpreter page
+/// (`0x0100-0x01FF` of the interpreter image) to reproduce the
+/// original sequences.
+pub struct VipRng {
+    /// Contents of the interpreter page the pointer walks through
+    page: Vec<u8>,
+    /// Low byte of the pointer into the page
+    pointer: u8,
+    /// Running random value
+    value: u8
+}
+
+impl VipRng {
+    /// Create the VIP random routine
+    ///
+    /// # Arguments
+    ///
+    /// * `page` - 256 bytes of interpreter code, missing bytes are zeroed
+    pub fn new(page: &[u8]) -> Self {
+        let mut bytes = vec![0; 256];
+        for (dst, src) in bytes.iter_mut().zip(page.iter()) {
+            *dst = *src;
+        }
+        VipRng {
+            page: bytes,
+            pointer: 0,
+            value: 0
+        }
+    }
+}
+
+impl RngSource for VipRng {
+    fn next_byte(&mut self) -> u8 {
+        self.pointer = self.pointer.wrapping_add(1);
+        let code = self.page[self.pointer as usize];
+        self.value = self.value.wrapping_add(code).rotate_right(1) ^ self.pointer;
+        self.value
+    }
+
+    fn vblank(&mut self) {
+        self.pointer = self.pointer.wrapping_add(1);
+    }
+
+    fn save_state(&self) -> Option<Vec<u8>> {
+        let mut state = vec![b'V', self.pointer, self.value];
+        state.extend(&self.page);
+        Some(state)
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        match state {
+            [b'V', pointer, value, page @ ..] if page.len() == 256 => {
+                self.pointer = *pointer;
+                self.value = *value;
+                self.page = page.to_vec();
+                true
+            },
+            _ => false
+        }
+    }
+}
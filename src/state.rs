@@ -0,0 +1,356 @@
+// Standard Library Modules //
+use std::error;
+use std::fmt;
+
+// Local Modules Use //
+use crate::audio::{ChipAudio, PATTERN_LEN};
+use crate::binary::{self, FormatError, Reader};
+use crate::bus::Bus;
+use crate::display::{ChipDisplay, PLANE_COUNT};
+use crate::keyboard::ChipKeyboard;
+use crate::quirks::Quirks;
+use crate::registers::ChipRegisters;
+use crate::timing::{VipClock, INTERPRETER_CYCLES};
+use crate::{ChipSystem, CpuState, Speed};
+
+/// First four bytes of every save state
+pub const MAGIC: [u8; 4] = *b"C8SS";
+/// Major version of the format, states with another major version
+/// can't be loaded
+pub const FORMAT_MAJOR: u16 = 1;
+/// Minor version of the format, raised when sections or fields are
+/// added, states with any minor version can be loaded
+pub const FORMAT_MINOR: u16 = 0;
+
+/// Number of SUPER-CHIP RPL flags
+const RPL_COUNT: usize = 16;
+
+/// Error returned when a save state can't be loaded, the system is
+/// left unchanged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// The data doesn't start with the save state magic
+    BadMagic,
+    /// The state was written with an incompatible major version
+    UnsupportedVersion { major: u16, minor: u16 },
+    /// The checksum doesn't match, the data is corrupted
+    Checksum,
+    /// The data ends in the middle of a header, section or field
+    Truncated,
+    /// A section every state has is missing
+    MissingSection(&'static str),
+    /// A section holds a value that isn't valid
+    InvalidSection(&'static str),
+    /// The saved memory isn't the size of the system's bus
+    MemorySize { expected: usize, found: usize },
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion { major, minor } =>
+                write!(f, "unsupported save state version {}.{}", major, minor),
+            StateError::Checksum => write!(f, "save state checksum mismatch"),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::MissingSection(tag) => write!(f, "save state has no `{}` section", tag),
+            StateError::InvalidSection(tag) => write!(f, "save state `{}` section is invalid", tag),
+            StateError::MemorySize { expected, found } =>
+                write!(f, "save state has {} bytes of memory, the system has {}", found, expected),
+        }
+    }
+}
+
+impl error::Error for StateError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+impl From<FormatError> for StateError {
+    fn from(e: FormatError) -> Self {
+        match e {
+            FormatError::BadMagic => StateError::BadMagic,
+            FormatError::UnsupportedVersion { major, minor } => StateError::UnsupportedVersion { major, minor },
+            FormatError::Checksum => StateError::Checksum,
+            FormatError::Truncated => StateError::Truncated,
+        }
+    }
+}
+
+/// Append a section to a state
+fn section(out: &mut Vec<u8>, tag: &[u8; 4], payload: &[u8]) {
+    out.extend(tag);
+    out.extend((payload.len() as u32).to_le_bytes());
+    out.extend(payload);
+}
+
+/// Pack pixels into bytes, 8 per byte, first pixel in the high bit
+fn pack(pixels: &[bool]) -> Vec<u8> {
+    pixels
+        .chunks(8)
+        .map(|chunk| chunk.iter().enumerate().fold(0, |byte, (n, &set)| byte | ((set as u8) << (7 - n))))
+        .collect()
+}
+
+/// Unpack `count` pixels packed by `pack`
+fn unpack(bytes: &[u8], count: usize) -> Vec<bool> {
+    (0..count).map(|n| bytes[n / 8] & (0x80 >> (n % 8)) != 0).collect()
+}
+
+/// Everything a state holds, decoded before any of it is applied
+struct Snapshot {
+    registers: ChipRegisters,
+    state: CpuState,
+    wait_key: Option<u8>,
+    vblank: bool,
+    stalled: bool,
+    frame_pos: u32,
+    frame_budget: u32,
+    ips_carry: u32,
+    speed: Speed,
+    memory: Vec<u8>,
+    display: ChipDisplay,
+    keyboard: ChipKeyboard,
+    audio: ChipAudio,
+    quirks: Option<Quirks>,
+    vip: Option<VipClock>,
+    rng: Option<Vec<u8>>
+}
+
+impl<B: Bus> ChipSystem<B> {
+    /// Save the complete state of the system: registers, stack, timers,
+    /// memory, display, keys, audio, quirks, speed, timing and the
+    /// random source (if it supports saving)
+    ///
+    /// The format is little endian:
+    ///
+    /// * 4 bytes magic `C8SS`
+    /// * 2 bytes major and 2 bytes minor format version
+    /// * sections, each a 4 byte ASCII tag, a 4 byte payload length and
+    ///   the payload: `CPU `, `MEM `, `DISP`, `KEYS`, `AUDI`, `RPL `,
+    ///   `QRKS`, `VIP ` (only in VIP timing) and `RNG `
+    /// * 4 bytes CRC-32 of everything before it
+    ///
+    /// Readers skip sections they don't know and ignore bytes past the
+    /// fields they know at the end of a section, so later minor versions
+    /// add extension state as new sections or trailing fields. Optional
+    /// sections missing from a state load as the power on state.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = binary::header(MAGIC, FORMAT_MAJOR, FORMAT_MINOR);
+
+        let regs = &self.registers;
+        let mut cpu: Vec<u8> = (0..16).map(|x| regs.get_gp(x)).collect();
+        cpu.extend(regs.get_i().to_le_bytes());
+        cpu.extend(regs.get_pc().to_le_bytes());
+        cpu.push(regs.get_d());
+        cpu.push(regs.get_s());
+        let stack = regs.get_stack();
+        cpu.push(stack.len() as u8);
+        for addr in stack {
+            cpu.extend(addr.to_le_bytes());
+        }
+        let (state, key_reg) = match self.state {
+            CpuState::Running => (0, 0),
+            CpuState::AwaitingKey(x) => (1, x),
+            CpuState::Exited => (2, 0)
+        };
+        cpu.extend([state, key_reg, self.wait_key.unwrap_or(0xFF), self.vblank as u8, self.stalled as u8]);
+        cpu.extend(self.frame_pos.to_le_bytes());
+        cpu.extend(self.frame_budget.to_le_bytes());
+        cpu.extend(self.ips_carry.to_le_bytes());
+        let (kind, value) = match self.speed {
+            Speed::InstructionsPerFrame(n) => (0, n),
+            Speed::InstructionsPerSecond(n) => (1, n)
+        };
+        cpu.push(kind);
+        cpu.extend(value.to_le_bytes());
+        section(&mut out, b"CPU ", &cpu);
+
+        section(&mut out, b"MEM ", &self.ram.dump());
+
+        let display = &self.display;
+        let mut disp = vec![display.is_hires() as u8, display.selected_planes(), PLANE_COUNT as u8];
+        for plane in 0..PLANE_COUNT {
+            disp.extend(pack(&display.get_plane(plane)));
+        }
+        section(&mut out, b"DISP", &disp);
+
+        let keys = (0..16).fold(0u16, |mask, key| mask | ((self.keyboard.get_key(key) as u16) << key));
+        section(&mut out, b"KEYS", &keys.to_le_bytes());
+
+        let mut audio = self.audio.get_pattern().to_vec();
+        audio.push(self.audio.get_pitch());
+        section(&mut out, b"AUDI", &audio);
+
+        let rpl: Vec<u8> = (0..RPL_COUNT).map(|n| regs.get_rpl(n)).collect();
+        section(&mut out, b"RPL ", &rpl);
+
+        section(&mut out, b"QRKS", &self.quirks.to_bytes());
+
+        if let Some(vip) = &self.vip {
+            let mut clock = vip.frame_cycle().to_le_bytes().to_vec();
+            clock.extend(vip.total_cycles().to_le_bytes());
+            clock.push(vip.has_waited() as u8);
+            section(&mut out, b"VIP ", &clock);
+        }
+
+        if let Some(rng) = self.rng.save_state() {
+            section(&mut out, b"RNG ", &rng);
+        }
+
+        binary::seal(&mut out);
+        out
+    }
+
+    /// Load a state written by `save_state`, the system is unchanged
+    /// if the state can't be loaded
+    ///
+    /// The random source is restored when it is the same kind of source
+    /// that saved the state, otherwise it is left as it is.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - the saved state
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let snapshot = self.decode_state(data)?;
+        self.registers = snapshot.registers;
+        self.state = snapshot.state;
+        self.wait_key = snapshot.wait_key;
+        self.vblank = snapshot.vblank;
+        self.stalled = snapshot.stalled;
+        self.frame_pos = snapshot.frame_pos;
+        self.frame_budget = snapshot.frame_budget;
+        self.ips_carry = snapshot.ips_carry;
+        self.speed = snapshot.speed;
+        self.display = snapshot.display;
+        self.keyboard = snapshot.keyboard;
+        self.audio = snapshot.audio;
+        if let Some(quirks) = snapshot.quirks {
+            self.quirks = quirks;
+        }
+        self.vip = snapshot.vip;
+        if let Some(rng) = snapshot.rng {
+            self.rng.load_state(&rng);
+        }
+        // Checked against the bus size while decoding, so this can't fail
+        self.ram.restore(&snapshot.memory).map_err(|_| StateError::InvalidSection("MEM "))
+    }
+
+    /// Check and decode a state without touching the system
+    fn decode_state(&self, data: &[u8]) -> Result<Snapshot, StateError> {
+        let (mut body, _) = binary::open(data, MAGIC, FORMAT_MAJOR)?;
+
+        // Split into sections, the last one with a tag wins
+        let mut sections: Vec<([u8; 4], &[u8])> = Vec::new();
+        while !body.is_empty() {
+            let mut tag = [0; 4];
+            tag.copy_from_slice(body.take(4)?);
+            let len = body.u32()? as usize;
+            sections.push((tag, body.take(len)?));
+        }
+        let find = |tag: &[u8; 4]| sections.iter().rev().find(|(t, _)| t == tag).map(|&(_, payload)| Reader::init(payload));
+
+        let mut cpu = find(b"CPU ").ok_or(StateError::MissingSection("CPU "))?;
+        let invalid = |_| StateError::InvalidSection("CPU ");
+        let mut registers = ChipRegisters::init();
+        for x in 0..16 {
+            registers.set_gp(x, cpu.u8().map_err(invalid)?);
+        }
+        registers.set_i(cpu.u16().map_err(invalid)?);
+        registers.set_pc(cpu.u16().map_err(invalid)?);
+        registers.set_d(cpu.u8().map_err(invalid)?);
+        registers.set_s(cpu.u8().map_err(invalid)?);
+        for _ in 0..cpu.u8().map_err(invalid)? {
+            registers.push_stack(cpu.u16().map_err(invalid)?).map_err(|_| StateError::InvalidSection("CPU "))?;
+        }
+        let state = match (cpu.u8().map_err(invalid)?, cpu.u8().map_err(invalid)?) {
+            (0, _) => CpuState::Running,
+            (1, x) if x < 16 => CpuState::AwaitingKey(x),
+            (2, _) => CpuState::Exited,
+            _ => return Err(StateError::InvalidSection("CPU "))
+        };
+        let wait_key = match cpu.u8().map_err(invalid)? {
+            key if key < 16 => Some(key),
+            _ => None
+        };
+        let vblank = cpu.bool().map_err(invalid)?;
+        let stalled = cpu.bool().map_err(invalid)?;
+        let frame_pos = cpu.u32().map_err(invalid)?;
+        let frame_budget = cpu.u32().map_err(invalid)?;
+        let ips_carry = cpu.u32().map_err(invalid)?;
+        let speed = match (cpu.u8().map_err(invalid)?, cpu.u32().map_err(invalid)?) {
+            (0, n) => Speed::InstructionsPerFrame(n),
+            (1, n) => Speed::InstructionsPerSecond(n),
+            _ => return Err(StateError::InvalidSection("CPU "))
+        };
+
+        let memory = find(b"MEM ").ok_or(StateError::MissingSection("MEM "))?.rest().to_vec();
+        if memory.len() != self.ram.size() {
+            return Err(StateError::MemorySize {expected: self.ram.size(), found: memory.len()});
+        }
+
+        let mut display = ChipDisplay::init();
+        if let Some(mut disp) = find(b"DISP") {
+            let invalid = |_| StateError::InvalidSection("DISP");
+            display.set_hires(disp.bool().map_err(invalid)?);
+            display.select_planes(disp.u8().map_err(invalid)?);
+            let pixels = display.width() * display.height();
+            // Planes this version doesn't know about are skipped
+            for plane in 0..disp.u8().map_err(invalid)? as usize {
+                let bytes = disp.take(pixels.div_ceil(8)).map_err(invalid)?;
+                if plane < PLANE_COUNT {
+                    display.set_plane(plane, &unpack(bytes, pixels));
+                }
+            }
+        }
+
+        let mut keyboard = ChipKeyboard::init();
+        if let Some(mut keys) = find(b"KEYS") {
+            let mask = keys.u16().map_err(|_| StateError::InvalidSection("KEYS"))?;
+            for key in 0..16 {
+                keyboard.set_key(key, mask & (1 << key) != 0);
+            }
+        }
+
+        let mut audio = ChipAudio::init();
+        if let Some(mut audi) = find(b"AUDI") {
+            let invalid = |_| StateError::InvalidSection("AUDI");
+            audio.set_pattern(audi.take(PATTERN_LEN).map_err(invalid)?);
+            audio.set_pitch(audi.u8().map_err(invalid)?);
+        }
+
+        if let Some(mut rpl) = find(b"RPL ") {
+            let flags = rpl.take(RPL_COUNT).map_err(|_| StateError::InvalidSection("RPL "))?;
+            for (n, &flag) in flags.iter().enumerate() {
+                registers.set_rpl(n, flag);
+            }
+        }
+
+        let quirks = match find(b"QRKS") {
+            Some(qrks) => Some(Quirks::from_bytes(qrks.rest()).ok_or(StateError::InvalidSection("QRKS"))?),
+            None => None
+        };
+
+        let vip = match find(b"VIP ") {
+            Some(mut clock) => {
+                let invalid = |_| StateError::InvalidSection("VIP ");
+                let frame_cycle = clock.u32().map_err(invalid)?;
+                // The clock always stops short of the interrupt
+                if frame_cycle >= INTERPRETER_CYCLES {
+                    return Err(StateError::InvalidSection("VIP "));
+                }
+                let total = clock.u64().map_err(invalid)?;
+                Some(VipClock::init_with_state(frame_cycle, total, clock.bool().map_err(invalid)?))
+            },
+            None => None
+        };
+
+        let rng = find(b"RNG ").map(|rng| rng.rest().to_vec());
+
+        Ok(Snapshot {
+            registers, state, wait_key, vblank, stalled, frame_pos, frame_budget, ips_carry, speed,
+            memory, display, keyboard, audio, quirks, vip, rng
+        })
+    }
+}
@@ -1,37 +1,115 @@
 
 // Standard Library Modules //
+use std::convert::TryFrom;
 use std::error;
 use std::fmt;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 // Local Modules Use //
-use memory::ChipMemory;
-use registers::ChipRegisters;
+use memory::{ChipMemory, MemResult, OutOfRange, BIG_FONT_ADDR, FONT_ADDR};
+use registers::{ChipRegisters, StackError};
 use display::ChipDisplay;
 use keyboard::ChipKeyboard;
-
-// Modules From Crates.io //
-use rand::Rng;
+use audio::ChipAudio;
+use quirks::{Quirks, IndexIncrement, KeyWait};
+use instruction::Instruction;
+use rng::{RngSource, SeededRng};
+use bus::Bus;
+use timing::{TimingMode, VipClock};
 
 // Local Modules //
 pub mod memory;
 pub mod registers;
 pub mod display;
 pub mod keyboard;
+pub mod audio;
+pub mod quirks;
+pub mod instruction;
+pub mod rng;
+pub mod timing;
+pub mod bus;
+pub mod debugger;
+pub mod gdb;
+pub mod disasm;
+pub mod asm;
+pub mod octo;
+pub mod binary;
+pub mod state;
+pub mod rewind;
+pub mod movie;
+pub mod testing;
+pub mod trace;
+pub mod profile;
+#[cfg(feature = "jit")]
+pub mod jit;
 
 
 // Define a opcode execution error type //
 pub type ExResult<T> = std::result::Result<T, ExError>;
 
-#[derive(Debug, Clone)]
-pub struct ExError {
-    opcode: u16
+/// Errors that stop execution of an instruction
+///
+/// Every variant carries the address (`pc`) and first opcode word
+/// of the instruction that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExError {
+    /// `CALL` with all 16 stack entries in use
+    StackOverflow { pc: u16, opcode: u16 },
+    /// `RET` with an empty stack
+    StackUnderflow { pc: u16, opcode: u16 },
+    /// A fetch, read or write outside of ram
+    AddressOutOfRange { pc: u16, opcode: u16, addr: usize },
+    /// A key instruction whose Vx holds a key above 0xF
+    InvalidKey { pc: u16, opcode: u16, key: u8 },
+    /// The program counter points at an odd address
+    MisalignedPc { pc: u16, opcode: u16 },
+    /// The opcode isn't a known instruction
+    UnknownOpcode { pc: u16, opcode: u16 },
+}
+
+impl ExError {
+    /// Address of the instruction that failed
+    pub fn pc(&self) -> u16 {
+        match *self {
+            ExError::StackOverflow { pc, .. } |
+            ExError::StackUnderflow { pc, .. } |
+            ExError::AddressOutOfRange { pc, .. } |
+            ExError::InvalidKey { pc, .. } |
+            ExError::MisalignedPc { pc, .. } |
+            ExError::UnknownOpcode { pc, .. } => pc
+        }
+    }
+
+    /// Opcode of the instruction that failed
+    pub fn opcode(&self) -> u16 {
+        match *self {
+            ExError::StackOverflow { opcode, .. } |
+            ExError::StackUnderflow { opcode, .. } |
+            ExError::AddressOutOfRange { opcode, .. } |
+            ExError::InvalidKey { opcode, .. } |
+            ExError::MisalignedPc { opcode, .. } |
+            ExError::UnknownOpcode { opcode, .. } => opcode
+        }
+    }
 }
 
 impl fmt::Display for ExError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "invalid opcode previded for execution: {:04x}", self.opcode)
+        match *self {
+            ExError::StackOverflow { pc, opcode } =>
+                write!(f, "stack overflow at {:04x} ({:04x})", pc, opcode),
+            ExError::StackUnderflow { pc, opcode } =>
+                write!(f, "stack underflow at {:04x} ({:04x})", pc, opcode),
+            ExError::AddressOutOfRange { pc, opcode, addr } =>
+                write!(f, "address {:04x} out of range at {:04x} ({:04x})", addr, pc, opcode),
+            ExError::InvalidKey { pc, opcode, key } =>
+                write!(f, "invalid key {:02x} at {:04x} ({:04x})", key, pc, opcode),
+            ExError::MisalignedPc { pc, opcode } =>
+                write!(f, "program counter is not even: {:04x} ({:04x})", pc, opcode),
+            ExError::UnknownOpcode { pc, opcode } =>
+                write!(f, "invalid opcode previded for execution at {:04x}: {:04x}", pc, opcode),
+        }
     }
 }
 
@@ -42,63 +120,372 @@ impl error::Error for ExError {
     }
 }
 
-/// Representation of a 2 byte chip8 opcode
-struct Opcode {
-    h1: u16,
-    v1: u16, 
-    v2: u16, 
-    v3: u16
+/// Execution state of the Chip8 CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuState {
+    /// Executing instructions
+    Running,
+    /// Halted by `Fx0A` until a key is pressed (or released), the
+    /// key will be stored in the given register
+    AwaitingKey(u8),
+    /// The program executed `00FD` (exit)
+    Exited,
 }
 
-impl Opcode {
-    /// create a new opcode struct from a 2 byte opcode
-    fn new(opcode: u16) -> Self {
-        let h1 = (opcode >> 12) & 0xf;
-        let v1 = (opcode >> 8) & 0xf;
-        let v2 = (opcode >> 4) & 0xf;
-        let v3 = (opcode) & 0xf;
-        Opcode {h1, v1, v2, v3}
-    }
+/// Outcome of a single `step`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    /// Opcode that was executed, `None` when the CPU isn't running
+    pub opcode: Option<u16>,
+    /// CPU state after the step
+    pub state: CpuState,
+    /// The screen if it has been modified
+    pub display: Option<Vec<bool>>,
+    /// True if this step finished a 60 Hz frame and the timers ticked
+    pub frame_done: bool,
+    /// Machine cycles consumed, only reported in `TimingMode::Vip`
+    pub cycles: Option<u32>,
+}
+
+/// Outcome of a single `run_frame`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// Number of opcodes executed during the frame
+    pub instructions: u32,
+    /// CPU state after the frame
+    pub state: CpuState,
+    /// The screen if it was modified during the frame
+    pub display: Option<Vec<bool>>,
 }
 
+/// Emulation speed, the timers always tick at 60 Hz
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speed {
+    /// A fixed number of instructions per 60 Hz frame
+    InstructionsPerFrame(u32),
+    /// A target number of instructions per second, spread over the frames
+    InstructionsPerSecond(u32),
+}
+
+impl Default for Speed {
+    /// Roughly the speed of the original COSMAC VIP interpreter
+    fn default() -> Self {
+        Speed::InstructionsPerFrame(11)
+    }
+}
 
 /// A representation of the Chip8 Architecture
-pub struct ChipSystem {
+/// 
+/// Memory is accessed through a `Bus`, plain `ChipMemory` unless
+/// the system is built with `init_with_bus` or wrapped with `map_bus`.
+pub struct ChipSystem<B: Bus = ChipMemory> {
     /// Registers and related methods
     pub registers: ChipRegisters,
     /// Display and related methods
     pub display: ChipDisplay,
     /// RAM and related functions
-    pub ram: ChipMemory,
+    pub ram: B,
     /// Keyboard and related functions
     pub keyboard: ChipKeyboard,
+    /// XO-CHIP audio pattern and pitch
+    pub audio: ChipAudio,
+    /// Interpretation of ambiguous opcodes
+    pub quirks: Quirks,
+    /// Random source for `Cxkk`
+    rng: Box<dyn RngSource>,
+    /// True once a vertical blank has passed since the last draw
+    vblank: bool,
+    /// Execution state
+    state: CpuState,
+    /// Instructions executed per frame
+    speed: Speed,
+    /// Instruction slots used in the current frame
+    frame_pos: u32,
+    /// Instruction slots available in the current frame
+    frame_budget: u32,
+    /// Instructions per second carried over between frames
+    ips_carry: u32,
+    /// True when a draw is held back by the display wait quirk
+    stalled: bool,
+    /// Machine cycle clock when running in `TimingMode::Vip`
+    vip: Option<VipClock>,
+    /// Key pressed while awaiting a key release
+    wait_key: Option<u8>,
+    /// Native code compiler used by `run_frame`, if enabled
+    #[cfg(feature = "jit")]
+    jit: Option<jit::Jit>,
 }
 
 impl ChipSystem {
-    /// Initialize the Chip8 System
+    /// Initialize the Chip8 System with the default (COSMAC VIP) quirks
     pub fn init() -> Self {
-        let ram = ChipMemory::init();
+        ChipSystem::init_with_quirks(Quirks::default())
+    }
+
+    /// Initialize the Chip8 System with a specific set of quirks
+    /// 
+    /// # Arguments
+    /// 
+    /// * `quirks` - how ambiguous opcodes should be interpreted
+    pub fn init_with_quirks(quirks: Quirks) -> Self {
+        let ram = ChipMemory::init_with_size(quirks.memory_size);
+        ChipSystem::init_with_bus(ram, quirks)
+    }
+}
+
+impl<B: Bus> ChipSystem<B> {
+    /// Initialize the Chip8 System with a custom memory bus, the
+    /// bus size should match `quirks.memory_size`
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ram` - the bus programs are executed from
+    /// * `quirks` - how ambiguous opcodes should be interpreted
+    pub fn init_with_bus(ram: B, quirks: Quirks) -> Self {
         let disp = ChipDisplay::init();
         let reg = ChipRegisters::init();
         let key = ChipKeyboard::init();
         ChipSystem {
             registers: reg,
             display: disp,
-            ram: ram,
-            keyboard: key
+            ram,
+            keyboard: key,
+            audio: ChipAudio::init(),
+            quirks,
+            rng: Box::new(SeededRng::from_entropy()),
+            vblank: true,
+            state: CpuState::Running,
+            speed: Speed::default(),
+            frame_pos: 0,
+            frame_budget: 0,
+            ips_carry: 0,
+            stalled: false,
+            vip: None,
+            wait_key: None,
+            #[cfg(feature = "jit")]
+            jit: None
+        }.with_budget()
+    }
+
+    /// Wrap or replace the memory bus, keeping the rest of the
+    /// system state
+    /// 
+    /// # Arguments
+    /// 
+    /// * `map` - builds the new bus from the current one
+    pub fn map_bus<C: Bus, F: FnOnce(B) -> C>(self, map: F) -> ChipSystem<C> {
+        ChipSystem {
+            registers: self.registers,
+            display: self.display,
+            ram: map(self.ram),
+            keyboard: self.keyboard,
+            audio: self.audio,
+            quirks: self.quirks,
+            rng: self.rng,
+            vblank: self.vblank,
+            state: self.state,
+            speed: self.speed,
+            frame_pos: self.frame_pos,
+            frame_budget: self.frame_budget,
+            ips_carry: self.ips_carry,
+            stalled: self.stalled,
+            vip: self.vip,
+            wait_key: self.wait_key,
+            #[cfg(feature = "jit")]
+            jit: self.jit
+        }
+    }
+
+    /// Replace the random source used by `Cxkk`
+    /// 
+    /// # Arguments
+    /// 
+    /// * `rng` - the new random source
+    pub fn set_rng(&mut self, rng: Box<dyn RngSource>) {
+        self.rng = rng;
+    }
+
+    /// Use the default generator with a fixed seed, so runs of
+    /// the same ROM with the same input are repeatable
+    /// 
+    /// # Arguments
+    /// 
+    /// * `seed` - generator seed
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Box::new(SeededRng::new(seed));
+    }
+
+    /// Returns true once the program has executed `00FD` (exit)
+    pub fn has_exited(&self) -> bool {
+        self.state == CpuState::Exited
+    }
+
+    /// Get the current execution state
+    pub fn state(&self) -> CpuState {
+        self.state
+    }
+
+    /// Press a key (0-15), completes a pending `Fx0A` wait
+    /// when `quirks.key_wait` is `Press`
+    /// 
+    /// # Arguments
+    /// 
+    /// * `key` - which key was pressed, keys above 15 are ignored
+    pub fn press(&mut self, key: u8) {
+        if key > 0xF {
+            return;
+        }
+        self.keyboard.set_key(key, true);
+        if let CpuState::AwaitingKey(x) = self.state {
+            match self.quirks.key_wait {
+                KeyWait::Press => self.finish_key_wait(x, key),
+                KeyWait::Release => self.wait_key = Some(key)
+            }
+        }
+    }
+
+    /// Release a key (0-15), completes a pending `Fx0A` wait when
+    /// `quirks.key_wait` is `Release` and the key was pressed during the wait
+    /// 
+    /// # Arguments
+    /// 
+    /// * `key` - which key was released, keys above 15 are ignored
+    pub fn release(&mut self, key: u8) {
+        if key > 0xF {
+            return;
+        }
+        self.keyboard.set_key(key, false);
+        if let CpuState::AwaitingKey(x) = self.state {
+            if self.quirks.key_wait == KeyWait::Release && self.wait_key == Some(key) {
+                self.finish_key_wait(x, key);
+            }
+        }
+    }
+
+    /// Store the key that ended a `Fx0A` wait and resume execution
+    fn finish_key_wait(&mut self, x: u8, key: u8) {
+        self.registers.set_gp(x as usize, key);
+        self.wait_key = None;
+        self.state = CpuState::Running;
+    }
+
+    /// Compute the first frame budget for a new system
+    fn with_budget(mut self) -> Self {
+        self.frame_budget = self.next_budget();
+        self
+    }
+
+    /// Set the emulation speed, takes effect from the next frame
+    /// 
+    /// # Arguments
+    /// 
+    /// * `speed` - instructions per frame or per second
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+        self.ips_carry = 0;
+    }
+
+    /// Get the emulation speed
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Choose how instructions are paced against the 60 Hz timers,
+    /// switching to `TimingMode::Vip` starts a new cycle clock
+    /// 
+    /// # Arguments
+    /// 
+    /// * `mode` - instruction count or COSMAC VIP machine cycle timing
+    pub fn set_timing(&mut self, mode: TimingMode) {
+        self.vip = match mode {
+            TimingMode::Instructions => None,
+            TimingMode::Vip => Some(VipClock::init())
+        };
+    }
+
+    /// Get the current timing mode
+    pub fn timing(&self) -> TimingMode {
+        match self.vip {
+            Some(_) => TimingMode::Vip,
+            None => TimingMode::Instructions
         }
     }
 
-    /// Return a random u8
-    fn random_byte() -> u8 {
-        let mut rng = rand::thread_rng();
-        let rand_byte: u8 = rng.gen();
-        return rand_byte;
+    /// Get the machine cycle clock, only present in `TimingMode::Vip`
+    pub fn vip_clock(&self) -> Option<&VipClock> {
+        self.vip.as_ref()
+    }
+
+    /// Enable or disable compiling hot code to native code, returns true
+    /// if the JIT is enabled
+    /// 
+    /// Compiled blocks are only used by `run_frame`, `step` always
+    /// interprets a single instruction. The JIT is never used in
+    /// `TimingMode::Vip`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `enabled` - true to enable the JIT, fails if the host isn't supported
+    #[cfg(feature = "jit")]
+    pub fn set_jit_enabled(&mut self, enabled: bool) -> bool {
+        self.jit = match enabled {
+            true => jit::Jit::init(),
+            false => None
+        };
+        self.jit.is_some()
+    }
+
+    /// Get the JIT, present when enabled
+    #[cfg(feature = "jit")]
+    pub fn jit(&self) -> Option<&jit::Jit> {
+        self.jit.as_ref()
+    }
+
+    /// Run the compiled block at the program counter if there is one
+    /// and it ends before the frame does, returns the number of
+    /// instructions executed
+    #[cfg(feature = "jit")]
+    fn run_jit_block(&mut self) -> Option<u32> {
+        if self.state != CpuState::Running || self.vip.is_some() || !self.registers.get_pc().is_multiple_of(2) {
+            return None;
+        }
+        // Leave the last instruction slot of the frame to `step`
+        let budget = self.frame_budget.checked_sub(self.frame_pos + 1)?;
+        let jit = self.jit.as_mut()?;
+        let executed = jit.run_block(&mut self.ram, &mut self.registers, &self.quirks, budget)?;
+        self.frame_pos += executed;
+        Some(executed)
+    }
+
+    /// Number of instruction slots in the next frame, at least one
+    fn next_budget(&mut self) -> u32 {
+        let budget = match self.speed {
+            Speed::InstructionsPerFrame(ipf) => ipf,
+            Speed::InstructionsPerSecond(ips) => {
+                let total = ips + self.ips_carry;
+                self.ips_carry = total % 60;
+                total / 60
+            }
+        };
+        budget.max(1)
+    }
+
+    /// Signal a vertical blank, timers are decremented and
+    /// a pending draw (display wait quirk) is released
+    fn tick(&mut self) {
+        self.registers.decr_d();
+        self.registers.decr_s();
+        self.rng.vblank();
+        self.vblank = true;
+        self.frame_pos = 0;
+        self.frame_budget = self.next_budget();
     }
 
     /// Execute a Chip8 Opcode
     /// 
-    /// This function can deal with the original 35 Chip8 opcodes
+    /// This function can deal with the original 35 Chip8 opcodes, the
+    /// SUPER-CHIP 1.1 and the XO-CHIP extensions, ambiguous opcodes are
+    /// interpreted according to `quirks`
     /// 
     /// # Arguments
     /// 
@@ -121,18 +508,18 @@ impl ChipSystem {
     /// 13. 0x8xy3 - Logical XOR the registers `Vx = Vx ^ Vy`
     /// 14. 0x8xy4 - Add the registers with overflow check `Vx = Vx + Vy`
     /// 15. 0x8xy5 - Subtract the registers `Vx = Vx - Vy`, on borrow `Vf = 0 else 1`
-    /// 16. 0x8xy6 - Logical right shift, LSB to `Vf`, `Vx = Vx >> 1`
+    /// 16. 0x8xy6 - Logical right shift, LSB to `Vf`, `Vx = Vx >> 1` (or `Vy >> 1`)
     /// 17. 0x8xy7 - Subtract the registers `Vx = Vy - Vx`, on borrow `Vf = 0 else 1`
-    /// 18. 0x8xyE - Logical left shift, MSB to `Vf`, `Vx = Vx << 1`
+    /// 18. 0x8xyE - Logical left shift, MSB to `Vf`, `Vx = Vx << 1` (or `Vy << 1`)
     /// 19. 0x9xy0 - SKip next instruction when `Vx != Vy`
     /// 20. 0xAnnn - Set register `I` to `nnn`, `I = nnn`
-    /// 21. 0xBnnn - Jump to location `PC = nnn + V0`
+    /// 21. 0xBnnn - Jump to location `PC = nnn + V0` (or `nnn + Vx`)
     /// 22. 0xCxkk - Set register to random value `Vx = rand<u8> & kk`
     /// 23. 0xDxyn - Draw a sprite on the screen at xy of height n
     /// 24. 0xEx9E - Skip next instruction if key with value `Vx` is pressed
     /// 25. 0xExA1 - Skip next instruction if key with value `Vx` is not pressed
     /// 26. 0xFx07 - Set the value in `Vx` to the delay timer
-    /// 27. 0xFx0A - Wait for keypress, store value in `Vx`
+    /// 27. 0xFx0A - Wait for keypress, store value in `Vx` (see `CpuState::AwaitingKey`)
     /// 28. 0xFx15 - Set the delay timer to value in `Vx`
     /// 29. 0xFx18 - Set the sound timer to value in `Vx`
     /// 30. 0xFx1E - Set value of `I` to `I = I + Vx`
@@ -140,355 +527,634 @@ impl ChipSystem {
     /// 32. 0xFx33 - Store BCD of `Vx` in `I, I+1, I+2`
     /// 33. 0xFx55 - Store `V0 -> Vx` at I
     /// 34. 0xFx65 - Retrieve `V0 -> Vx` from I
+    ///
+    /// ## SUPER-CHIP 1.1 Opcodes
+    /// 0. 0x00Cn - Scroll display down `n` pixels
+    /// 1. 0x00FB - Scroll display right 4 pixels
+    /// 2. 0x00FC - Scroll display left 4 pixels
+    /// 3. 0x00FD - Exit the interpreter
+    /// 4. 0x00FE - Switch to 64x32 (lo-res) mode
+    /// 5. 0x00FF - Switch to 128x64 (hi-res) mode
+    /// 6. 0xDxy0 - Draw a 16x16 sprite at xy
+    /// 7. 0xFx30 - Set I to location of the big font digit in `Vx`
+    /// 8. 0xFx75 - Store `V0 -> Vx` in the RPL user flags
+    /// 9. 0xFx85 - Retrieve `V0 -> Vx` from the RPL user flags
+    ///
+    /// ## XO-CHIP Opcodes
+    /// 0. 0x00Dn - Scroll display up `n` pixels
+    /// 1. 0x5xy2 - Store `Vx -> Vy` at I, I is unchanged
+    /// 2. 0x5xy3 - Retrieve `Vx -> Vy` from I, I is unchanged
+    /// 3. 0xF000 0xnnnn - Set I to the 16 bit address `nnnn`
+    /// 4. 0xFn01 - Select the drawing planes with bit mask `n`
+    /// 5. 0xF002 - Load the 16 byte audio pattern from I
+    /// 6. 0xFx3A - Set the audio pitch register to `Vx`
     ///  
     pub fn ex_opcode(&mut self, opcode: u16) -> ExResult<()> {
-        let comps = Opcode::new(opcode);
+        let instruction = self.decode_opcode(opcode)?;
+        self.execute(instruction)
+    }
+
+    /// Decode an opcode at the program counter into an instruction
+    fn decode_opcode(&mut self, opcode: u16) -> ExResult<Instruction> {
+        let pc = self.registers.get_pc();
+        // Only the XO-CHIP long load reads past the opcode
+        let decoded = match opcode {
+            0xF000 => {
+                let next = self.ram.fetch_opcode(pc.wrapping_add(2))
+                    .map_err(|e| ExError::AddressOutOfRange {pc, opcode, addr: e.addr})?;
+                Instruction::decode_long(opcode, next)
+            },
+            _ => Instruction::decode(opcode)
+        };
+        decoded.map_err(|_| ExError::UnknownOpcode {pc, opcode})
+    }
+
+    /// Execute a decoded instruction, see `ex_opcode` for
+    /// a description of each opcode
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instruction` - instruction to execute
+    pub fn execute(&mut self, instruction: Instruction) -> ExResult<()> {
+        let pc = self.registers.get_pc();
+        // The opcode is only encoded again when reporting an error
+        let opcode = || instruction.encode();
+        let oob = |e: OutOfRange| ExError::AddressOutOfRange {pc, opcode: opcode(), addr: e.addr};
         let mut update_pc = true;
-        match comps.h1 {
-            0x0 => {
-                match comps.v3 {
-                    // CLS - Clear Display
-                    0 => self.display.clear_display(),
-                    // RET - Return from subroutine
-                    14 => {
-                        let pc: u16 = self.registers.pop_stack();
-                        self.registers.set_pc(pc);
-                        // update_pc = false;
-                    },
-                    // Skip Opcode
-                    _ => {},
-                }
+        match instruction {
+            // SYS Addr - Machine code routine, skip opcode
+            Instruction::Sys(_) => {},
+            // CLS - Clear Display
+            Instruction::Cls => self.display.clear_display(),
+            // RET - Return from subroutine
+            Instruction::Ret => {
+                let ret_pc: u16 = self.registers.pop_stack()
+                    .map_err(|_| ExError::StackUnderflow {pc, opcode: opcode()})?;
+                self.registers.set_pc(ret_pc);
             },
-            // JP - Jumps to address without modifying stack
-            0x1 => {
-                let pc: u16 = (comps.v1 << 8) + (comps.v2 << 4) + comps.v3;
-                self.registers.set_pc(pc);
+            // JP Addr - Jumps to address without modifying stack
+            Instruction::Jp(addr) => {
+                self.registers.set_pc(addr);
                 update_pc = false;
             },
-            // CALL - Jump to address with push to stack
-            0x2 => {
-                let new_pc: u16 = (comps.v1 << 8) + (comps.v2 << 4) + comps.v3;
-                let cur_pc = self.registers.get_pc();
-                self.registers.push_stack(cur_pc);
-                self.registers.set_pc(new_pc);
+            // CALL Addr - Jump to address with push to stack
+            Instruction::Call(addr) => {
+                if let Err(StackError::Overflow) = self.registers.push_stack(pc) {
+                    return Err(ExError::StackOverflow {pc, opcode: opcode()});
+                }
+                self.registers.set_pc(addr);
                 update_pc = false;
             },
             // SE Vx, Byte - Skip instruction if Vx == Byte
-            0x3 => {
-                let comp_val: u8 = ((comps.v2 as u8) << 4) + comps.v3 as u8;
-                let reg_val: u8 = self.registers.get_gp(comps.v1 as usize);
-                if comp_val == reg_val {
-                    self.registers.incr_pc();
+            Instruction::Se(x, byte) => {
+                if self.registers.get_gp(x as usize) == byte {
+                    self.skip_next();
                 }
             },
             // SNE Vx, Byte - Skip instruction if Vx != Byte
-            0x4 => {
-                let comp_val: u8 = ((comps.v2 as u8) << 4) + comps.v3 as u8;
-                let reg_val: u8 = self.registers.get_gp(comps.v1 as usize);
-                if comp_val != reg_val {
-                    self.registers.incr_pc();
+            Instruction::Sne(x, byte) => {
+                if self.registers.get_gp(x as usize) != byte {
+                    self.skip_next();
                 }
             },
             // SE Vx, Vy - Skip instruction if Vx == Vy
-            0x5 => {
-                let reg_x_val: u8 = self.registers.get_gp(comps.v1 as usize);
-                let reg_y_val: u8 = self.registers.get_gp(comps.v2 as usize);
-                if reg_x_val == reg_y_val {
-                    self.registers.incr_pc();
+            Instruction::SeReg(x, y) => {
+                if self.registers.get_gp(x as usize) == self.registers.get_gp(y as usize) {
+                    self.skip_next();
                 }
             },
             // LD Vx, Byte - Load byte value into Vx (Vx = Byte)
-            0x6 => {
-                let value: u8 = ((comps.v2 as u8) << 4) + comps.v3 as u8;
-                self.registers.set_gp(comps.v1 as usize, value);
-            },
+            Instruction::Ld(x, byte) => self.registers.set_gp(x as usize, byte),
             // ADD Vx, Byte - Add byte value to Vx (Vx += Byte) no carry flag
-            0x7 => {
-                let value: u16 = ((comps.v2) << 4) + comps.v3;
-                self.registers.add_gp(comps.v1 as usize, (value & 0xff) as u8);
-            },
-            0x8 => {
-                match comps.v3 {
-                    // LD Vx, Vy - Store value of Vy in Vx (Vx = Vy)
-                    0x0 => {
-                        let reg_y_val = self.registers.get_gp(comps.v2 as usize);
-                        self.registers.set_gp(comps.v1 as usize, reg_y_val);
-                    },
-                    // OR Vx, Vy - Bitwise OR on Vx, Vy store in Vx (Vx = Vx | Vy)
-                    0x1 => {
-                        let reg_x_val = self.registers.get_gp(comps.v1 as usize);
-                        let reg_y_val = self.registers.get_gp(comps.v2 as usize);
-                        let value = reg_x_val | reg_y_val;
-                        self.registers.set_gp(comps.v1 as usize, value);
-                    },
-                    // AND Vx, Vy - Bitwise AND on Vx, Vy store in Vx (Vx = Vx & Vy)
-                    0x2 => {
-                        let reg_x_val = self.registers.get_gp(comps.v1 as usize);
-                        let reg_y_val = self.registers.get_gp(comps.v2 as usize);
-                        let value = reg_x_val & reg_y_val;
-                        self.registers.set_gp(comps.v1 as usize, value);
-                    },
-                    // XOR Vx, Vy - Bitwise XOR on Vx, Vy store in Vx (Vx = Vx ^ Vy)
-                    0x3 => {
-                        let reg_x_val = self.registers.get_gp(comps.v1 as usize);
-                        let reg_y_val = self.registers.get_gp(comps.v2 as usize);
-                        let value = reg_x_val ^ reg_y_val;
-                        self.registers.set_gp(comps.v1 as usize, value);
-                    },
-                    // ADD Vx, Vy - Add Vx, Vy if > 255 set Vf to 1 (Vx = Vx + Vy)
-                    0x4 => {
-                        let reg_x_val = self.registers.get_gp(comps.v1 as usize);
-                        let reg_y_val = self.registers.get_gp(comps.v2 as usize);
-                        let holder: u16 = reg_x_val as u16 + reg_y_val as u16;
-                        match holder > 255 {
-                            true => self.registers.set_gp(15, 1),
-                            false => self.registers.set_gp(15, 0)
-                        }
-                        self.registers.set_gp(comps.v1 as usize, (holder & 0xff) as u8);
-                    },
-                    // SUB Vx, Vy - Subtract Vx, Vy if Vx < Vy set Vf to 0 (Vx = Vx - Vy)
-                    0x5 => {
-                        let reg_x_val = self.registers.get_gp(comps.v1 as usize);
-                        let reg_y_val = self.registers.get_gp(comps.v2 as usize);
-                        match reg_x_val < reg_y_val {
-                            true => self.registers.set_gp(15, 0),
-                            false => self.registers.set_gp(15, 1)
-                        }
-                        let holder = reg_x_val - reg_y_val;
-                        self.registers.set_gp(comps.v1 as usize, holder);
-                    },
-                    // SHR Vx, _ - Shift Vx right by 1, set Vf to LSB (Vx = Vx >> 1)
-                    0x6 => {
-                        let mut reg_x_val = self.registers.get_gp(comps.v1 as usize);
-                        self.registers.set_gp(15, reg_x_val & 0x01);
-                        reg_x_val = reg_x_val >> 1; 
-                        self.registers.set_gp(comps.v1 as usize, reg_x_val);
-                    },
-                    // SUBN Vx, Vy - Subtract Vy, Vx if Vy < Vx set Vf to 0 (Vx = Vy - Vx)
-                    0x7 => {
-                        let reg_x_val = self.registers.get_gp(comps.v1 as usize);
-                        let reg_y_val = self.registers.get_gp(comps.v2 as usize);
-                        match reg_y_val < reg_x_val {
-                            true => self.registers.set_gp(15, 0),
-                            false => self.registers.set_gp(15, 1)
-                        }
-                        let holder = reg_y_val - reg_x_val;
-                        self.registers.set_gp(comps.v1 as usize, holder);
-                    },
-                    // SHL Vx, _ - Shift Vx left by 1, set Vf to MSB (Vx = Vx << 1)
-                    0xE => {
-                        let mut reg_x_val = self.registers.get_gp(comps.v1 as usize);
-                        self.registers.set_gp(15, reg_x_val & 0x80);
-                        reg_x_val = reg_x_val << 1;
-                        self.registers.set_gp(comps.v1 as usize, reg_x_val);
-                    },
-                    _ => return Err(ExError {opcode})
+            Instruction::Add(x, byte) => self.registers.add_gp(x as usize, byte),
+            // LD Vx, Vy - Store value of Vy in Vx (Vx = Vy)
+            Instruction::LdReg(x, y) => {
+                let reg_y_val = self.registers.get_gp(y as usize);
+                self.registers.set_gp(x as usize, reg_y_val);
+            },
+            // OR Vx, Vy - Bitwise OR on Vx, Vy store in Vx (Vx = Vx | Vy)
+            Instruction::Or(x, y) => self.logic_op(x, y, |a, b| a | b),
+            // AND Vx, Vy - Bitwise AND on Vx, Vy store in Vx (Vx = Vx & Vy)
+            Instruction::And(x, y) => self.logic_op(x, y, |a, b| a & b),
+            // XOR Vx, Vy - Bitwise XOR on Vx, Vy store in Vx (Vx = Vx ^ Vy)
+            Instruction::Xor(x, y) => self.logic_op(x, y, |a, b| a ^ b),
+            // ADD Vx, Vy - Add Vx, Vy if > 255 set Vf to 1 (Vx = Vx + Vy)
+            Instruction::AddReg(x, y) => {
+                let reg_x_val = self.registers.get_gp(x as usize);
+                let reg_y_val = self.registers.get_gp(y as usize);
+                let holder: u16 = reg_x_val as u16 + reg_y_val as u16;
+                // The flag is written last so it wins when x is F
+                self.registers.set_gp(x as usize, (holder & 0xff) as u8);
+                match holder > 255 {
+                    true => self.registers.set_gp(15, 1),
+                    false => self.registers.set_gp(15, 0)
                 }
             },
+            // SUB Vx, Vy - Subtract Vx, Vy if Vx < Vy set Vf to 0 (Vx = Vx - Vy)
+            Instruction::Sub(x, y) => {
+                let reg_x_val = self.registers.get_gp(x as usize);
+                let reg_y_val = self.registers.get_gp(y as usize);
+                let holder = reg_x_val.wrapping_sub(reg_y_val);
+                self.registers.set_gp(x as usize, holder);
+                match reg_x_val < reg_y_val {
+                    true => self.registers.set_gp(15, 0),
+                    false => self.registers.set_gp(15, 1)
+                }
+            },
+            // SHR Vx, Vy - Shift Vx (or Vy) right by 1, set Vf to LSB (Vx = Vx >> 1)
+            Instruction::Shr(x, y) => {
+                let src = if self.quirks.shift_vy { y } else { x };
+                let reg_val = self.registers.get_gp(src as usize);
+                self.registers.set_gp(x as usize, reg_val >> 1);
+                self.registers.set_gp(15, reg_val & 0x01);
+            },
+            // SUBN Vx, Vy - Subtract Vy, Vx if Vy < Vx set Vf to 0 (Vx = Vy - Vx)
+            Instruction::Subn(x, y) => {
+                let reg_x_val = self.registers.get_gp(x as usize);
+                let reg_y_val = self.registers.get_gp(y as usize);
+                let holder = reg_y_val.wrapping_sub(reg_x_val);
+                self.registers.set_gp(x as usize, holder);
+                match reg_y_val < reg_x_val {
+                    true => self.registers.set_gp(15, 0),
+                    false => self.registers.set_gp(15, 1)
+                }
+            },
+            // SHL Vx, Vy - Shift Vx (or Vy) left by 1, set Vf to MSB (Vx = Vx << 1)
+            Instruction::Shl(x, y) => {
+                let src = if self.quirks.shift_vy { y } else { x };
+                let reg_val = self.registers.get_gp(src as usize);
+                self.registers.set_gp(x as usize, reg_val << 1);
+                self.registers.set_gp(15, reg_val >> 7);
+            },
             // SNE Vx, Vy - Skip next instruction if Vx != Vy
-            0x9 => {
-                let reg_x_val = self.registers.get_gp(comps.v1 as usize);
-                let reg_y_val = self.registers.get_gp(comps.v2 as usize);
-                if reg_x_val != reg_y_val {
-                    self.registers.incr_pc();
+            Instruction::SneReg(x, y) => {
+                if self.registers.get_gp(x as usize) != self.registers.get_gp(y as usize) {
+                    self.skip_next();
                 }
             },
             // LD I, Addr (12bit) - Register I is set to the address
-            0xA => {
-                let value = (comps.v1 << 8) + (comps.v2 << 4) + comps.v3;
-                self.registers.set_i(value);
-            },
-            // JP V0, Addr (12bit) - Jump to the location Addr + V0
-            0xB => {
-                let reg_v0_val = self.registers.get_gp(0);
-                let address = (comps.v1 << 8) + (comps.v2 << 4) + comps.v3;
-                self.registers.set_pc(address + reg_v0_val as u16);
+            Instruction::LdI(addr) => self.registers.set_i(addr),
+            // JP V0, Addr (12bit) - Jump to the location Addr + V0 (or Addr + Vx)
+            Instruction::JpV0(addr) => {
+                let offset = match self.quirks.jump_vx {
+                    true => self.registers.get_gp((addr >> 8) as usize),
+                    false => self.registers.get_gp(0)
+                };
+                self.registers.set_pc(addr + offset as u16);
                 update_pc = false;
             },
             // RND Vx, Byte - Set Vx to Byte & Random byte
-            0xC => {
-                let byte_val: u8 = ((comps.v2 as u8) << 4) + comps.v3 as u8;
-                let value = byte_val & ChipSystem::random_byte(); 
-                self.registers.set_gp(comps.v1 as usize, value);
-            },
-            // DRW Vx, Vy, N - Draw a sprite coord (Vx, Vy) with height N
-            0xD => {
-                let x_loc = self.registers.get_gp(comps.v1 as usize) as u16;
-                let y_loc = self.registers.get_gp(comps.v2 as usize) as u16;
-                // let y_loc = comps.v2;
-                let nbytes = comps.v3;
+            Instruction::Rnd(x, byte) => {
+                let value = byte & self.rng.next_byte();
+                self.registers.set_gp(x as usize, value);
+            },
+            // DRW Vx, Vy, N - Draw a sprite coord (Vx, Vy) with height N,
+            // a height of 0 draws a 16x16 SUPER-CHIP sprite
+            Instruction::Drw(x, y, nbytes) => {
+                // Hold the draw until the next vertical blank
+                if self.quirks.display_wait && !self.vblank {
+                    self.stalled = true;
+                    return Ok(());
+                }
+                self.vblank = false;
+                let x_loc = self.registers.get_gp(x as usize) as u16;
+                let y_loc = self.registers.get_gp(y as usize) as u16;
                 let sprite_mem_loc = self.registers.get_i();
-                let sprite_bytes = self.ram.get_nbytes(sprite_mem_loc, nbytes);
-                let overlap = self.display.draw_sprite(x_loc, y_loc, sprite_bytes);
+                let clip = self.quirks.clip_sprites;
+                // Each selected XO-CHIP plane reads its own sprite data
+                let planes = self.display.selected_planes().count_ones() as u16;
+                let overlap = match nbytes {
+                    0 => {
+                        let sprite_bytes = self.ram.read_bytes(sprite_mem_loc, 32 * planes).map_err(oob)?;
+                        self.display.draw_sprite_16(x_loc, y_loc, sprite_bytes, clip)
+                    },
+                    _ => {
+                        let sprite_bytes = self.ram.read_bytes(sprite_mem_loc, nbytes as u16 * planes).map_err(oob)?;
+                        self.display.draw_sprite(x_loc, y_loc, sprite_bytes, clip)
+                    }
+                };
                 match overlap {
                     true => self.registers.set_gp(15, 1),
                     false => self.registers.set_gp(15, 0),
                 }
             },
-            0xE => {
-                match (comps.v2 << 4) + comps.v3 {
-                    // SKP Vx - Skip next instruction if key (0-15) is pressed
-                    0x9E => {
-                        let index = comps.v1 as u8;
-                        let key_val = self.keyboard.get_key(index);
-                        if key_val {
-                            self.registers.incr_pc();
-                        }
-                    },
-                    // SKNP Vx - Skip next instruction if key (0-15) is not pressed
-                    0xA1 => {
-                        let index = comps.v1 as u8;
-                        let key_val = self.keyboard.get_key(index);
-                        if !key_val {
-                            self.registers.incr_pc();
-                        }
-                    }
-                    _ => return Err(ExError {opcode})
+            // SKP Vx - Skip next instruction if the key in Vx (0-15) is pressed
+            Instruction::Skp(x) => {
+                let key = self.registers.get_gp(x as usize);
+                if self.key_state(key, pc, opcode())? {
+                    self.skip_next();
                 }
             },
-            0xF => {
-                match (comps.v2 << 4) + comps.v3 {
-                    // LD Vx, DT - Set Vx to the value of the delay timer
-                    0x07 => {
-                        let delay_val = self.registers.get_d();
-                        self.registers.set_gp(comps.v1 as usize, delay_val);
-                    },
-                    // LD Vx, K - Wait for keypress (halt), put key value in Vx
-                    0x0A => {
-                        let key = self.keyboard.wait_key();
-                        let index = comps.v1 as usize;
-                        self.registers.set_gp(index, key); 
-                    },
-                    // LD DT, Vx - Set the delay timer to the value in Vx
-                    0x15 => {
-                        let delay_val = comps.v1 as u8;
-                        self.registers.set_d(delay_val);
-                    },
-                    // LD ST, Vx - Set the sound timer to the value in Vx
-                    0x18 => {
-                        let delay_val = comps.v1 as u8;
-                        self.registers.set_s(delay_val);
-                    },
-                    // ADD I, Vx - Set register I to I + Vx
-                    0x1E => {
-                        let i_val = self.registers.get_i();
-                        let reg_x_val = self.registers.get_gp(comps.v1 as usize);
-                        let value = i_val + reg_x_val as u16;
-                        self.registers.set_i(value);
-                    },
-                    // LD F, Vx - Set I to the location of sprite (I = Vx * 5)
-                    0x29 => {
-                        let reg_x_val = comps.v1;
-                        let new_i_val = reg_x_val * 5;
-                        self.registers.set_i(new_i_val);
-                    },
-                    // LD B, Vx - Place the BCD of Vx in I (Hundreds), I+1 (Tens), I+2 (Ones)
-                    0x33 => {
-                        let reg_val = self.registers.get_gp(comps.v1 as usize);
-                        let i_val = self.registers.get_i();
-                        let ones = reg_val % 10;
-                        let tens = (reg_val / 10) % 10;
-                        let huns = (reg_val / 100) % 10;
-                        self.ram.set_byte(i_val, huns);
-                        self.ram.set_byte(i_val + 1, tens);
-                        self.ram.set_byte(i_val + 2, ones);
-                    },
-                    // LD I, Vx - Stores V0 to Vx in memory starting at address I, then (I = I + x + 1)
-                    0x55 => {
-                        let i_val = self.registers.get_i();
-                        let x_range = comps.v1;
-                        let mut cur_reg: u8;
-                        for loc in 0..x_range {
-                            cur_reg = self.registers.get_gp(loc as usize);
-                            self.ram.set_byte(i_val + loc, cur_reg);
-                        }
-                        let new_i = i_val + x_range + 1;
-                        self.registers.set_i(new_i);
-                    },
-                    // LD Vx, I - Fills V0 to Vx with values from memory starting at address then (I = I + x + 1)
-                    0x65 => {
-                        let i_val = self.registers.get_i();
-                        let x_range = comps.v1;
-                        let mut cur_reg: u8;
-                        for loc in 0..x_range {
-                            cur_reg = self.ram.get_byte(i_val + loc);
-                            self.registers.set_gp(loc as usize, cur_reg);
-                        }
-                    },
-                    _ => return Err(ExError {opcode})
+            // SKNP Vx - Skip next instruction if the key in Vx (0-15) is not pressed
+            Instruction::Sknp(x) => {
+                let key = self.registers.get_gp(x as usize);
+                if !self.key_state(key, pc, opcode())? {
+                    self.skip_next();
                 }
-            }
-            _ => return Err(ExError {opcode})
+            },
+            // LD Vx, DT - Set Vx to the value of the delay timer
+            Instruction::LdVxDt(x) => {
+                let delay_val = self.registers.get_d();
+                self.registers.set_gp(x as usize, delay_val);
+            },
+            // LD Vx, K - Halt until a key is pressed (see `press`/`release`), put key value in Vx
+            Instruction::LdVxK(x) => {
+                self.state = CpuState::AwaitingKey(x);
+                self.wait_key = None;
+            },
+            // LD DT, Vx - Set the delay timer to the value in Vx
+            Instruction::LdDt(x) => {
+                let reg_x_val = self.registers.get_gp(x as usize);
+                self.registers.set_d(reg_x_val);
+            },
+            // LD ST, Vx - Set the sound timer to the value in Vx
+            Instruction::LdSt(x) => {
+                let reg_x_val = self.registers.get_gp(x as usize);
+                self.registers.set_s(reg_x_val);
+            },
+            // ADD I, Vx - Set register I to I + Vx
+            Instruction::AddI(x) => {
+                let i_val = self.registers.get_i();
+                let reg_x_val = self.registers.get_gp(x as usize);
+                self.registers.set_i(i_val.wrapping_add(reg_x_val as u16));
+            },
+            // LD F, Vx - Set I to the location of font sprite (I = 0x50 + Vx * 5)
+            Instruction::LdF(x) => {
+                let digit = self.registers.get_gp(x as usize) & 0xf;
+                self.registers.set_i(FONT_ADDR + digit as u16 * 5);
+            },
+            // LD B, Vx - Place the BCD of Vx in I (Hundreds), I+1 (Tens), I+2 (Ones)
+            Instruction::LdB(x) => {
+                let reg_val = self.registers.get_gp(x as usize);
+                let i_val = self.registers.get_i() as usize;
+                let ones = reg_val % 10;
+                let tens = (reg_val / 10) % 10;
+                let huns = (reg_val / 100) % 10;
+                self.write_byte(i_val, huns).map_err(oob)?;
+                self.write_byte(i_val + 1, tens).map_err(oob)?;
+                self.write_byte(i_val + 2, ones).map_err(oob)?;
+            },
+            // LD [I], Vx - Stores V0 to Vx in memory starting at address I, then increments I per quirks
+            Instruction::StoreRegs(x) => {
+                let i_val = self.registers.get_i() as usize;
+                for loc in 0..=x as usize {
+                    let cur_reg = self.registers.get_gp(loc);
+                    self.write_byte(i_val + loc, cur_reg).map_err(oob)?;
+                }
+                self.increment_index(x as u16);
+            },
+            // LD Vx, [I] - Fills V0 to Vx with values from memory starting at address I, then increments I per quirks
+            Instruction::LoadRegs(x) => {
+                let i_val = self.registers.get_i() as usize;
+                for loc in 0..=x as usize {
+                    let cur_byte = self.read_byte(i_val + loc).map_err(oob)?;
+                    self.registers.set_gp(loc, cur_byte);
+                }
+                self.increment_index(x as u16);
+            },
+            // SCD n - Scroll display down n pixels
+            Instruction::Scd(n) => self.display.scroll_down(n as usize),
+            // SCR - Scroll display right 4 pixels
+            Instruction::Scr => self.display.scroll_right(),
+            // SCL - Scroll display left 4 pixels
+            Instruction::Scl => self.display.scroll_left(),
+            // EXIT - Stop the interpreter
+            Instruction::Exit => {
+                self.state = CpuState::Exited;
+                update_pc = false;
+            },
+            // LOW - Switch to 64x32 mode
+            Instruction::Low => self.display.set_hires(false),
+            // HIGH - Switch to 128x64 mode
+            Instruction::High => self.display.set_hires(true),
+            // LD HF, Vx - Set I to the location of big font sprite (I = 0xA0 + Vx * 10)
+            Instruction::LdHf(x) => {
+                let digit = self.registers.get_gp(x as usize) & 0xf;
+                self.registers.set_i(BIG_FONT_ADDR + digit as u16 * 10);
+            },
+            // LD R, Vx - Stores V0 to Vx in the RPL user flags
+            Instruction::StoreFlags(x) => {
+                for loc in 0..=x as usize {
+                    let cur_reg = self.registers.get_gp(loc);
+                    self.registers.set_rpl(loc, cur_reg);
+                }
+            },
+            // LD Vx, R - Fills V0 to Vx from the RPL user flags
+            Instruction::LoadFlags(x) => {
+                for loc in 0..=x as usize {
+                    let cur_flag = self.registers.get_rpl(loc);
+                    self.registers.set_gp(loc, cur_flag);
+                }
+            },
+            // SCU n - Scroll display up n pixels
+            Instruction::Scu(n) => self.display.scroll_up(n as usize),
+            // SAVE Vx - Vy - Store Vx to Vy (in either order) at I
+            Instruction::StoreRange(x, y) => {
+                let i_val = self.registers.get_i() as usize;
+                for (offset, reg) in Self::reg_range(x, y).enumerate() {
+                    let cur_reg = self.registers.get_gp(reg);
+                    self.write_byte(i_val + offset, cur_reg).map_err(oob)?;
+                }
+            },
+            // LOAD Vx - Vy - Fill Vx to Vy (in either order) from I
+            Instruction::LoadRange(x, y) => {
+                let i_val = self.registers.get_i() as usize;
+                for (offset, reg) in Self::reg_range(x, y).enumerate() {
+                    let cur_byte = self.read_byte(i_val + offset).map_err(oob)?;
+                    self.registers.set_gp(reg, cur_byte);
+                }
+            },
+            // LD I, Long - Set I to the 16 bit address in the next word (F000 nnnn)
+            Instruction::LdILong(addr) => {
+                self.registers.set_i(addr);
+                self.registers.incr_pc();
+            },
+            // PLANE n - Select drawing planes by bit mask
+            Instruction::Plane(n) => self.display.select_planes(n),
+            // AUDIO - Load the audio pattern buffer from I
+            Instruction::Audio => {
+                let i_val = self.registers.get_i();
+                let pattern = self.ram.read_bytes(i_val, audio::PATTERN_LEN as u16).map_err(oob)?;
+                self.audio.set_pattern(&pattern);
+            },
+            // PITCH Vx - Set the audio pitch register to Vx
+            Instruction::Pitch(x) => {
+                let reg_x_val = self.registers.get_gp(x as usize);
+                self.audio.set_pitch(reg_x_val);
+            },
         }
         // Increment program counter after opcode execution
         if update_pc {
             self.registers.incr_pc()
         }
-        return Ok(());
+        Ok(())
+    }
+
+    /// Apply a bitwise operation to Vx and Vy, storing in Vx
+    fn logic_op(&mut self, x: u8, y: u8, op: fn(u8, u8) -> u8) {
+        let reg_x_val = self.registers.get_gp(x as usize);
+        let reg_y_val = self.registers.get_gp(y as usize);
+        self.registers.set_gp(x as usize, op(reg_x_val, reg_y_val));
+        if self.quirks.vf_reset {
+            self.registers.set_gp(15, 0);
+        }
+    }
+
+    /// Skip the next instruction, the XO-CHIP `F000 nnnn`
+    /// instruction is 4 bytes long and is skipped entirely
+    fn skip_next(&mut self) {
+        let next = self.registers.get_pc().wrapping_add(2);
+        if self.ram.fetch_opcode(next) == Ok(0xF000) {
+            self.registers.incr_pc();
+        }
+        self.registers.incr_pc();
+    }
+
+    /// Register indices from `x` to `y`, descending when `x > y`
+    fn reg_range(x: u8, y: u8) -> Box<dyn Iterator<Item = usize>> {
+        let (x, y) = (x as usize, y as usize);
+        match x <= y {
+            true => Box::new(x..=y),
+            false => Box::new((y..=x).rev())
+        }
+    }
+
+    /// Move I past a `Fx55`/`Fx65` transfer according to the quirks
+    fn increment_index(&mut self, x: u16) {
+        let i_val = self.registers.get_i();
+        match self.quirks.index_increment {
+            IndexIncrement::None => {},
+            IndexIncrement::X => self.registers.set_i(i_val.wrapping_add(x)),
+            IndexIncrement::XPlusOne => self.registers.set_i(i_val.wrapping_add(x + 1)),
+        }
+    }
+
+    /// Get the state of key `key`, failing for keys above 0xF
+    fn key_state(&self, key: u8, pc: u16, opcode: u16) -> ExResult<bool> {
+        match key {
+            0x0..=0xF => Ok(self.keyboard.get_key(key)),
+            _ => Err(ExError::InvalidKey {pc, opcode, key})
+        }
+    }
+
+    /// Read a byte of ram at a computed address
+    fn read_byte(&mut self, addr: usize) -> MemResult<u8> {
+        match u16::try_from(addr) {
+            Ok(loc) => self.ram.read_byte(loc),
+            Err(_) => Err(OutOfRange {addr})
+        }
     }
 
-    fn get_next_opcode(&self) -> u16 {
-        let mut index = self.registers.get_pc();
-        if index % 2 != 0 {
-            index -= 1;
-            println!("Program Counter is not even: {}", index);
-            // panic!("Program Counter register invalid")
+    /// Write a byte of ram at a computed address
+    fn write_byte(&mut self, addr: usize, val: u8) -> MemResult<()> {
+        match u16::try_from(addr) {
+            Ok(loc) => self.ram.write_byte(loc, val),
+            Err(_) => Err(OutOfRange {addr})
         }
-        self.ram.get_opcode(index)
     }
 
-    /// Run the chip8 emulator in an infinite loop
+    /// Fetch and decode the instruction the program counter points at,
+    /// returns the opcode along with the instruction
+    fn fetch_next(&mut self) -> ExResult<(u16, Instruction)> {
+        let pc = self.registers.get_pc();
+        if !pc.is_multiple_of(2) {
+            return Err(ExError::MisalignedPc {pc, opcode: self.ram.fetch_opcode(pc).unwrap_or(0)});
+        }
+        match self.ram.fetch_instruction(pc) {
+            Ok(Ok(instruction)) => Ok((instruction.encode(), instruction)),
+            Ok(Err(e)) => Err(ExError::UnknownOpcode {pc, opcode: e.opcode}),
+            Err(e) => {
+                // The opcode itself may be readable when a long load runs off the end
+                let opcode = self.ram.fetch_opcode(pc).unwrap_or(0);
+                Err(ExError::AddressOutOfRange {pc, opcode, addr: e.addr})
+            }
+        }
+    }
+
+    /// Run the chip8 emulator in an infinite loop, one frame
+    /// every 1/60th of a second
     pub fn run(&mut self) {
         if !self.ram.has_loaded() {
             println!("No ROM has been loaded.");
             return 
         }
 
-        let mut opcode: u16;
-        let mut res: ExResult<()>;
-        loop {
-            // Get current opcode and execute
-            opcode = self.get_next_opcode();
-            res = self.ex_opcode(opcode);
-            match res {
-                Ok(_) => self.registers.incr_pc(),
-                Err(e) => {
-                    println!("Execution halted; error occured");
-                    println!("Error: {:#?}", e);
-                    break;
-                }
+        let frame_time = Duration::from_micros(1_000_000 / 60);
+        while !self.has_exited() {
+            let start = Instant::now();
+            if let Err(e) = self.run_frame() {
+                println!("Execution halted; error occured");
+                println!("Error: {}", e);
+                break;
             }
-            self.registers.decr_d();
-            self.registers.decr_s();
             self.registers.dump_registers();
-            thread::sleep(Duration::from_millis(16))
+            if let Some(rest) = frame_time.checked_sub(start.elapsed()) {
+                thread::sleep(rest);
+            }
         }
         println!("Program Stopped");
     }
 
+    /// Run a single 60 Hz frame, executing instructions according to
+    /// the configured `Speed` and then ticking the delay and sound
+    /// timers once
+    /// 
+    /// A frame ends early when a draw waits for the vertical blank
+    /// (display wait quirk). If `step` was used part way through a
+    /// frame, only the rest of that frame is run.
+    pub fn run_frame(&mut self) -> ExResult<Frame> {
+        // Compiled blocks run in between the steps, never using the
+        // last instruction slot, so `step` still ends the frame
+        #[cfg_attr(not(feature = "jit"), allow(unused_mut))]
+        let mut compiled = 0;
+        let mut frame = self.run_frame_with(|system| {
+            #[cfg(feature = "jit")]
+            while let Some(executed) = system.run_jit_block() {
+                compiled += executed;
+            }
+            system.step(false)
+        })?;
+        frame.instructions += compiled;
+        Ok(frame)
+    }
+
+    /// Run the rest of the current frame through a step function, so
+    /// tools such as `trace::Tracer` and `profile::Profiler` can look
+    /// at every instruction, the JIT isn't used
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - runs one step of the system, usually wrapping `step`
+    pub fn run_frame_with<F: FnMut(&mut Self) -> ExResult<Step>>(&mut self, mut step: F) -> ExResult<Frame> {
+        let mut instructions = 0;
+        let mut modified = false;
+        while !self.has_exited() {
+            let step = step(self)?;
+            if step.opcode.is_some() {
+                instructions += 1;
+            }
+            modified |= step.display.is_some();
+            if step.frame_done {
+                break;
+            }
+        }
+        let display = match modified {
+            true => Some(self.display.get_display()),
+            false => None
+        };
+        Ok(Frame {instructions, state: self.state, display})
+    }
+
     /// Run an emulaton step, this executes a single opcode
     /// from the chip8 memory system, pointed to by the PC reg
     /// 
-    /// Returns a representation of the screen if it has been modified
-    pub fn step(&mut self, display_opcode:  bool) -> (u16, Option<Vec<bool>>) {
-        let opcode = self.get_next_opcode();
-        if display_opcode {
-            println!("Opcode: {:04x}", opcode);
+    /// No opcode is executed while the CPU is awaiting a key or has
+    /// exited, the returned `Step` reports the state. Each step uses one
+    /// instruction slot of the current frame, the timers tick once the
+    /// frame is used up or a draw waits for the vertical blank.
+    /// 
+    /// Returns the executed opcode, the CPU state and a representation
+    /// of the screen if it has been modified, or the error that stopped
+    /// execution. `display_opcode` only prints the raw opcode, step
+    /// through a `trace::Tracer` to record what each instruction did
+    pub fn step(&mut self, display_opcode:  bool) -> ExResult<Step> {
+        if self.vip.is_some() {
+            return self.step_vip(display_opcode);
         }
-        let res: ExResult<()> = self.ex_opcode(opcode);
-        match res {
-            Ok(_) => {},
-            Err(e) => {
-                println!("Execution halted; error occured");
-                println!("Error: {:#?}", e);
+        let opcode = match self.state {
+            CpuState::Running => {
+                let (opcode, instruction) = self.fetch_next()?;
+                if display_opcode {
+                    println!("Opcode: {:04x}", opcode);
+                }
+                self.execute(instruction)?;
+                Some(opcode)
+            },
+            CpuState::AwaitingKey(_) => None,
+            CpuState::Exited => {
+                return Ok(Step {opcode: None, state: self.state, display: None, frame_done: false, cycles: None})
             }
+        };
+        // A stalled draw didn't execute, it waits for the next frame
+        let opcode = match self.stalled {
+            true => None,
+            false => opcode
+        };
+        self.frame_pos += 1;
+        let frame_done = self.stalled || self.frame_pos >= self.frame_budget;
+        if frame_done {
+            self.stalled = false;
+            self.tick();
         }
-        self.registers.decr_d();
-        self.registers.decr_s();
-        match self.display.mod_check() {
-            true => return (opcode, Some(self.display.get_display())),
-            false => return (opcode, None)
+        let display = match self.display.mod_check() {
+            true => Some(self.display.get_display()),
+            false => None
+        };
+        Ok(Step {opcode, state: self.state, display, frame_done, cycles: None})
+    }
+
+    /// Run a step in `TimingMode::Vip`, the timers tick when the
+    /// emulated interrupt fires
+    /// 
+    /// A draw first idles until the interrupt (reported as a step
+    /// without an opcode) and executes on the following step.
+    fn step_vip(&mut self, display_opcode: bool) -> ExResult<Step> {
+        let (opcode, cycles, interrupts) = match self.state {
+            CpuState::Running => {
+                let (opcode, instruction) = self.fetch_next()?;
+                let waited = match self.vip.as_mut() {
+                    Some(clock) => clock.take_wait(),
+                    None => true
+                };
+                match instruction {
+                    Instruction::Drw(..) if !waited => {
+                        let clock = self.vip.get_or_insert_with(VipClock::init);
+                        clock.set_waited();
+                        (None, clock.idle_to_interrupt(), 1)
+                    },
+                    _ => {
+                        let mut cycles = VipClock::instruction_cycles(&instruction, &self.registers);
+                        let pc = self.registers.get_pc();
+                        if display_opcode {
+                            println!("Opcode: {:04x}", opcode);
+                        }
+                        self.execute(instruction)?;
+                        if Self::is_skip(&instruction) && self.registers.get_pc() != pc.wrapping_add(2) {
+                            cycles += timing::SKIP_CYCLES;
+                        }
+                        let clock = self.vip.get_or_insert_with(VipClock::init);
+                        (Some(opcode), cycles, clock.advance(cycles))
+                    }
+                }
+            },
+            CpuState::AwaitingKey(_) => {
+                let clock = self.vip.get_or_insert_with(VipClock::init);
+                (None, clock.idle_to_interrupt(), 1)
+            },
+            CpuState::Exited => {
+                return Ok(Step {opcode: None, state: self.state, display: None, frame_done: false, cycles: Some(0)})
+            }
+        };
+        let frame_done = interrupts > 0;
+        if frame_done {
+            self.stalled = false;
         }
+        // Every interrupt ticks the timers, even one in the middle of an instruction
+        for _ in 0..interrupts {
+            self.tick();
+        }
+        let display = match self.display.mod_check() {
+            true => Some(self.display.get_display()),
+            false => None
+        };
+        Ok(Step {opcode, state: self.state, display, frame_done, cycles: Some(cycles)})
+    }
+
+    /// Returns true for the conditional skip instructions
+    fn is_skip(instruction: &Instruction) -> bool {
+        matches!(instruction,
+            Instruction::Se(..) | Instruction::Sne(..) | Instruction::SeReg(..) |
+            Instruction::SneReg(..) | Instruction::Skp(_) | Instruction::Sknp(_))
     }
 
     /// Load a ROM into the chip8 memory
@@ -496,7 +1162,7 @@ impl ChipSystem {
     /// # Arguments
     /// 
     /// * `rom` - a u8 vector representing the rom
-    pub fn load_rom(&mut self, rom: Vec<u8>) {
-        self.ram.load_bytes(rom);
+    pub fn load_rom(&mut self, rom: Vec<u8>) -> MemResult<()> {
+        self.ram.load_bytes(rom)
     }
 }
\ No newline at end of file
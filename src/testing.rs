@@ -0,0 +1,195 @@
+// Standard Library Modules //
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Local Modules Use //
+use crate::binary::crc32;
+use crate::display::ChipDisplay;
+use crate::movie::{KeyEvent, Movie, Player};
+use crate::quirks::Quirks;
+use crate::timing::TimingMode;
+use crate::{ChipSystem, Speed};
+
+/// Seed of the random generator in headless runs, so `Cxkk` is repeatable
+pub const TEST_SEED: u64 = 0;
+
+/// Environment variable that makes `check_snapshot_file` rewrite
+/// snapshot files instead of comparing against them
+pub const UPDATE_VAR: &str = "CHIP8_UPDATE_SNAPSHOTS";
+
+/// A hash of the display contents
+///
+/// The hash is 64 bit FNV-1a over the width and height (2 bytes each,
+/// little endian) followed by the colour index of every pixel, row
+/// major. It doesn't depend on the platform or the crate version, so
+/// it can be stored in tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameHash(pub u64);
+
+impl FrameHash {
+    /// Hash the current contents of a display
+    ///
+    /// # Arguments
+    ///
+    /// * `display` - the display to hash
+    pub fn of(display: &ChipDisplay) -> Self {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let size = [display.width() as u16, display.height() as u16];
+        let bytes = size.iter().flat_map(|n| n.to_le_bytes()).chain(display.get_pixels());
+        for byte in bytes {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        FrameHash(hash)
+    }
+}
+
+impl fmt::Display for FrameHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Run a ROM without a window for a number of frames and return the
+/// system, panicking if the ROM can't be loaded or execution fails
+///
+/// The random generator is seeded with `TEST_SEED` and the default
+/// speed is used. Inputs are applied before the frame they are tagged
+/// with, as in a `Movie`, and don't need to be sorted.
+///
+/// # Arguments
+///
+/// * `rom` - the ROM to run
+/// * `quirks` - quirks to run it with
+/// * `frames` - number of frames to run
+/// * `inputs` - key presses and releases
+pub fn run_rom(rom: &[u8], quirks: Quirks, frames: u64, inputs: &[KeyEvent]) -> ChipSystem {
+    let mut events = inputs.to_vec();
+    events.sort_by_key(|event| event.frame);
+    let movie = Movie {
+        rom_hash: crc32(rom),
+        seed: TEST_SEED,
+        quirks,
+        speed: Speed::default(),
+        timing: TimingMode::Instructions,
+        frames,
+        events
+    };
+    let mut player = Player::init(movie, rom.to_vec()).unwrap_or_else(|e| panic!("can't load ROM: {}", e));
+    while !player.is_finished() {
+        let frame = player.frame();
+        if let Err(e) = player.run_frame() {
+            panic!("ROM failed in frame {}: {}", frame, e);
+        }
+    }
+    player.system
+}
+
+/// Run a ROM with the default quirks for a number of frames and hash
+/// the final display, see `run_rom`
+///
+/// # Arguments
+///
+/// * `rom` - the ROM to run
+/// * `frames` - number of frames to run
+/// * `inputs` - key presses and releases
+pub fn run_rom_for_frames(rom: &[u8], frames: u64, inputs: &[KeyEvent]) -> FrameHash {
+    FrameHash::of(&run_rom(rom, Quirks::default(), frames, inputs).display)
+}
+
+/// Lines of a snapshot with surrounding whitespace and blank lines at
+/// either end removed, so snapshots can be indented in source
+fn snapshot_lines(text: &str) -> Vec<&str> {
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    let start = lines.iter().position(|line| !line.is_empty()).unwrap_or(lines.len());
+    let end = lines.iter().rposition(|line| !line.is_empty()).map_or(start, |n| n + 1);
+    lines[start..end].to_vec()
+}
+
+/// Compare two ASCII snapshots, None if they match or a readable diff
+///
+/// Every differing row is shown as expected (`-`) and actual (`+`),
+/// with `^` under the columns that differ. Whitespace around each
+/// line and blank lines at either end are ignored.
+///
+/// # Arguments
+///
+/// * `expected` - the snapshot the screen should match
+/// * `actual` - the snapshot of the screen
+pub fn screen_diff(expected: &str, actual: &str) -> Option<String> {
+    let expected = snapshot_lines(expected);
+    let actual = snapshot_lines(actual);
+    if expected == actual {
+        return None;
+    }
+    let mut diff = String::new();
+    if expected.len() != actual.len() {
+        diff.push_str(&format!("expected {} lines, found {}\n", expected.len(), actual.len()));
+    }
+    for row in 0..expected.len().max(actual.len()) {
+        let want = expected.get(row).copied().unwrap_or("");
+        let got = actual.get(row).copied().unwrap_or("");
+        if want == got {
+            continue;
+        }
+        let (mut want_chars, mut got_chars) = (want.chars(), got.chars());
+        let mut marker = String::new();
+        loop {
+            match (want_chars.next(), got_chars.next()) {
+                (None, None) => break,
+                (a, b) => marker.push(if a == b { ' ' } else { '^' })
+            }
+        }
+        diff.push_str(&format!("row {:>3} -{}\n        +{}\n         {}\n", row, want, got, marker.trim_end()));
+    }
+    Some(diff)
+}
+
+/// Compare a screen against a snapshot file, writing the file instead
+/// when `UPDATE_VAR` is set in the environment
+///
+/// Panics with a diff when the screen doesn't match, and when the file
+/// doesn't exist so a missing snapshot can't pass unnoticed.
+///
+/// # Arguments
+///
+/// * `path` - the snapshot file
+/// * `actual` - the snapshot of the screen, from `ChipDisplay::to_ascii`
+pub fn check_snapshot_file<P: AsRef<Path>>(path: P, actual: &str) {
+    let path = path.as_ref();
+    if env::var_os(UPDATE_VAR).is_some() {
+        if let Err(e) = fs::write(path, actual) {
+            panic!("can't write snapshot {}: {}", path.display(), e);
+        }
+        return;
+    }
+    if !path.exists() {
+        panic!("snapshot {} doesn't exist (set {} to write it)", path.display(), UPDATE_VAR);
+    }
+    let expected = fs::read_to_string(path).unwrap_or_else(|e| panic!("can't read snapshot {}: {}", path.display(), e));
+    if let Some(diff) = screen_diff(&expected, actual) {
+        panic!("screen doesn't match snapshot {} (set {} to update it):\n{}", path.display(), UPDATE_VAR, diff);
+    }
+}
+
+/// Assert that an ASCII snapshot of the screen matches the expected
+/// one, panicking with a row by row diff if it doesn't
+///
+/// Both arguments are anything that derefs to `str`, usually
+/// `system.display.to_ascii()` and a raw string literal. Whitespace
+/// around each line is ignored so the expected snapshot can be indented.
+#[macro_export]
+macro_rules! assert_screen_eq {
+    ($actual:expr, $expected:expr $(,)?) => {
+        if let Some(diff) = $crate::testing::screen_diff(&$expected, &$actual) {
+            panic!("screens differ:\n{}", diff);
+        }
+    };
+    ($actual:expr, $expected:expr, $($arg:tt)+) => {
+        if let Some(diff) = $crate::testing::screen_diff(&$expected, &$actual) {
+            panic!("screens differ: {}\n{}", format_args!($($arg)+), diff);
+        }
+    };
+}
@@ -0,0 +1,192 @@
+// Local Modules Use //
+use crate::instruction::Instruction;
+use crate::registers::ChipRegisters;
+
+/// Machine cycles per 60 Hz frame on the COSMAC VIP
+/// (1.76 MHz clock, 8 clocks per cycle, 14 cycles per line, 262 lines)
+pub const FRAME_CYCLES: u32 = 3668;
+/// Machine cycles the CPU is halted by display DMA each frame
+/// (128 displayed lines of 8 bytes)
+pub const DMA_CYCLES: u32 = 1024;
+/// Machine cycles of the interpreter's interrupt routine, which
+/// also decrements the timers
+pub const INTERRUPT_CYCLES: u32 = 46;
+/// Machine cycles left to the interpreter each frame
+pub const INTERPRETER_CYCLES: u32 = FRAME_CYCLES - DMA_CYCLES - INTERRUPT_CYCLES;
+/// Machine cycles to fetch and dispatch any instruction
+pub const FETCH_CYCLES: u32 = 40;
+/// Extra machine cycles when a skip instruction skips
+pub const SKIP_CYCLES: u32 = 4;
+
+/// How instructions are paced against the 60 Hz timers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingMode {
+    /// A number of instructions per frame, see `Speed`
+    Instructions,
+    /// Every instruction consumes its COSMAC VIP machine cycles, frames
+    /// (and timer ticks) happen when the emulated interrupt fires, and
+    /// every draw waits for the interrupt first
+    Vip,
+}
+
+/// Machine cycle clock for the `TimingMode::Vip` execution mode
+///
+/// Instruction costs are taken from the routines of the original VIP
+/// interpreter, variable length instructions (draws, BCD and register
+/// transfers) are computed from their operands.
+pub struct VipClock {
+    /// Interpreter cycles used in the current frame
+    frame_cycle: u32,
+    /// Machine cycles since the clock was created, including DMA and interrupts
+    total: u64,
+    /// True once a pending draw has waited for the interrupt
+    waited: bool
+}
+
+impl VipClock {
+    /// Create a clock at the start of a frame
+    pub fn init() -> Self {
+        VipClock {
+            frame_cycle: 0,
+            total: 0,
+            waited: false
+        }
+    }
+
+    /// Create a clock part way through a frame, used by save states
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_cycle` - interpreter cycles used in the current frame
+    /// * `total` - machine cycles elapsed
+    /// * `waited` - true if a draw has waited for this frame's interrupt
+    pub fn init_with_state(frame_cycle: u32, total: u64, waited: bool) -> Self {
+        VipClock {
+            frame_cycle,
+            total,
+            waited
+        }
+    }
+
+    /// Total machine cycles elapsed
+    pub fn total_cycles(&self) -> u64 {
+        self.total
+    }
+
+    /// Machine cycles used by the interpreter in the current frame
+    pub fn frame_cycle(&self) -> u32 {
+        self.frame_cycle
+    }
+
+    /// Machine cycles an instruction takes, not counting a taken skip
+    ///
+    /// # Arguments
+    ///
+    /// * `instruction` - the instruction about to execute
+    /// * `registers` - register values before execution
+    pub fn instruction_cycles(instruction: &Instruction, registers: &ChipRegisters) -> u32 {
+        use crate::instruction::Instruction::*;
+        let cycles = match *instruction {
+            Sys(_) => 0,
+            Cls => 3078,
+            Ret => 10,
+            Jp(_) => 12,
+            Call(_) => 26,
+            Se(..) | Sne(..) => 10,
+            SeReg(..) | SneReg(..) => 14,
+            Ld(..) => 6,
+            Add(..) => 10,
+            LdReg(..) => 12,
+            Or(..) | And(..) | Xor(..) | AddReg(..) | Sub(..) | Shr(..) | Subn(..) | Shl(..) => 44,
+            LdI(_) => 12,
+            JpV0(addr) => {
+                // Crossing a page boundary costs an extra branch
+                let target = addr as u32 + registers.get_gp(0) as u32;
+                match target >> 8 == addr as u32 >> 8 {
+                    true => 22,
+                    false => 24
+                }
+            },
+            Rnd(..) => 36,
+            Drw(x, _, n) => {
+                // Unaligned sprites are shifted across two display bytes
+                let per_row = match registers.get_gp(x as usize) % 8 {
+                    0 => 34,
+                    _ => 58
+                };
+                26 + per_row * n as u32
+            },
+            Skp(_) | Sknp(_) => 14,
+            LdVxDt(_) | LdDt(_) | LdSt(_) => 10,
+            LdVxK(_) => 19,
+            AddI(_) => 16,
+            LdF(_) => 20,
+            LdB(x) => {
+                // The digits are found by repeated subtraction
+                let value = registers.get_gp(x as usize) as u32;
+                80 + 16 * (value / 100 + (value / 10) % 10 + value % 10)
+            },
+            StoreRegs(x) | LoadRegs(x) => 14 + 14 * (x as u32 + 1),
+            // Extensions never ran on the VIP, charge them like their closest base opcode
+            Scd(_) | Scu(_) | Scr | Scl | Low | High => 3078,
+            Exit => 0,
+            LdHf(_) => 20,
+            StoreFlags(x) | LoadFlags(x) => 14 + 14 * (x as u32 + 1),
+            StoreRange(x, y) | LoadRange(x, y) => 14 + 14 * ((x as i32 - y as i32).unsigned_abs() + 1),
+            LdILong(_) => 24,
+            Plane(_) | Pitch(_) => 10,
+            Audio => 14 + 14 * 16,
+        };
+        FETCH_CYCLES + cycles
+    }
+
+    /// Advance the clock, returns the number of interrupts that fired
+    ///
+    /// # Arguments
+    ///
+    /// * `cycles` - interpreter cycles consumed
+    pub fn advance(&mut self, cycles: u32) -> u32 {
+        self.total += cycles as u64;
+        self.frame_cycle += cycles;
+        let mut interrupts = 0;
+        // An instruction running over the interrupt finishes in the next
+        // frame, a long one (such as a clear) can span several
+        while self.frame_cycle >= INTERPRETER_CYCLES {
+            self.frame_cycle -= INTERPRETER_CYCLES;
+            self.total += (DMA_CYCLES + INTERRUPT_CYCLES) as u64;
+            interrupts += 1;
+        }
+        interrupts
+    }
+
+    /// Idle until the next interrupt, returns the cycles spent waiting
+    pub fn idle_to_interrupt(&mut self) -> u32 {
+        let cycles = INTERPRETER_CYCLES.saturating_sub(self.frame_cycle);
+        self.advance(cycles);
+        cycles
+    }
+
+    /// Returns true if a draw has already waited for this frame's
+    /// interrupt, and clears the flag
+    pub fn take_wait(&mut self) -> bool {
+        let waited = self.waited;
+        self.waited = false;
+        waited
+    }
+
+    /// Returns true if a draw has waited for this frame's interrupt
+    pub fn has_waited(&self) -> bool {
+        self.waited
+    }
+
+    /// Mark that a draw has waited for the interrupt
+    pub fn set_waited(&mut self) {
+        self.waited = true;
+    }
+}
+
+impl Default for VipClock {
+    fn default() -> Self {
+        VipClock::init()
+    }
+}
@@ -0,0 +1,408 @@
+// Standard Library Modules //
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+// Local Modules Use //
+use crate::binary::Reader;
+use crate::bus::Bus;
+use crate::debugger::Register;
+use crate::instruction::Instruction;
+use crate::{ChipSystem, ExResult, Frame, Step};
+
+/// First four bytes of a binary trace
+pub const MAGIC: [u8; 4] = *b"C8TR";
+/// Version of the binary trace format
+pub const FORMAT_VERSION: u16 = 1;
+
+/// Registers compared before and after each step, in binary trace order
+const TRACED_REGISTERS: [Register; 19] = [
+    Register::V(0), Register::V(1), Register::V(2), Register::V(3),
+    Register::V(4), Register::V(5), Register::V(6), Register::V(7),
+    Register::V(8), Register::V(9), Register::V(10), Register::V(11),
+    Register::V(12), Register::V(13), Register::V(14), Register::V(15),
+    Register::I, Register::Dt, Register::St
+];
+
+/// Broad group of an instruction, used to filter traces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpClass {
+    /// Jumps, calls, returns, skips and exit
+    Flow,
+    /// Register loads and arithmetic, including `Cxkk`
+    Alu,
+    /// Index register loads and memory transfers
+    Memory,
+    /// Clearing, drawing, scrolling and display modes
+    Display,
+    /// Key tests and waits
+    Keys,
+    /// Delay and sound timer access
+    Timers,
+    /// XO-CHIP audio pattern and pitch
+    Audio
+}
+
+impl OpClass {
+    /// Get the class of an instruction
+    ///
+    /// # Arguments
+    ///
+    /// * `instruction` - the instruction to classify
+    pub fn of(instruction: &Instruction) -> Self {
+        use crate::instruction::Instruction::*;
+        match instruction {
+            Sys(_) | Ret | Jp(_) | Call(_) | Se(..) | Sne(..) | SeReg(..) | SneReg(..) | JpV0(_) | Exit => OpClass::Flow,
+            Ld(..) | Add(..) | LdReg(..) | Or(..) | And(..) | Xor(..) | AddReg(..) | Sub(..) | Shr(..) |
+            Subn(..) | Shl(..) | Rnd(..) => OpClass::Alu,
+            LdI(_) | AddI(_) | LdF(_) | LdB(_) | StoreRegs(_) | LoadRegs(_) | LdHf(_) | StoreFlags(_) |
+            LoadFlags(_) | StoreRange(..) | LoadRange(..) | LdILong(_) => OpClass::Memory,
+            Cls | Drw(..) | Scd(_) | Scr | Scl | Low | High | Scu(_) | Plane(_) => OpClass::Display,
+            Skp(_) | Sknp(_) | LdVxK(_) => OpClass::Keys,
+            LdVxDt(_) | LdDt(_) | LdSt(_) => OpClass::Timers,
+            Audio | Pitch(_) => OpClass::Audio
+        }
+    }
+}
+
+impl fmt::Display for OpClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            OpClass::Flow => "flow",
+            OpClass::Alu => "alu",
+            OpClass::Memory => "memory",
+            OpClass::Display => "display",
+            OpClass::Keys => "keys",
+            OpClass::Timers => "timers",
+            OpClass::Audio => "audio"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Output format of a `Tracer`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One JSON object per line, see `TraceRecord::to_json`
+    JsonLines,
+    /// The compact format read by `read_binary`
+    Binary
+}
+
+/// Everything one executed instruction did
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord {
+    /// Number of the step since tracing started, steps that were
+    /// filtered out or didn't execute an instruction are counted too
+    pub index: u64,
+    /// Address of the instruction
+    pub pc: u16,
+    /// The opcode, the first word of `F000 nnnn`
+    pub opcode: u16,
+    /// The decoded instruction
+    pub instruction: Instruction,
+    /// Registers that changed and their new values, timers included
+    pub registers: Vec<(Register, u16)>,
+    /// Bytes written to memory and their new values
+    pub memory: Vec<(u16, u8)>,
+    /// Whether a draw collided, None if the instruction didn't draw
+    pub collision: Option<bool>
+}
+
+impl TraceRecord {
+    /// Encode the record as a single line of JSON, without the newline
+    ///
+    /// Numbers other than the index are hex strings so traces can be
+    /// searched for the values the debugger and disassembler print:
+    ///
+    /// `{"index":3,"pc":"0x0206","opcode":"0x8014","asm":"ADD V0, V1",
+    /// "class":"alu","regs":{"V0":"0x10","VF":"0x01"},"mem":{},"collision":null}`
+    pub fn to_json(&self) -> String {
+        let registers: Vec<String> = self.registers.iter().map(|(reg, value)| match reg {
+            Register::I => format!("\"{}\":\"0x{:04x}\"", reg, value),
+            _ => format!("\"{}\":\"0x{:02x}\"", reg, value)
+        }).collect();
+        let memory: Vec<String> = self.memory.iter().map(|(addr, value)| format!("\"0x{:04x}\":\"0x{:02x}\"", addr, value)).collect();
+        let collision = match self.collision {
+            Some(hit) => hit.to_string(),
+            None => String::from("null")
+        };
+        format!(
+            "{{\"index\":{},\"pc\":\"0x{:04x}\",\"opcode\":\"0x{:04x}\",\"asm\":\"{}\",\"class\":\"{}\",\"regs\":{{{}}},\"mem\":{{{}}},\"collision\":{}}}",
+            self.index, self.pc, self.opcode, self.instruction, OpClass::of(&self.instruction),
+            registers.join(","), memory.join(","), collision
+        )
+    }
+
+    /// Encode the record in the binary format, little endian:
+    ///
+    /// * index (8), PC (2), opcode (2) and for `F000 nnnn` the address (2)
+    /// * flags (1): bit 0 set for a draw, bit 1 for a collision
+    /// * register count (1), then the registers: number (1, V0-VF are
+    ///   0-15, I 16, DT 17, ST 18) and value (2)
+    /// * memory count (1), then the bytes: address (2) and value (1)
+    fn to_binary(&self, out: &mut Vec<u8>) {
+        out.extend(self.index.to_le_bytes());
+        out.extend(self.pc.to_le_bytes());
+        out.extend(self.opcode.to_le_bytes());
+        if let Instruction::LdILong(addr) = self.instruction {
+            out.extend(addr.to_le_bytes());
+        }
+        out.push(match self.collision {
+            Some(hit) => 1 | (hit as u8) << 1,
+            None => 0
+        });
+        out.push(self.registers.len() as u8);
+        for &(reg, value) in &self.registers {
+            let number = TRACED_REGISTERS.iter().position(|&traced| traced == reg).unwrap_or(0);
+            out.push(number as u8);
+            out.extend(value.to_le_bytes());
+        }
+        out.push(self.memory.len() as u8);
+        for &(addr, value) in &self.memory {
+            out.extend(addr.to_le_bytes());
+            out.push(value);
+        }
+    }
+}
+
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_json())
+    }
+}
+
+/// The error for malformed binary traces
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Read a binary trace written by a `Tracer`
+///
+/// # Arguments
+///
+/// * `reader` - the trace
+pub fn read_binary<R: Read>(mut reader: R) -> io::Result<Vec<TraceRecord>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let mut cursor = Reader::init(&bytes);
+    cursor.magic(MAGIC).map_err(|_| invalid("not a binary trace"))?;
+    if cursor.u16()? != FORMAT_VERSION {
+        return Err(invalid("unsupported trace version"));
+    }
+    let mut records = Vec::new();
+    while !cursor.is_empty() {
+        let index = cursor.u64()?;
+        let pc = cursor.u16()?;
+        let opcode = cursor.u16()?;
+        let instruction = match opcode {
+            0xF000 => Instruction::decode_long(opcode, cursor.u16()?),
+            _ => Instruction::decode(opcode)
+        };
+        let instruction = instruction.map_err(|_| invalid("trace holds an unknown opcode"))?;
+        let flags = cursor.u8()?;
+        let collision = match flags & 1 {
+            0 => None,
+            _ => Some(flags & 2 != 0)
+        };
+        let mut registers = Vec::new();
+        for _ in 0..cursor.u8()? {
+            let reg = *TRACED_REGISTERS.get(cursor.u8()? as usize).ok_or_else(|| invalid("trace holds an unknown register"))?;
+            registers.push((reg, cursor.u16()?));
+        }
+        let mut memory = Vec::new();
+        for _ in 0..cursor.u8()? {
+            memory.push((cursor.u16()?, cursor.u8()?));
+        }
+        records.push(TraceRecord {index, pc, opcode, instruction, registers, memory, collision});
+    }
+    Ok(records)
+}
+
+/// Records every instruction a system executes to a writer
+///
+/// Run the system through `step` or `run_frame` on the tracer. Each
+/// executed instruction that passes the filters is written as a
+/// `TraceRecord` in the chosen format. Without filters everything is
+/// recorded. Write errors stop the trace and are returned by `finish`.
+pub struct Tracer<W: Write> {
+    /// Where records are written
+    out: W,
+    format: TraceFormat,
+    /// Inclusive address ranges to record, empty for every address
+    ranges: Vec<(u16, u16)>,
+    /// Instruction classes to record, None for every class
+    classes: Option<Vec<OpClass>>,
+    /// Number of steps taken so far
+    steps: u64,
+    /// Number of records written
+    written: u64,
+    /// First write error, no more records are written after it
+    error: Option<io::Error>
+}
+
+impl<W: Write> Tracer<W> {
+    /// Start a trace, the binary format writes its header right away
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - where to write records, buffer it for long traces
+    /// * `format` - how to encode records
+    pub fn init(out: W, format: TraceFormat) -> Self {
+        let mut tracer = Tracer {
+            out,
+            format,
+            ranges: Vec::new(),
+            classes: None,
+            steps: 0,
+            written: 0,
+            error: None
+        };
+        if format == TraceFormat::Binary {
+            let mut header = MAGIC.to_vec();
+            header.extend(FORMAT_VERSION.to_le_bytes());
+            tracer.write(&header);
+        }
+        tracer
+    }
+
+    /// Only record instructions in an address range, can be called
+    /// several times to record several ranges
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - first address to record
+    /// * `end` - last address to record
+    pub fn add_range(&mut self, start: u16, end: u16) {
+        self.ranges.push((start, end));
+    }
+
+    /// Only record instructions of some classes
+    ///
+    /// # Arguments
+    ///
+    /// * `classes` - classes to record
+    pub fn set_classes(&mut self, classes: &[OpClass]) {
+        self.classes = Some(classes.to_vec());
+    }
+
+    /// Remove the address and class filters
+    pub fn clear_filters(&mut self) {
+        self.ranges.clear();
+        self.classes = None;
+    }
+
+    /// Number of records written so far
+    pub fn records(&self) -> u64 {
+        self.written
+    }
+
+    /// Run a single system step and record it
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - the system to step
+    pub fn step<B: Bus>(&mut self, system: &mut ChipSystem<B>) -> ExResult<Step> {
+        let pc = system.registers.get_pc();
+        let instruction = match system.ram.fetch_instruction(pc) {
+            Ok(Ok(instruction)) => Some(instruction),
+            _ => None
+        };
+        let before: Vec<u16> = TRACED_REGISTERS.iter().map(|reg| reg.value(&system.registers)).collect();
+        let writes = instruction.map_or(0..0, |instruction| written_range(&instruction, system.registers.get_i()));
+
+        let step = system.step(false)?;
+        let index = self.steps;
+        self.steps += 1;
+        let (instruction, opcode) = match (instruction, step.opcode) {
+            (Some(instruction), Some(opcode)) => (instruction, opcode),
+            _ => return Ok(step)
+        };
+        if !self.wanted(pc, &instruction) {
+            return Ok(step);
+        }
+
+        let registers = TRACED_REGISTERS
+            .iter()
+            .zip(before)
+            .map(|(&reg, old)| (reg, reg.value(&system.registers), old))
+            .filter(|&(_, new, old)| new != old)
+            .map(|(reg, new, _)| (reg, new))
+            .collect();
+        let memory = writes
+            .filter_map(|addr| u16::try_from(addr).ok())
+            .filter_map(|addr| system.ram.peek_byte(addr).ok().map(|value| (addr, value)))
+            .collect();
+        let collision = match instruction {
+            Instruction::Drw(..) => Some(system.registers.get_gp(15) != 0),
+            _ => None
+        };
+        let record = TraceRecord {index, pc, opcode, instruction, registers, memory, collision};
+        match self.format {
+            TraceFormat::JsonLines => {
+                let line = format!("{}\n", record.to_json());
+                self.write(line.as_bytes());
+            },
+            TraceFormat::Binary => {
+                let mut bytes = Vec::new();
+                record.to_binary(&mut bytes);
+                self.write(&bytes);
+            }
+        }
+        if self.error.is_none() {
+            self.written += 1;
+        }
+        Ok(step)
+    }
+
+    /// Run the rest of the current frame one step at a time, recording
+    /// every instruction, as `ChipSystem::run_frame` (without the JIT)
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - the system to run
+    pub fn run_frame<B: Bus>(&mut self, system: &mut ChipSystem<B>) -> ExResult<Frame> {
+        system.run_frame_with(|system| self.step(system))
+    }
+
+    /// Flush the trace and get the writer back, or the first write error
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    /// Returns true if an instruction passes the filters
+    fn wanted(&self, pc: u16, instruction: &Instruction) -> bool {
+        let in_range = self.ranges.is_empty() || self.ranges.iter().any(|&(start, end)| (start..=end).contains(&pc));
+        let in_class = match &self.classes {
+            Some(classes) => classes.contains(&OpClass::of(instruction)),
+            None => true
+        };
+        in_range && in_class
+    }
+
+    /// Write bytes unless an earlier write failed
+    fn write(&mut self, bytes: &[u8]) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.out.write_all(bytes) {
+            self.error = Some(e);
+        }
+    }
+}
+
+/// Addresses an instruction writes to, given the index register
+/// before it runs
+fn written_range(instruction: &Instruction, i: u16) -> std::ops::Range<usize> {
+    let start = i as usize;
+    match *instruction {
+        Instruction::LdB(_) => start..start + 3,
+        Instruction::StoreRegs(x) => start..start + x as usize + 1,
+        Instruction::StoreRange(x, y) => start..start + (x as i32 - y as i32).unsigned_abs() as usize + 1,
+        _ => start..start
+    }
+}
@@ -0,0 +1,57 @@
+// Standard Library Modules //
+use std::fs;
+use std::process;
+
+// Local Modules Use //
+use chip8emu::asm::{assemble, Assembler};
+
+/// Assemble and return the error as printed
+fn error_text(source: &str) -> String {
+    assemble(source).unwrap_err().to_string()
+}
+
+#[test]
+fn undefined_label_location() {
+    assert_eq!(error_text("start: CLS\n  JP nowhere\n"), "<input>:2:6: undefined symbol `nowhere`");
+}
+
+#[test]
+fn duplicate_label_location() {
+    assert_eq!(error_text("a: CLS\na: CLS\n"), "<input>:2:1: `a` is already defined");
+}
+
+#[test]
+fn bad_operand_location() {
+    assert_eq!(error_text("CLS\nLD V0, 300\n"), "<input>:2:8: 300 doesn't fit in a byte");
+    assert_eq!(error_text("ADD I, 5, 6\n"), "<input>:1:1: invalid operands for `ADD`");
+}
+
+#[test]
+fn include_error_location() {
+    let dir = std::env::temp_dir().join(format!("chip8-asm-include-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("bad.asm"), "LD V0, 1\nJP nowhere\n").unwrap();
+    let mut assembler = Assembler::init();
+    assembler.set_include_dir(&dir);
+
+    let error = assembler.assemble("CLS\nINCLUDE \"bad.asm\"\n").unwrap_err();
+    assert_eq!((error.file.as_str(), error.line, error.column), (dir.join("bad.asm").to_str().unwrap(), 2, 4));
+    assert_eq!(error.message, "undefined symbol `nowhere`");
+
+    let error = assembler.assemble("CLS\n  INCLUDE \"missing.asm\"\n").unwrap_err();
+    assert_eq!((error.file.as_str(), error.line, error.column), ("<input>", 2, 3));
+    assert!(error.message.starts_with("can't include"), "{}", error.message);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn directives_encode() {
+    let source = "SIZE EQU 3\nstart: LD V0, SIZE * 2\nORG 0x210\ndata: DB 1, SIZE, \"AB\"\nDW 0x1234, data\nend:\n";
+    let assembly = Assembler::init().assemble(source).unwrap();
+    let mut rom = vec![0x60, 0x06];
+    rom.resize(0x10, 0x00);
+    rom.extend_from_slice(&[0x01, 0x03, 0x41, 0x42, 0x12, 0x34, 0x02, 0x10]);
+    assert_eq!(assembly.rom, rom);
+    // Constants aren't labels
+    assert_eq!(assembly.symbol_map(), "0200 start\n0210 data\n0218 end\n");
+}
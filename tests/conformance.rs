@@ -0,0 +1,157 @@
+// Standard Library Modules //
+use std::fs;
+use std::path::Path;
+
+// Local Modules Use //
+use chip8emu::asm::Assembler;
+use chip8emu::assert_screen_eq;
+use chip8emu::binary::crc32;
+use chip8emu::movie::{KeyEvent, Movie, Player};
+use chip8emu::octo;
+use chip8emu::quirks::Quirks;
+use chip8emu::testing::TEST_SEED;
+use chip8emu::timing::TimingMode;
+use chip8emu::{ChipSystem, Speed};
+
+/// Frames a conformance ROM gets to exit in
+const FRAME_LIMIT: u64 = 600;
+
+/// Speed of the `TimingMode::Instructions` runs, fast enough for the
+/// JIT's blocks to fit in a frame
+const SPEED: Speed = Speed::InstructionsPerFrame(500);
+
+/// Build a conformance ROM from its source in `tests/roms`, `.8o`
+/// files are Octo and everything else is assembly
+fn build(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms").join(name);
+    let assembly = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("8o") => {
+            let source = fs::read_to_string(&path).unwrap_or_else(|e| panic!("can't read {}: {}", name, e));
+            octo::compile(&source)
+        },
+        _ => Assembler::init().assemble_file(&path)
+    };
+    assembly.unwrap_or_else(|e| panic!("{}", e)).rom
+}
+
+/// Run a conformance ROM until it exits, in both timing modes and with
+/// the JIT when it is built, and check every run passed
+///
+/// Returns the system from the last run for checks the ROM can't make
+/// itself.
+fn conform(name: &str, quirks: Quirks, inputs: &[KeyEvent]) -> ChipSystem {
+    let rom = build(name);
+    let mut modes = vec![("instructions", TimingMode::Instructions, false), ("vip", TimingMode::Vip, false)];
+    if cfg!(feature = "jit") {
+        modes.push(("jit", TimingMode::Instructions, true));
+    }
+    let mut last = None;
+    for (mode, timing, jit) in modes {
+        let movie = Movie {
+            rom_hash: crc32(&rom),
+            seed: TEST_SEED,
+            quirks,
+            speed: SPEED,
+            timing,
+            frames: FRAME_LIMIT,
+            events: inputs.to_vec()
+        };
+        let mut player = Player::init(movie, rom.clone()).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        #[cfg(feature = "jit")]
+        assert_eq!(player.system.set_jit_enabled(jit), jit, "{}: JIT unavailable", name);
+        #[cfg(not(feature = "jit"))]
+        let _ = jit;
+        while !player.is_finished() && !player.system.has_exited() {
+            if let Err(e) = player.run_frame() {
+                panic!("{} ({}): {}", name, mode, e);
+            }
+        }
+        let system = player.system;
+        assert!(system.has_exited(), "{} ({}) didn't finish, PC is {:03x}", name, mode, system.registers.get_pc());
+        let check = system.registers.get_gp(0xE);
+        assert!(check == 0, "{} ({}) failed check {}", name, mode, check);
+        last = Some(system);
+    }
+    last.unwrap()
+}
+
+#[test]
+fn flow() {
+    conform("flow.asm", Quirks::cosmac_vip(), &[]);
+}
+
+#[test]
+fn alu() {
+    conform("alu.asm", Quirks::cosmac_vip(), &[]);
+}
+
+#[test]
+fn hot_alu() {
+    let system = conform("hot_alu.asm", Quirks::cosmac_vip(), &[]);
+    #[cfg(feature = "jit")]
+    assert!(system.jit().map_or(0, |jit| jit.compiled_blocks()) > 0, "the loop wasn't compiled");
+    #[cfg(not(feature = "jit"))]
+    let _ = system;
+}
+
+#[test]
+fn memory() {
+    conform("memory.asm", Quirks::cosmac_vip(), &[]);
+}
+
+#[test]
+fn timers() {
+    let system = conform("timers.asm", Quirks::cosmac_vip(), &[]);
+    let sound = system.registers.get_s();
+    assert!((0x3F..=0x40).contains(&sound), "sound timer is {:#x}, expected 0x40", sound);
+}
+
+#[test]
+fn keys() {
+    let inputs = [
+        KeyEvent {frame: 0, key: 5, pressed: true},
+        KeyEvent {frame: 10, key: 9, pressed: true},
+        KeyEvent {frame: 11, key: 9, pressed: false}
+    ];
+    conform("keys.asm", Quirks::cosmac_vip(), &inputs);
+}
+
+#[test]
+fn random() {
+    conform("random.asm", Quirks::cosmac_vip(), &[]);
+}
+
+#[test]
+fn display() {
+    let system = conform("display.asm", Quirks::cosmac_vip(), &[]);
+    let screen = system.display.to_ascii();
+    let top: String = screen.lines().take(9).map(|line| format!("{}\n", line)).collect();
+    assert_screen_eq!(top, r"
+        |----------------------------------------------------------------|
+        |                                                                |
+        |                                                                |
+        |  ####  ####  ####                                              |
+        |  #  #     #  #                                                 |
+        |  #  #    #   ####                                              |
+        |  #  #   #    #                                                 |
+        |  ####   #    #                                                 |
+        |                                                                |
+    ");
+}
+
+#[test]
+fn schip() {
+    conform("schip.asm", Quirks::superchip(), &[]);
+}
+
+#[test]
+fn xochip() {
+    let system = conform("xochip.asm", Quirks::octo(), &[]);
+    assert_eq!(system.audio.get_pitch(), 0x50);
+    assert_eq!(&system.audio.get_pattern()[..4], &[0x00, 0xFF, 0x00, 0xFF]);
+}
+
+#[test]
+fn octo_compare() {
+    conform("compare.8o", Quirks::cosmac_vip(), &[]);
+}
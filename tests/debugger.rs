@@ -0,0 +1,43 @@
+// Local Modules Use //
+use chip8emu::debugger::{Access, Debugger, Register, StopReason, WatchTarget};
+use chip8emu::ChipSystem;
+
+#[test]
+fn key_wait_writes_vx_when_the_key_arrives() {
+    // LD V3, 0x07 / LD V3, K / loop: JP loop
+    let rom = vec![0x63, 0x07, 0xF3, 0x0A, 0x12, 0x04];
+    let mut system = ChipSystem::init();
+    system.load_rom(rom).unwrap();
+    let mut debugger = Debugger::init(system);
+    debugger.watch_register(Register::V(3), Access::Write);
+    let write_at = |pc| StopReason::Watchpoint {pc, target: WatchTarget::Register(Register::V(3)), access: Access::Write};
+
+    assert_eq!(debugger.step_into(), write_at(0x200));
+    assert_eq!(debugger.cont(Some(10)), StopReason::AwaitingKey(3));
+    assert_eq!(debugger.system.registers.get_gp(3), 0x07);
+
+    // The default quirks end the wait when the key is released
+    debugger.system.press(0x5);
+    debugger.system.release(0x5);
+    assert_eq!(debugger.cont(Some(10)), write_at(0x202));
+    assert_eq!(debugger.system.registers.get_gp(3), 0x05);
+    assert_eq!(debugger.cont(Some(10)), StopReason::LimitReached);
+}
+
+#[test]
+fn step_out_needs_a_subroutine() {
+    // CALL sub / loop: JP loop / sub: LD V0, 0x01 / RET
+    let rom = vec![0x22, 0x04, 0x12, 0x02, 0x60, 0x01, 0x00, 0xEE];
+    let mut system = ChipSystem::init();
+    system.load_rom(rom).unwrap();
+    let mut debugger = Debugger::init(system);
+
+    assert_eq!(debugger.step_out(), StopReason::NotInSubroutine);
+    assert_eq!(debugger.system.registers.get_pc(), 0x200);
+
+    assert_eq!(debugger.step_into(), StopReason::Done);
+    assert_eq!(debugger.step_out(), StopReason::Done);
+    assert_eq!(debugger.system.registers.get_pc(), 0x202);
+    assert_eq!(debugger.system.registers.get_gp(0), 0x01);
+    assert_eq!(debugger.step_out(), StopReason::NotInSubroutine);
+}
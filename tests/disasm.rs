@@ -0,0 +1,58 @@
+// Local Modules Use //
+use chip8emu::disasm::{LineKind, Listing};
+use chip8emu::instruction::Instruction;
+
+/// CALL 0x208 / JP 0x20C / a sprite / LD V0, 0x01 / RET / JP 0x20C
+const ROM: [u8; 14] = [
+    0x22, 0x08, 0x12, 0x0C, 0xF0, 0x90, 0x90, 0xF0,
+    0x60, 0x01, 0x00, 0xEE, 0x12, 0x0C
+];
+
+#[test]
+fn labels_jump_and_call_targets() {
+    let listing = Listing::init(ROM.to_vec());
+    let labels: Vec<(u16, &str)> = listing.labels().iter().map(|(&addr, label)| (addr, label.as_str())).collect();
+    assert_eq!(labels, vec![(0x208, "sub_208"), (0x20C, "label_20C")]);
+
+    let lines = listing.lines();
+    assert_eq!(listing.text(&lines[0]), "CALL sub_208");
+    assert_eq!(listing.text(&lines[1]), "JP label_20C");
+    assert_eq!(listing.text(&lines[5]), "JP label_20C");
+}
+
+#[test]
+fn unreachable_bytes_stay_data() {
+    let listing = Listing::init(ROM.to_vec());
+    let addrs: Vec<u16> = listing.lines().iter().map(|line| line.addr).collect();
+    assert_eq!(addrs, vec![0x200, 0x202, 0x204, 0x208, 0x20A, 0x20C]);
+
+    let sprite = &listing.lines()[2];
+    assert_eq!(sprite.kind, LineKind::Data);
+    assert_eq!(sprite.bytes, vec![0xF0, 0x90, 0x90, 0xF0]);
+    assert_eq!(listing.text(sprite), "DB 0xF0, 0x90, 0x90, 0xF0");
+    assert_eq!(listing.lines()[3].kind, LineKind::Code(Instruction::Ld(0, 0x01)));
+}
+
+#[test]
+fn entries_start_the_traversal() {
+    // RET / two data bytes / LD V3, 0x05 / RET, placed at 0x300
+    let bytes = vec![0x00, 0xEE, 0xAA, 0xBB, 0x63, 0x05, 0x00, 0xEE];
+    let listing = Listing::init_with_entries(bytes.clone(), 0x300, &[0x300, 0x304]);
+    let kinds: Vec<(u16, LineKind)> = listing.lines().iter().map(|line| (line.addr, line.kind)).collect();
+    assert_eq!(kinds, vec![
+        (0x300, LineKind::Code(Instruction::Ret)),
+        (0x302, LineKind::Data),
+        (0x304, LineKind::Code(Instruction::Ld(3, 0x05))),
+        (0x306, LineKind::Code(Instruction::Ret))
+    ]);
+    assert!(listing.labels().is_empty());
+
+    // Without the second entry the rest is never reached
+    let listing = Listing::init_with_entries(bytes, 0x300, &[0x300]);
+    assert_eq!(listing.lines().len(), 3);
+    let data: Vec<Vec<u8>> = listing.lines()[1..].iter()
+        .filter(|line| line.kind == LineKind::Data)
+        .map(|line| line.bytes.clone())
+        .collect();
+    assert_eq!(data, vec![vec![0xAA, 0xBB, 0x63, 0x05], vec![0x00, 0xEE]]);
+}
@@ -0,0 +1,96 @@
+// Standard Library Modules //
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+// Local Modules Use //
+use chip8emu::bus::Bus;
+use chip8emu::debugger::Debugger;
+use chip8emu::gdb::GdbStub;
+use chip8emu::ChipSystem;
+
+/// A minimal RSP client, acknowledgements from the stub are skipped
+struct Client {
+    stream: TcpStream
+}
+
+impl Client {
+    /// Send a packet and return the data of the reply
+    fn send(&mut self, data: &str) -> String {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${}#{:02x}", data, checksum).unwrap();
+        self.reply()
+    }
+
+    /// Read the next reply packet
+    fn reply(&mut self) -> String {
+        let mut byte = [0];
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'$' {
+                break;
+            }
+        }
+        let mut data = Vec::new();
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'#' {
+                break;
+            }
+            data.push(byte[0]);
+        }
+        let mut checksum = [0; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        String::from_utf8(data).unwrap()
+    }
+}
+
+#[test]
+fn scripted_session() {
+    // LD V0, 0x12 / LD V1, 0x34 / loop: ADD V2, 1 / JP loop
+    let rom = vec![0x60, 0x12, 0x61, 0x34, 0x72, 0x01, 0x12, 0x04];
+    let mut system = ChipSystem::init();
+    system.load_rom(rom).unwrap();
+    let mut debugger = Debugger::init(system);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = thread::spawn(move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        stream.set_nodelay(true).unwrap();
+        let mut client = Client {stream};
+
+        // V0-VF, I, PC, SP, DT and ST, wide registers little endian
+        let registers = format!("{}00000002000000", "00".repeat(16));
+        assert_eq!(client.send("g"), registers);
+        assert_eq!(client.send("s"), "S05");
+        assert_eq!(client.send("p0"), "12");
+
+        assert_eq!(client.send("m200,4"), "60126134");
+        assert_eq!(client.send("M300,2:abcd"), "OK");
+        assert_eq!(client.send("m300,2"), "abcd");
+        assert_eq!(client.send("mffffffffffffffff,1"), "E01");
+        assert_eq!(client.send("Mffffffffffffffff,1:00"), "E01");
+        assert_eq!(client.send("m1,ffffffffffffffff"), "E01");
+
+        assert_eq!(client.send("Z0,206,2"), "OK");
+        assert_eq!(client.send("c"), "S05");
+        assert_eq!(client.send("p11"), "0602");
+        assert_eq!(client.send("p2"), "01");
+        assert_eq!(client.send("z0,206,2"), "OK");
+
+        // Without the breakpoint the loop runs until interrupted
+        client.stream.write_all(b"$c#63").unwrap();
+        thread::sleep(Duration::from_millis(50));
+        client.stream.write_all(&[0x03]).unwrap();
+        assert_eq!(client.reply(), "S02");
+        assert_eq!(client.send("D"), "OK");
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    GdbStub::init(&mut debugger, stream).run().unwrap();
+    client.join().unwrap();
+    assert_eq!(debugger.system.ram.peek_byte(0x300).unwrap(), 0xAB);
+}
@@ -0,0 +1,82 @@
+// Local Modules Use //
+use chip8emu::asm::assemble;
+use chip8emu::ChipSystem;
+use chip8emu::instruction::Instruction;
+
+#[test]
+fn decode_encode_round_trip() {
+    for opcode in 0..=0xFFFFu16 {
+        if let Ok(instruction) = Instruction::decode(opcode) {
+            assert_eq!(instruction.encode(), opcode, "{:04x} decoded to {:?}", opcode, instruction);
+        }
+    }
+}
+
+#[test]
+fn display_reassembles() {
+    for opcode in 0..=0xFFFFu16 {
+        let instruction = match Instruction::decode(opcode) {
+            Ok(instruction) => instruction,
+            Err(_) => continue
+        };
+        let text = instruction.to_string();
+        let rom = assemble(&text).unwrap_or_else(|e| panic!("{:04x} prints as `{}`: {}", opcode, text, e));
+        assert_eq!(rom, instruction.to_bytes(), "{:04x} prints as `{}`", opcode, text);
+    }
+}
+
+#[test]
+fn long_load_round_trip() {
+    let instruction = Instruction::decode_long(0xF000, 0xBEEF).unwrap();
+    assert_eq!(instruction.size(), 4);
+    assert_eq!(instruction.to_bytes(), vec![0xF0, 0x00, 0xBE, 0xEF]);
+    assert_eq!(assemble(&instruction.to_string()).unwrap(), instruction.to_bytes());
+}
+
+#[test]
+fn flag_wins_when_vx_is_vf() {
+    let mut system = ChipSystem::init();
+    system.registers.set_gp(15, 0xF0);
+    system.execute(Instruction::AddReg(15, 15)).unwrap();
+    assert_eq!(system.registers.get_gp(15), 1);
+
+    system.registers.set_gp(15, 7);
+    system.registers.set_gp(0, 5);
+    system.execute(Instruction::Sub(15, 0)).unwrap();
+    assert_eq!(system.registers.get_gp(15), 1);
+
+    system.registers.set_gp(15, 3);
+    system.execute(Instruction::Shr(15, 15)).unwrap();
+    assert_eq!(system.registers.get_gp(15), 1);
+
+    system.registers.set_gp(15, 0x81);
+    system.execute(Instruction::Shl(15, 15)).unwrap();
+    assert_eq!(system.registers.get_gp(15), 1);
+}
+
+#[cfg(feature = "jit")]
+#[test]
+fn compiled_flag_wins_when_vx_is_vf() {
+    use chip8emu::jit::{Jit, HOT_THRESHOLD};
+    use chip8emu::memory::ChipMemory;
+    use chip8emu::quirks::Quirks;
+    use chip8emu::registers::ChipRegisters;
+
+    // LD VF, 0xF0 / ADD VF, VF / JP 0x200
+    let mut ram = ChipMemory::init();
+    ram.load_bytes(vec![0x6F, 0xF0, 0x8F, 0xF4, 0x12, 0x00]).unwrap();
+    let mut registers = ChipRegisters::init();
+    let quirks = Quirks::default();
+    let mut jit = match Jit::init() {
+        Some(jit) => jit,
+        None => return
+    };
+    for _ in 0..HOT_THRESHOLD {
+        registers.set_pc(0x200);
+        if jit.run_block(&mut ram, &mut registers, &quirks, 100).is_some() {
+            assert_eq!(registers.get_gp(15), 1);
+            return;
+        }
+    }
+    panic!("the block wasn't compiled");
+}
@@ -0,0 +1,9 @@
+// Local Modules Use //
+use chip8emu::keyboard::ChipKeyboard;
+
+#[test]
+fn key_f_can_be_pressed() {
+    let mut keyboard = ChipKeyboard::init();
+    keyboard.set_key(0xF, true);
+    assert!(keyboard.get_key(0xF));
+}
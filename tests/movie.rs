@@ -0,0 +1,86 @@
+// Local Modules Use //
+use chip8emu::asm::assemble;
+use chip8emu::movie::{Movie, MovieError, Player, Recorder};
+use chip8emu::quirks::Quirks;
+
+/// Counts instructions run with key 5 held in V2 and key A held in
+/// V4, drawing random numbers on the way
+const SOURCE: &str = "
+    LD V1, 5
+    LD V5, 0xA
+loop:
+    SKNP V1
+    ADD V2, 1
+    SKNP V5
+    ADD V4, 1
+    RND V3, 0xFF
+    JP loop
+";
+
+/// Record a short run with a few key presses and releases
+fn record() -> (Movie, Vec<u8>) {
+    let rom = assemble(SOURCE).unwrap();
+    let mut recorder = Recorder::init(rom.clone(), Quirks::default(), 42).unwrap();
+    for frame in 0..12 {
+        match frame {
+            2 => recorder.press(5),
+            4 => recorder.press(0xA),
+            5 => recorder.release(5),
+            8 => {
+                recorder.release(0xA);
+                recorder.press(5);
+            },
+            _ => ()
+        }
+        recorder.run_frame().unwrap();
+    }
+    let state = recorder.system.save_state();
+    (recorder.finish(), state)
+}
+
+#[test]
+fn recording_replays_exactly() {
+    let (movie, state) = record();
+    assert_eq!(movie.frames, 12);
+    assert_eq!(movie.events.len(), 5);
+
+    let decoded = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(decoded, movie);
+
+    let mut player = Player::init(decoded, assemble(SOURCE).unwrap()).unwrap();
+    player.run_to_end().unwrap();
+    assert!(player.is_finished());
+    assert_eq!(player.system.save_state(), state);
+
+    // Without the keys the run ends elsewhere
+    let mut silent = movie.clone();
+    silent.events.clear();
+    let mut player = Player::init(silent, assemble(SOURCE).unwrap()).unwrap();
+    player.run_to_end().unwrap();
+    assert_ne!(player.system.save_state(), state);
+}
+
+#[test]
+fn wrong_rom_is_rejected() {
+    let (movie, _) = record();
+    match Player::init(movie, vec![0x12, 0x00]) {
+        Err(MovieError::RomMismatch {..}) => (),
+        other => panic!("expected a ROM mismatch, got {:?}", other.err())
+    }
+}
+
+#[test]
+fn events_out_of_order_are_rejected() {
+    let (mut movie, _) = record();
+    movie.events.swap(1, 2);
+    assert_eq!(Movie::from_bytes(&movie.to_bytes()), Err(MovieError::Invalid("key event")));
+}
+
+#[test]
+fn corrupted_movie_is_rejected() {
+    let (movie, _) = record();
+    let mut bytes = movie.to_bytes();
+    assert_eq!(Movie::from_bytes(&bytes[..bytes.len() - 1]), Err(MovieError::Checksum));
+    bytes[0] = b'X';
+    assert_eq!(Movie::from_bytes(&bytes), Err(MovieError::BadMagic));
+}
@@ -0,0 +1,102 @@
+// Local Modules Use //
+use chip8emu::octo::compile;
+use chip8emu::ChipSystem;
+
+/// Compile and return the ROM image
+fn rom(source: &str) -> Vec<u8> {
+    compile(source).unwrap_or_else(|e| panic!("{}", e)).rom
+}
+
+/// Compile and return the error as printed
+fn error_text(source: &str) -> String {
+    compile(source).unwrap_err().to_string()
+}
+
+#[test]
+fn next_labels_the_operand() {
+    let assembly = compile(": main\n  :next target v0 := 5\n  i := target\n").unwrap();
+    assert_eq!(assembly.rom, vec![0x12, 0x02, 0x60, 0x05, 0xA2, 0x03]);
+    assert_eq!(assembly.labels["target"], 0x203);
+}
+
+#[test]
+fn unpack_loads_an_address() {
+    assert_eq!(rom(": main\n  :unpack 0xA data\n  ;\n: data 1 2\n"), vec![0x12, 0x02, 0x60, 0xA2, 0x61, 0x08, 0x00, 0xEE, 0x01, 0x02]);
+}
+
+#[test]
+fn macro_expands_arguments() {
+    assert_eq!(rom(":macro twice reg { reg += 1 reg += 1 }\n: main\n  twice v2\n"), vec![0x12, 0x02, 0x72, 0x01, 0x72, 0x01]);
+}
+
+#[test]
+fn calc_and_alias() {
+    // As in Octo, `:calc` has no precedence and evaluates right to left
+    assert_eq!(rom(":calc size { 2 * 3 + 1 }\n: main\n  v0 := size\n"), vec![0x12, 0x02, 0x60, 0x08]);
+    assert_eq!(rom(":alias px v3\n: main\n  px := 4\n  px += px\n"), vec![0x12, 0x02, 0x63, 0x04, 0x83, 0x34]);
+}
+
+#[test]
+fn org_moves_output() {
+    let assembly = compile(": main\n  jump far\n:org 0x300\n: far\n  return\n").unwrap();
+    assert_eq!(assembly.labels["far"], 0x300);
+    assert_eq!(&assembly.rom[..4], &[0x12, 0x02, 0x13, 0x00]);
+    assert_eq!(assembly.rom.len(), 0x102);
+    assert!(assembly.rom[4..0x100].iter().all(|&byte| byte == 0));
+    assert_eq!(&assembly.rom[0x100..], &[0x00, 0xEE]);
+}
+
+#[test]
+fn nested_if_blocks() {
+    let source = ": main\n  if v0 == 1 begin\n    if v1 < v2 begin\n      v3 := 1\n    else\n      v3 := 2\n    end\n  else\n    v3 := 3\n  end\n";
+    assert_eq!(rom(source), vec![
+        0x12, 0x02,
+        // if v0 == 1 begin, skip the jump to the outer else
+        0x30, 0x01, 0x12, 0x16,
+        // if v1 < v2 begin, vf := v1 - v2 then skip the jump on a borrow
+        0x8F, 0x20, 0x8F, 0x17, 0x3F, 0x00, 0x12, 0x12,
+        0x63, 0x01, 0x12, 0x14,
+        0x63, 0x02,
+        0x12, 0x18,
+        0x63, 0x03
+    ]);
+}
+
+#[test]
+fn ordering_comparisons_run() {
+    for &(compare, a, b, expected) in &[
+        ("<", 1, 2, true), ("<", 2, 2, false), (">", 3, 2, true), (">", 2, 2, false),
+        ("<=", 2, 2, true), ("<=", 3, 2, false), (">=", 2, 2, true), (">=", 1, 2, false)
+    ] {
+        let source = format!(
+            ": main\n  v1 := {}\n  v2 := {}\n  v3 := 2\n  if v1 {} v2 begin v3 := 1 end\n  loop again\n",
+            a, b, compare
+        );
+        let mut system = ChipSystem::init();
+        system.load_rom(rom(&source)).unwrap();
+        for _ in 0..20 {
+            system.step(false).unwrap();
+        }
+        assert_eq!(system.registers.get_gp(3) == 1, expected, "{} {} {}", a, compare, b);
+    }
+}
+
+#[test]
+fn undefined_label() {
+    assert_eq!(error_text(": main\n  jump nowhere\n"), "<input>:2:8: undefined label `nowhere`");
+}
+
+#[test]
+fn begin_without_end() {
+    assert_eq!(error_text(": main\n  if v0 == 1 begin\n    v1 := 2\n"), "<input>:2:3: `begin` without `end`");
+}
+
+#[test]
+fn self_expanding_macro() {
+    assert_eq!(error_text(":macro boom { boom }\n: main\n  boom\n"), "<input>:1:15: too many macro expansions, is a macro expanding itself?");
+}
+
+#[test]
+fn missing_main() {
+    assert_eq!(error_text(": start\n  return\n"), "<input>: program has no `main` label");
+}
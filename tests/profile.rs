@@ -0,0 +1,67 @@
+// Standard Library Modules //
+use std::collections::BTreeMap;
+
+// Local Modules Use //
+use chip8emu::profile::{CallEdge, Profiler, Routine};
+use chip8emu::ChipSystem;
+
+/// Calls a routine that calls another one twice, then loops
+const ROM: [u8; 16] = [
+    0x22, 0x04, // 0x200 CALL outer
+    0x12, 0x02, // 0x202 JP 0x202
+    0x60, 0x01, // 0x204 outer: LD V0, 1
+    0x22, 0x0C, // 0x206 CALL inner
+    0x22, 0x0C, // 0x208 CALL inner
+    0x00, 0xEE, // 0x20A RET
+    0x71, 0x01, // 0x20C inner: ADD V1, 1
+    0x00, 0xEE  // 0x20E RET
+];
+
+/// Profile the first frame of the ROM, at the default speed it runs
+/// 11 instructions: the whole call tree and two passes of the loop
+fn profile() -> Profiler {
+    let mut system = ChipSystem::init();
+    system.load_rom(ROM.to_vec()).unwrap();
+    let mut profiler = Profiler::init();
+    let frame = profiler.run_frame(&mut system).unwrap();
+    assert_eq!(frame.instructions, 11);
+    profiler
+}
+
+#[test]
+fn routines_have_exclusive_and_inclusive_totals() {
+    let profiler = profile();
+    assert_eq!(profiler.total(), 11);
+    assert_eq!(profiler.routines(), [
+        Routine {entry: 0x200, calls: 0, exclusive: 3, inclusive: 11},
+        Routine {entry: 0x204, calls: 1, exclusive: 4, inclusive: 8},
+        Routine {entry: 0x20C, calls: 2, exclusive: 4, inclusive: 4}
+    ]);
+    assert_eq!(profiler.count(0x20C), 2);
+    assert_eq!(profiler.count(0x204), 1);
+    assert_eq!(profiler.hotspots()[..2], [(0x202, 2), (0x20C, 2)]);
+}
+
+#[test]
+fn call_edges_count_calls() {
+    assert_eq!(profile().calls(), [
+        CallEdge {caller: 0x200, callee: 0x204, calls: 1, inclusive: 8},
+        CallEdge {caller: 0x204, callee: 0x20C, calls: 2, inclusive: 4}
+    ]);
+}
+
+#[test]
+fn folded_stacks_use_labels() {
+    let mut profiler = profile();
+    assert_eq!(profiler.folded(), "0x0200 3\n0x0200;0x0204 4\n0x0200;0x0204;0x020C 4\n");
+
+    let labels: BTreeMap<u16, String> = vec![(0x200, "main"), (0x204, "outer")]
+        .into_iter()
+        .map(|(addr, name)| (addr, name.to_string()))
+        .collect();
+    profiler.set_labels(&labels);
+    assert_eq!(profiler.folded(), "main 3\nmain;outer 4\nmain;outer;0x020C 4\n");
+
+    profiler.reset();
+    assert_eq!(profiler.folded(), "");
+}
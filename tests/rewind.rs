@@ -0,0 +1,110 @@
+// Local Modules Use //
+use chip8emu::asm::assemble;
+use chip8emu::debugger::Debugger;
+use chip8emu::rewind::Rewind;
+use chip8emu::ChipSystem;
+
+/// Draws a moving sprite with a timer running and random numbers drawn
+const SOURCE: &str = "
+    LD V0, 0x3C
+    LD DT, V0
+loop:
+    RND V1, 0xFF
+    LD I, sprite
+    DRW V2, V2, 5
+    ADD V2, 1
+    JP loop
+sprite:
+    DB 0xF0, 0x90, 0x90, 0x90, 0xF0
+";
+
+fn system() -> ChipSystem {
+    let mut system = ChipSystem::init();
+    system.seed_rng(3);
+    system.load_rom(assemble(SOURCE).unwrap()).unwrap();
+    system
+}
+
+/// Run `frames` frames capturing after each, returns the state of every capture
+fn run_captured(system: &mut ChipSystem, rewind: &mut Rewind, frames: usize) -> Vec<Vec<u8>> {
+    let mut states = Vec::new();
+    for _ in 0..frames {
+        system.run_frame().unwrap();
+        rewind.capture(system);
+        states.push(system.save_state());
+    }
+    states
+}
+
+#[test]
+fn rewind_restores_an_earlier_state() {
+    let mut system = system();
+    let mut rewind = Rewind::default();
+    let states = run_captured(&mut system, &mut rewind, 20);
+    assert_eq!(rewind.available(), 19);
+
+    assert_eq!(rewind.rewind(&mut system, 5), Ok(5));
+    assert_eq!(system.save_state(), states[14]);
+    assert_eq!(rewind.available(), 14);
+
+    // Changes since the last capture are undone with 0
+    system.run_frame().unwrap();
+    assert_eq!(rewind.rewind(&mut system, 0), Ok(0));
+    assert_eq!(system.save_state(), states[14]);
+
+    // Going back further than the buffer stops at the oldest snapshot
+    assert_eq!(rewind.rewind(&mut system, 100), Ok(14));
+    assert_eq!(system.save_state(), states[0]);
+}
+
+#[test]
+fn step_back_one_snapshot() {
+    let mut system = system();
+    let mut rewind = Rewind::default();
+    let states = run_captured(&mut system, &mut rewind, 3);
+    assert_eq!(rewind.step_back(&mut system), Ok(true));
+    assert_eq!(system.save_state(), states[1]);
+    assert_eq!(rewind.step_back(&mut system), Ok(true));
+    assert_eq!(system.save_state(), states[0]);
+    assert_eq!(rewind.step_back(&mut system), Ok(false));
+    assert_eq!(system.save_state(), states[0]);
+}
+
+#[test]
+fn debugger_steps_back_over_instructions() {
+    let mut debugger = Debugger::init(system());
+    debugger.enable_rewind(1024 * 1024);
+    let start = debugger.system.save_state();
+    debugger.step_into();
+    let after_one = debugger.system.save_state();
+    debugger.step_into();
+    assert!(debugger.step_back());
+    assert_eq!(debugger.system.save_state(), after_one);
+    assert!(debugger.step_back());
+    assert_eq!(debugger.system.save_state(), start);
+    assert!(!debugger.step_back());
+}
+
+#[test]
+fn budget_drops_the_oldest_snapshots() {
+    let mut system = system();
+    let state_len = system.save_state().len();
+    let budget = state_len + 200;
+    let mut rewind = Rewind::init(budget);
+    let states = run_captured(&mut system, &mut rewind, 60);
+    assert!(rewind.memory_used() <= budget, "{} bytes used", rewind.memory_used());
+    assert!(rewind.available() > 0 && rewind.available() < 59);
+
+    // The newest is always kept and the oldest kept one is still exact
+    let available = rewind.available();
+    assert_eq!(rewind.rewind(&mut system, 0), Ok(0));
+    assert_eq!(system.save_state(), states[59]);
+    assert_eq!(rewind.rewind(&mut system, available), Ok(available));
+    assert_eq!(system.save_state(), states[59 - available]);
+
+    // Shrinking the budget below a single snapshot still keeps the newest
+    rewind.set_budget(0);
+    assert_eq!(rewind.available(), 0);
+    assert_eq!(rewind.rewind(&mut system, 0), Ok(0));
+    assert_eq!(system.save_state(), states[59 - available]);
+}
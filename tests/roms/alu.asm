@@ -0,0 +1,190 @@
+; Register operations with the COSMAC VIP quirks (shift Vy, VF reset):
+; 6xkk 7xkk 8xy0 8xy1 8xy2 8xy3 8xy4 8xy5 8xy6 8xy7 8xyE
+
+    LD VE, 1            ; 6xkk
+    LD V0, 0x12
+    SE V0, 0x12
+    JP fail
+
+    LD VE, 2            ; 7xkk wraps and leaves VF alone
+    LD VF, 5
+    ADD V0, 0xF0
+    SE V0, 0x02
+    JP fail
+    SE VF, 5
+    JP fail
+
+    LD VE, 3            ; 8xy0
+    LD V1, 0x34
+    LD V0, V1
+    SE V0, 0x34
+    JP fail
+
+    LD VE, 4            ; 8xy1, VF reset
+    LD V0, 0x0F
+    LD V1, 0xF0
+    LD VF, 7
+    OR V0, V1
+    SE V0, 0xFF
+    JP fail
+    SE VF, 0
+    JP fail
+
+    LD VE, 5            ; 8xy2, VF reset
+    LD V0, 0x3C
+    LD V1, 0x0F
+    LD VF, 7
+    AND V0, V1
+    SE V0, 0x0C
+    JP fail
+    SE VF, 0
+    JP fail
+
+    LD VE, 6            ; 8xy3, VF reset
+    LD V0, 0xFF
+    LD V1, 0x0F
+    LD VF, 7
+    XOR V0, V1
+    SE V0, 0xF0
+    JP fail
+    SE VF, 0
+    JP fail
+
+    LD VE, 7            ; 8xy4 with carry
+    LD V0, 0xF0
+    LD V1, 0x20
+    ADD V0, V1
+    SE V0, 0x10
+    JP fail
+    SE VF, 1
+    JP fail
+
+    LD VE, 8            ; 8xy4 without carry
+    LD V1, 0x20
+    ADD V0, V1
+    SE V0, 0x30
+    JP fail
+    SE VF, 0
+    JP fail
+
+    LD VE, 9            ; 8xy4 into VF keeps the flag
+    LD VF, 0x80
+    LD V1, 0x90
+    ADD VF, V1
+    SE VF, 1
+    JP fail
+    LD VF, 0x10
+    ADD VF, V1
+    SE VF, 0
+    JP fail
+
+    LD VE, 10           ; 8xy5 without borrow
+    LD V0, 0x30
+    LD V1, 0x10
+    SUB V0, V1
+    SE V0, 0x20
+    JP fail
+    SE VF, 1
+    JP fail
+
+    LD VE, 11           ; 8xy5 with borrow
+    LD V0, 0x10
+    LD V1, 0x30
+    SUB V0, V1
+    SE V0, 0xE0
+    JP fail
+    SE VF, 0
+    JP fail
+
+    LD VE, 12           ; 8xy5 of equal values doesn't borrow
+    LD V0, 0x30
+    LD V1, 0x30
+    SUB V0, V1
+    SE V0, 0
+    JP fail
+    SE VF, 1
+    JP fail
+
+    LD VE, 13           ; 8xy5 into VF keeps the flag
+    LD VF, 5
+    LD V1, 1
+    SUB VF, V1
+    SE VF, 1
+    JP fail
+    LD VF, 1
+    LD V1, 5
+    SUB VF, V1
+    SE VF, 0
+    JP fail
+
+    LD VE, 14           ; 8xy6 shifts Vy into Vx
+    LD V0, 0xFF
+    LD V1, 0x05
+    SHR V0, V1
+    SE V0, 0x02
+    JP fail
+    SE VF, 1
+    JP fail
+    SE V1, 0x05
+    JP fail
+    LD V1, 0x04
+    SHR V0, V1
+    SE V0, 0x02
+    JP fail
+    SE VF, 0
+    JP fail
+
+    LD VE, 15           ; 8xy6 into VF keeps the flag
+    LD V1, 0x05
+    SHR VF, V1
+    SE VF, 1
+    JP fail
+
+    LD VE, 16           ; 8xy7 without borrow
+    LD V0, 0x10
+    LD V1, 0x30
+    SUBN V0, V1
+    SE V0, 0x20
+    JP fail
+    SE VF, 1
+    JP fail
+
+    LD VE, 17           ; 8xy7 with borrow
+    LD V0, 0x30
+    LD V1, 0x10
+    SUBN V0, V1
+    SE V0, 0xE0
+    JP fail
+    SE VF, 0
+    JP fail
+
+    LD VE, 18           ; 8xy7 into VF keeps the flag
+    LD VF, 1
+    LD V1, 5
+    SUBN VF, V1
+    SE VF, 1
+    JP fail
+
+    LD VE, 19           ; 8xyE sets VF to 1, not the bit's value
+    LD V0, 0
+    LD V1, 0x81
+    SHL V0, V1
+    SE V0, 0x02
+    JP fail
+    SE VF, 1
+    JP fail
+    LD V1, 0x41
+    SHL V0, V1
+    SE V0, 0x82
+    JP fail
+    SE VF, 0
+    JP fail
+
+    LD VE, 20           ; 8xyE into VF keeps the flag
+    LD V1, 0x81
+    SHL VF, V1
+    SE VF, 1
+    JP fail
+    JP pass
+
+    INCLUDE "result.inc"
@@ -0,0 +1,89 @@
+; Display with the COSMAC VIP quirks (sprites clip at the edges):
+; 00E0 Dxyn. Ends with a picture the harness compares to a snapshot.
+
+    LD I, row
+    LD V1, 5
+
+    LD VE, 1            ; drawing on a blank screen doesn't collide
+    LD V0, 10
+    DRW V0, V1, 1
+    SE VF, 0
+    JP fail
+
+    LD VE, 2            ; drawing it again erases it and collides
+    DRW V0, V1, 1
+    SE VF, 1
+    JP fail
+
+    LD VE, 3            ; sprites side by side don't collide
+    LD V0, 10
+    DRW V0, V1, 1
+    LD V0, 18
+    DRW V0, V1, 1
+    SE VF, 0
+    JP fail
+
+    LD VE, 4            ; overlapping sprites collide
+    LD V0, 14
+    DRW V0, V1, 1
+    SE VF, 1
+    JP fail
+
+    LD VE, 5            ; 00E0 clears the screen
+    CLS
+    LD V0, 10
+    DRW V0, V1, 1
+    SE VF, 0
+    JP fail
+
+    LD VE, 6            ; sprites are clipped at the right edge
+    CLS
+    LD V0, 60
+    DRW V0, V1, 1
+    LD V0, 0
+    DRW V0, V1, 1
+    SE VF, 0
+    JP fail
+
+    LD VE, 7            ; the starting position wraps around
+    CLS
+    LD V0, 74
+    DRW V0, V1, 1
+    LD V0, 10
+    DRW V0, V1, 1
+    SE VF, 1
+    JP fail
+
+    LD VE, 8            ; a taller sprite collides on its last row
+    CLS
+    LD V0, 20
+    LD V1, 9
+    DRW V0, V1, 1
+    LD V1, 5
+    LD I, column
+    DRW V0, V1, 5
+    SE VF, 1
+    JP fail
+
+    CLS                 ; the picture: digits 0, 7 and F
+    LD V1, 2
+    LD V0, 2
+    LD V2, 0x0
+    LD F, V2
+    DRW V0, V1, 5
+    LD V0, 8
+    LD V2, 0x7
+    LD F, V2
+    DRW V0, V1, 5
+    LD V0, 14
+    LD V2, 0xF
+    LD F, V2
+    DRW V0, V1, 5
+    JP pass
+
+    INCLUDE "result.inc"
+
+row:
+    DB 0xFF
+column:
+    DB 0x80, 0x80, 0x80, 0x80, 0x80
@@ -0,0 +1,85 @@
+; Control flow: 0nnn 00EE 1nnn 2nnn 3xkk 4xkk 5xy0 9xy0 Bnnn
+
+    LD VE, 1            ; 1nnn
+    JP jump_ok
+    JP fail
+jump_ok:
+
+    LD VE, 2            ; 2nnn and 00EE
+    LD V0, 0
+    CALL set_v0
+    SE V0, 1
+    JP fail
+
+    LD VE, 3            ; nested calls return in order
+    LD V1, 0
+    CALL outer
+    SE V1, 3
+    JP fail
+
+    LD VE, 4            ; 3xkk skips when equal
+    LD V2, 0x42
+    SE V2, 0x42
+    JP fail
+
+    LD VE, 5            ; 3xkk doesn't skip when different
+    SE V2, 0x43
+    JP se_ok
+    JP fail
+se_ok:
+
+    LD VE, 6            ; 4xkk skips when different
+    SNE V2, 0x43
+    JP fail
+    SNE V2, 0x42        ; and doesn't skip when equal
+    JP sne_ok
+    JP fail
+sne_ok:
+
+    LD VE, 7            ; 5xy0
+    LD V3, 0x42
+    SE V2, V3
+    JP fail
+    LD V3, 0
+    SE V2, V3
+    JP se_reg_ok
+    JP fail
+se_reg_ok:
+
+    LD VE, 8            ; 9xy0
+    SNE V2, V3
+    JP fail
+    LD V3, 0x42
+    SNE V2, V3
+    JP sne_reg_ok
+    JP fail
+sne_reg_ok:
+
+    LD VE, 9            ; Bnnn jumps to nnn + V0
+    LD V0, 4
+    JP V0, table
+table:
+    JP fail
+    JP fail
+    JP table_ok
+    JP fail
+table_ok:
+
+    LD VE, 10           ; 0nnn is ignored
+    SYS 0x123
+    JP pass
+
+set_v0:
+    LD V0, 1
+    RET
+
+outer:
+    CALL inner
+    ADD V1, 1
+    RET
+
+inner:
+    LD V1, 2
+    RET
+
+    INCLUDE "result.inc"
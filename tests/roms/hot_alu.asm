@@ -0,0 +1,57 @@
+; Flag-setting register operations in a loop, hot enough for the JIT
+; to compile when it is enabled. Every flag written into VF has to be
+; 1, and the results below are checked after the last pass.
+
+    LD V9, 40
+hot:
+    LD V0, 0xF0
+    LD V1, 0x20
+    ADD V0, V1
+    LD V2, VF
+    LD VF, 0x80
+    LD V1, 0x90
+    ADD VF, V1
+    LD V3, VF
+    LD V1, 0x81
+    SHL V4, V1
+    LD V5, VF
+    LD VF, 1
+    LD V1, 5
+    SUBN VF, V1
+    LD V6, VF
+    SHR VF, V1
+    LD V7, VF
+    LD VF, 5
+    LD V1, 1
+    SUB VF, V1
+    LD V8, VF
+    ADD V9, -1
+    SE V9, 0
+    JP hot
+
+    LD VE, 1            ; 8xy4
+    SE V0, 0x10
+    JP fail
+    SE V2, 1
+    JP fail
+
+    LD VE, 2            ; 8xy4 into VF
+    SE V3, 1
+    JP fail
+
+    LD VE, 3            ; 8xyE
+    SE V4, 0x02
+    JP fail
+    SE V5, 1
+    JP fail
+
+    LD VE, 4            ; 8xy7, 8xy6 and 8xy5 into VF
+    SE V6, 1
+    JP fail
+    SE V7, 1
+    JP fail
+    SE V8, 1
+    JP fail
+    JP pass
+
+    INCLUDE "result.inc"
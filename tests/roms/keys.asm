@@ -0,0 +1,34 @@
+; Keyboard: Ex9E ExA1 Fx0A. The harness holds key 5 down from the
+; start and taps key 9 while Fx0A waits.
+
+    LD V1, 5
+    LD V2, 6
+
+    LD VE, 1            ; Ex9E skips when the key in Vx is down
+    SKP V1
+    JP fail
+
+    LD VE, 2            ; ExA1 skips when the key in Vx is up
+    SKNP V2
+    JP fail
+
+    LD VE, 3            ; Ex9E doesn't skip when the key is up
+    SKP V2
+    JP skp_ok
+    JP fail
+skp_ok:
+
+    LD VE, 4            ; ExA1 doesn't skip when the key is down
+    SKNP V1
+    JP sknp_ok
+    JP fail
+sknp_ok:
+
+    LD VE, 5            ; Fx0A waits for a key and stores it
+    LD V3, 0
+    LD V3, K
+    SE V3, 9
+    JP fail
+    JP pass
+
+    INCLUDE "result.inc"
@@ -0,0 +1,107 @@
+; Memory and index register with the COSMAC VIP quirks (I moves past
+; the registers): Annn Fx1E Fx29 Fx33 Fx55 Fx65
+
+    LD VE, 1            ; Annn and Fx65 of a single register
+    LD I, bytes
+    LD V0, [I]
+    SE V0, 0x11
+    JP fail
+
+    LD VE, 2            ; Fx65 loads V0 to Vx inclusive
+    LD I, bytes
+    LD V2, [I]
+    SE V0, 0x11
+    JP fail
+    SE V1, 0x22
+    JP fail
+    SE V2, 0x33
+    JP fail
+
+    LD VE, 3            ; Fx65 leaves I past the last register
+    LD V0, [I]
+    SE V0, 0x44
+    JP fail
+
+    LD VE, 4            ; Fx55 stores V0 to Vx inclusive
+    LD I, scratch
+    LD V0, 0xA1
+    LD V1, 0xA2
+    LD V2, 0xA3
+    LD [I], V2
+    LD V0, [I]          ; I was moved past V2, on to the sentinel
+    SE V0, 0x99
+    JP fail
+    LD I, scratch
+    LD V3, [I]
+    SE V0, 0xA1
+    JP fail
+    SE V1, 0xA2
+    JP fail
+    SE V2, 0xA3
+    JP fail
+    SE V3, 0x99
+    JP fail
+
+    LD VE, 5            ; Fx1E
+    LD I, bytes
+    LD V0, 3
+    ADD I, V0
+    LD V0, [I]
+    SE V0, 0x44
+    JP fail
+
+    LD VE, 6            ; Fx33
+    LD I, scratch
+    LD V0, 237
+    LD B, V0
+    LD V2, [I]
+    SE V0, 2
+    JP fail
+    SE V1, 3
+    JP fail
+    SE V2, 7
+    JP fail
+
+    LD VE, 7            ; Fx33 of a value below 100
+    LD I, scratch
+    LD V0, 9
+    LD B, V0
+    LD V2, [I]
+    SE V0, 0
+    JP fail
+    SE V1, 0
+    JP fail
+    SE V2, 9
+    JP fail
+
+    LD VE, 8            ; Fx29 points I at the font digit in Vx
+    LD V5, 1
+    LD F, V5
+    LD V1, [I]
+    SE V0, 0x20
+    JP fail
+    SE V1, 0x60
+    JP fail
+
+    LD VE, 9            ; Fx29 of digit A
+    LD V5, 0xA
+    LD F, V5
+    LD V4, [I]
+    SE V0, 0xF0
+    JP fail
+    SE V1, 0x90
+    JP fail
+    SE V2, 0xF0
+    JP fail
+    SE V3, 0x90
+    JP fail
+    SE V4, 0x90
+    JP fail
+    JP pass
+
+    INCLUDE "result.inc"
+
+bytes:
+    DB 0x11, 0x22, 0x33, 0x44
+scratch:
+    DB 0, 0, 0, 0x99
@@ -0,0 +1,35 @@
+; Random numbers: Cxkk
+
+    LD VE, 1            ; the result is masked with kk
+    LD V2, 32
+masked:
+    RND V0, 0x0F
+    LD V1, 0xF0
+    AND V1, V0
+    SE V1, 0
+    JP fail
+    ADD V2, -1
+    SE V2, 0
+    JP masked
+
+    LD VE, 2            ; a zero mask always gives 0
+    RND V0, 0
+    SE V0, 0
+    JP fail
+
+    LD VE, 3            ; the values change
+    RND V3, 0xFF
+    LD V4, 0
+    LD V2, 16
+vary:
+    RND V0, 0xFF
+    XOR V0, V3
+    OR V4, V0
+    ADD V2, -1
+    SE V2, 0
+    JP vary
+    SE V4, 0
+    JP pass
+    JP fail
+
+    INCLUDE "result.inc"
@@ -0,0 +1,149 @@
+; SUPER-CHIP instructions and quirks: 00Cn 00FB 00FC 00FD 00FE 00FF
+; Dxy0 Fx30 Fx75 Fx85, shifts in place, no VF reset, I left alone by
+; Fx55/Fx65 and Bxnn jumping to xnn + Vx
+
+    LD VE, 1            ; 00FF, x = 100 is on screen in hires
+    HIGH
+    LD I, dot
+    LD V0, 100
+    LD V1, 40
+    DRW V0, V1, 1
+    LD V0, 36           ; where x = 100, y = 40 wraps to in lores
+    LD V1, 8
+    DRW V0, V1, 1
+    SE VF, 0
+    JP fail
+
+    LD VE, 2            ; Dxy0 draws 16x16
+    CLS
+    LD I, big
+    LD V0, 0
+    LD V1, 0
+    DRW V0, V1, 0
+    LD I, dot
+    LD V0, 15
+    LD V1, 15
+    DRW V0, V1, 1
+    SE VF, 1
+    JP fail
+    LD V0, 16
+    DRW V0, V1, 1
+    SE VF, 0
+    JP fail
+
+    LD VE, 3            ; 00FB scrolls right 4 pixels
+    CLS
+    LD V0, 10
+    LD V1, 10
+    DRW V0, V1, 1
+    SCR
+    LD V0, 14
+    DRW V0, V1, 1
+    SE VF, 1
+    JP fail
+
+    LD VE, 4            ; 00FC scrolls left 4 pixels
+    CLS
+    LD V0, 10
+    DRW V0, V1, 1
+    SCL
+    LD V0, 6
+    DRW V0, V1, 1
+    SE VF, 1
+    JP fail
+
+    LD VE, 5            ; 00Cn scrolls down n pixels
+    CLS
+    LD V0, 10
+    DRW V0, V1, 1
+    SCD 3
+    LD V1, 13
+    DRW V0, V1, 1
+    SE VF, 1
+    JP fail
+
+    LD VE, 6            ; 00FE goes back to lores, positions wrap at 64x32
+    LOW
+    LD V0, 10
+    LD V1, 10
+    DRW V0, V1, 1
+    LD V0, 74
+    LD V1, 42
+    DRW V0, V1, 1
+    SE VF, 1
+    JP fail
+
+    LD VE, 7            ; Fx30 points I at the big digit in Vx
+    LD V5, 3
+    LD HF, V5
+    LD V2, [I]
+    SE V0, 0xFF
+    JP fail
+    SE V2, 0x03
+    JP fail
+    LD V5, 7
+    LD HF, V5
+    LD V4, [I]
+    SE V4, 0x06
+    JP fail
+
+    LD VE, 8            ; Fx75 and Fx85 copy V0 to Vx inclusive
+    LD V0, 1
+    LD V1, 2
+    LD V2, 3
+    LD R, V2
+    LD V0, 0
+    LD V1, 0
+    LD V2, 0
+    LD V2, R
+    SE V0, 1
+    JP fail
+    SE V1, 2
+    JP fail
+    SE V2, 3
+    JP fail
+
+    LD VE, 9            ; 8xy6 shifts Vx in place
+    LD V0, 4
+    LD V1, 0x81
+    SHR V0, V1
+    SE V0, 2
+    JP fail
+    SE VF, 0
+    JP fail
+
+    LD VE, 10           ; 8xy1 leaves VF alone
+    LD VF, 7
+    OR V0, V1
+    SE VF, 7
+    JP fail
+
+    LD VE, 11           ; Fx65 leaves I alone
+    LD I, marker
+    LD V0, [I]
+    LD V0, 0
+    LD V0, [I]
+    SE V0, 0x5A
+    JP fail
+
+    LD VE, 12           ; Bxnn jumps to xnn + Vx
+    LD V0, 0
+    LD V4, 2
+    JP V0, table
+
+    INCLUDE "result.inc"
+
+dot:
+    DB 0x80
+marker:
+    DB 0x5A
+big:
+    DB 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+    DB 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+    DB 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+    DB 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+
+    ORG 0x400           ; the jump table's high nibble picks V4
+table:
+    JP fail
+    JP pass
@@ -0,0 +1,28 @@
+; Timers: Fx07 Fx15 Fx18. The harness checks the sound timer, a
+; program can't read it.
+
+    LD VE, 1            ; Fx15 loads Vx, Fx07 reads it back
+    LD V3, 0x30
+    LD DT, V3
+    LD V0, DT
+    LD V1, 0x30         ; at most one tick can have passed
+    SUB V1, V0
+    LD V2, 0xFE
+    AND V1, V2
+    SE V1, 0
+    JP fail
+
+    LD VE, 2            ; the delay timer counts down to 0
+    LD V3, 3
+    LD DT, V3
+wait:
+    LD V0, DT
+    SE V0, 0
+    JP wait
+
+    LD VE, 3            ; Fx18 loads Vx
+    LD V4, 0x40
+    LD ST, V4
+    JP pass
+
+    INCLUDE "result.inc"
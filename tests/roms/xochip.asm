@@ -0,0 +1,91 @@
+; XO-CHIP instructions: 00Dn 5xy2 5xy3 F000 nnnn Fn01 F002 Fx3A. The
+; harness checks the audio pattern and pitch.
+
+    LD VE, 1            ; F000 nnnn loads a 16 bit address
+    LD I, LONG far
+    LD V0, [I]
+    SE V0, 0xA5
+    JP fail
+
+    LD VE, 2            ; 5xy2 stores Vx to Vy and leaves I alone
+    LD I, scratch
+    LD V1, 0x11
+    LD V2, 0x22
+    LD V3, 0x33
+    SAVE V1, V3
+    LD V0, [I]
+    SE V0, 0x11
+    JP fail
+    LD I, scratch
+    LD V3, [I]
+    SE V2, 0x33
+    JP fail
+    SE V3, 0x99
+    JP fail
+
+    LD VE, 3            ; 5xy3 loads in either order
+    LD I, scratch
+    LOAD V6, V4
+    SE V6, 0x11
+    JP fail
+    SE V5, 0x22
+    JP fail
+    SE V4, 0x33
+    JP fail
+
+    LD VE, 4            ; skipping F000 nnnn skips all 4 bytes
+    LD V0, 0
+    SE V0, 0
+    LD I, LONG 0x1000 | fail
+    LD VE, 5            ; Fn01 draws to the selected planes only
+    CLS
+    LD I, dot
+    LD V0, 5
+    LD V1, 5
+    PLANE 2
+    DRW V0, V1, 1
+    PLANE 1
+    DRW V0, V1, 1
+    SE VF, 0
+    JP fail
+    PLANE 3             ; a row for each plane, both collide
+    LD I, dots
+    DRW V0, V1, 1
+    SE VF, 1
+    JP fail
+
+    LD VE, 6            ; 00Dn scrolls up n pixels
+    CLS
+    PLANE 1
+    LD I, dot
+    LD V0, 10
+    LD V1, 10
+    DRW V0, V1, 1
+    SCU 3
+    LD V1, 7
+    DRW V0, V1, 1
+    SE VF, 1
+    JP fail
+
+    LD VE, 7            ; F002 and Fx3A
+    LD I, pattern
+    AUDIO
+    LD V7, 0x50
+    PITCH V7
+    JP pass
+
+    INCLUDE "result.inc"
+
+dot:
+    DB 0x80
+dots:
+    DB 0x80, 0x80
+scratch:
+    DB 0, 0, 0, 0x99
+pattern:
+    DB 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF
+    DB 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF
+
+    ORG 0x1000          ; out of reach of Annn
+far:
+    DB 0xA5
@@ -0,0 +1,160 @@
+// Local Modules Use //
+use chip8emu::asm::assemble;
+use chip8emu::binary::crc32;
+use chip8emu::quirks::Quirks;
+use chip8emu::state::{StateError, MAGIC};
+use chip8emu::timing::{TimingMode, INTERPRETER_CYCLES};
+use chip8emu::ChipSystem;
+
+/// Draws random sprites with a timer running and a call on every pass
+const SOURCE: &str = "
+    LD V0, 0x3C
+    LD DT, V0
+loop:
+    RND V1, 0x3F
+    LD I, sprite
+    DRW V1, V2, 5
+    ADD V2, 1
+    CALL sub
+    JP loop
+sub:
+    RET
+sprite:
+    DB 0xF0, 0x90, 0x90, 0x90, 0xF0
+";
+
+/// A system part way through `SOURCE`
+fn running(timing: TimingMode, frames: usize) -> ChipSystem {
+    let mut system = ChipSystem::init();
+    system.set_timing(timing);
+    system.seed_rng(7);
+    system.load_rom(assemble(SOURCE).unwrap()).unwrap();
+    for _ in 0..frames {
+        system.run_frame().unwrap();
+    }
+    system
+}
+
+/// Split a state into its sections
+fn sections(state: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+    let body = &state[8..state.len() - 4];
+    let mut sections = Vec::new();
+    let mut pos = 0;
+    while pos < body.len() {
+        let mut tag = [0; 4];
+        tag.copy_from_slice(&body[pos..pos + 4]);
+        let len = u32::from_le_bytes([body[pos + 4], body[pos + 5], body[pos + 6], body[pos + 7]]) as usize;
+        sections.push((tag, body[pos + 8..pos + 8 + len].to_vec()));
+        pos += 8 + len;
+    }
+    sections
+}
+
+/// Put a state back together from the header of `state` and `sections`
+fn rebuild(state: &[u8], sections: &[([u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut out = state[..8].to_vec();
+    for (tag, payload) in sections {
+        out.extend(tag);
+        out.extend((payload.len() as u32).to_le_bytes());
+        out.extend(payload);
+    }
+    let crc = crc32(&out);
+    out.extend(crc.to_le_bytes());
+    out
+}
+
+#[test]
+fn round_trip_in_both_timings() {
+    for &timing in &[TimingMode::Instructions, TimingMode::Vip] {
+        let mut system = running(timing, 5);
+        let state = system.save_state();
+        assert_eq!(&state[..4], &MAGIC);
+
+        let mut loaded = ChipSystem::init();
+        loaded.load_state(&state).unwrap();
+        assert_eq!(loaded.timing(), timing);
+        assert_eq!(loaded.save_state(), state);
+
+        // Both carry on identically, random numbers included
+        for _ in 0..5 {
+            assert_eq!(loaded.run_frame().unwrap(), system.run_frame().unwrap());
+        }
+        assert_eq!(loaded.save_state(), system.save_state());
+    }
+}
+
+#[test]
+fn header_errors() {
+    let state = running(TimingMode::Instructions, 2).save_state();
+    let mut system = ChipSystem::init();
+
+    assert_eq!(system.load_state(b"C8XX\x01\x00\x00\x00\x00\x00\x00\x00"), Err(StateError::BadMagic));
+    assert_eq!(system.load_state(&state[..10]), Err(StateError::Truncated));
+
+    let mut newer = state.clone();
+    newer[4..6].copy_from_slice(&2u16.to_le_bytes());
+    assert_eq!(system.load_state(&newer), Err(StateError::UnsupportedVersion {major: 2, minor: 0}));
+
+    let mut corrupted = state.clone();
+    corrupted[20] ^= 0xFF;
+    assert_eq!(system.load_state(&corrupted), Err(StateError::Checksum));
+
+    // A section running past the end, with a valid checksum
+    let mut cut = state[..state.len() - 4 - 10].to_vec();
+    let crc = crc32(&cut);
+    cut.extend(crc.to_le_bytes());
+    assert_eq!(system.load_state(&cut), Err(StateError::Truncated));
+}
+
+#[test]
+fn memory_size_must_match() {
+    let mut large = ChipSystem::init_with_quirks(Quirks::octo());
+    large.load_rom(assemble(SOURCE).unwrap()).unwrap();
+    let state = large.save_state();
+    let mut system = ChipSystem::init();
+    assert_eq!(system.load_state(&state), Err(StateError::MemorySize {expected: 0x1000, found: 0x10000}));
+}
+
+#[test]
+fn vip_clock_must_stop_short_of_the_interrupt() {
+    let state = running(TimingMode::Vip, 2).save_state();
+    let mut parts = sections(&state);
+    let vip = parts.iter_mut().find(|(tag, _)| tag == b"VIP ").unwrap();
+    vip.1[..4].copy_from_slice(&INTERPRETER_CYCLES.to_le_bytes());
+    let mut system = ChipSystem::init();
+    assert_eq!(system.load_state(&rebuild(&state, &parts)), Err(StateError::InvalidSection("VIP ")));
+}
+
+#[test]
+fn unknown_sections_and_trailing_bytes_are_skipped() {
+    let mut system = running(TimingMode::Vip, 3);
+    let state = system.save_state();
+    let mut parts = sections(&state);
+    parts.insert(1, (*b"NEW ", vec![1, 2, 3]));
+    // Memory and the random source fill their whole section
+    for (_, payload) in parts.iter_mut().filter(|(tag, _)| tag != b"MEM " && tag != b"RNG ") {
+        payload.extend([0xAA, 0xBB]);
+    }
+    let extended = rebuild(&state, &parts);
+    assert_ne!(extended, state);
+
+    let mut loaded = ChipSystem::init();
+    loaded.load_state(&extended).unwrap();
+    assert_eq!(loaded.save_state(), state);
+    assert_eq!(loaded.run_frame().unwrap(), system.run_frame().unwrap());
+}
+
+#[test]
+fn failed_load_leaves_system_unchanged() {
+    let mut system = running(TimingMode::Vip, 4);
+    let before = system.save_state();
+    let mut other = running(TimingMode::Instructions, 1).save_state();
+    let last = other.len() - 1;
+    other[last] ^= 0x01;
+    assert_eq!(system.load_state(&other), Err(StateError::Checksum));
+
+    let mut parts = sections(&other[..]);
+    parts.retain(|(tag, _)| tag != b"MEM ");
+    assert_eq!(system.load_state(&rebuild(&other, &parts)), Err(StateError::MissingSection("MEM ")));
+    assert_eq!(system.save_state(), before);
+}
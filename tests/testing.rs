@@ -0,0 +1,85 @@
+// Standard Library Modules //
+use std::fs;
+use std::process;
+
+// Local Modules Use //
+use chip8emu::display::ChipDisplay;
+use chip8emu::movie::KeyEvent;
+use chip8emu::quirks::Quirks;
+use chip8emu::testing::{check_snapshot_file, run_rom, run_rom_for_frames, screen_diff, FrameHash, UPDATE_VAR};
+use chip8emu::ChipSystem;
+
+/// LD I, 0x206 / DRW V0, V0, 5 / JP 0x204, draws a `0` in the corner
+const GLYPH_ROM: [u8; 11] = [0xA2, 0x06, 0xD0, 0x05, 0x12, 0x04, 0xF0, 0x90, 0x90, 0x90, 0xF0];
+
+/// Waits for a key into V0, then draws the same `0`
+const KEY_ROM: [u8; 13] = [0xF0, 0x0A, 0xA2, 0x08, 0xD1, 0x15, 0x12, 0x06, 0xF0, 0x90, 0x90, 0x90, 0xF0];
+
+#[test]
+fn frame_hash_is_pinned() {
+    // FNV-1a of 64, 0, 32, 0 and 2048 zero pixels
+    assert_eq!(FrameHash::of(&ChipDisplay::init()), FrameHash(0xdec2_e009_e36b_7315));
+    assert_eq!(run_rom_for_frames(&GLYPH_ROM, 2, &[]), FrameHash(0xb95d_14cb_1b7f_2be3));
+    assert_eq!(FrameHash(0xb95d_14cb_1b7f_2be3).to_string(), "b95d14cb1b7f2be3");
+}
+
+#[test]
+fn inputs_reach_the_rom() {
+    let blank = FrameHash::of(&ChipDisplay::init());
+    assert_eq!(run_rom_for_frames(&KEY_ROM, 5, &[]), blank);
+
+    // Events are sorted by frame, the default quirks take the key on release
+    let inputs = [
+        KeyEvent {frame: 3, key: 0, pressed: false},
+        KeyEvent {frame: 1, key: 0, pressed: true}
+    ];
+    assert_eq!(run_rom_for_frames(&KEY_ROM, 5, &inputs), run_rom_for_frames(&GLYPH_ROM, 2, &[]));
+    let system = run_rom(&KEY_ROM, Quirks::default(), 5, &inputs);
+    assert_eq!(system.registers.get_gp(0), 0);
+    assert_eq!(system.registers.get_pc(), 0x206);
+    assert_ne!(FrameHash::of(&system.display), blank);
+}
+
+#[test]
+fn screen_diff_marks_rows_and_columns() {
+    assert_eq!(screen_diff("\n  |# |\n  | #|\n", "|# |\n| #|"), None);
+    let diff = screen_diff("|# |\n| #|\n", "|# |\n|##|\n").unwrap();
+    assert_eq!(diff, "row   1 -| #|\n        +|##|\n          ^\n");
+    let diff = screen_diff("|# |\n", "|# |\n|  |\n").unwrap();
+    assert_eq!(diff, "expected 1 lines, found 2\nrow   1 -\n        +|  |\n         ^^^^\n");
+}
+
+#[test]
+fn snapshot_files() {
+    let path = std::env::temp_dir().join(format!("chip8-snapshot-{}.txt", process::id()));
+    let mut system = ChipSystem::init();
+    system.load_rom(GLYPH_ROM.to_vec()).unwrap();
+    system.run_frame().unwrap();
+    let screen = system.display.to_ascii();
+
+    fs::write(&path, &screen).unwrap();
+    check_snapshot_file(&path, &screen);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "doesn't exist")]
+fn missing_snapshot_file_fails() {
+    if std::env::var_os(UPDATE_VAR).is_some() {
+        panic!("snapshot doesn't exist, skipped while updating");
+    }
+    let path = std::env::temp_dir().join(format!("chip8-missing-{}.txt", process::id()));
+    check_snapshot_file(&path, "|#|\n");
+}
+
+#[test]
+#[should_panic(expected = "screen doesn't match snapshot")]
+fn changed_snapshot_file_fails() {
+    let path = std::env::temp_dir().join(format!("chip8-changed-{}.txt", process::id()));
+    fs::write(&path, "|# |\n").unwrap();
+    let result = std::panic::catch_unwind(|| check_snapshot_file(&path, "| #|\n"));
+    fs::remove_file(&path).unwrap();
+    if let Err(panic) = result {
+        std::panic::resume_unwind(panic);
+    }
+}
@@ -0,0 +1,36 @@
+// Local Modules Use //
+use chip8emu::ChipSystem;
+use chip8emu::timing::{TimingMode, INTERPRETER_CYCLES};
+
+#[test]
+fn clear_spanning_two_interrupts() {
+    // 46 loads push the clear over the end of the frame, its 3118 cycles
+    // run through the next interrupt as well, then the draw idles
+    let mut rom = Vec::new();
+    for _ in 0..46 {
+        rom.extend_from_slice(&[0x60, 0x00]);
+    }
+    rom.extend_from_slice(&[0x00, 0xE0, 0xD0, 0x01]);
+
+    let mut system = ChipSystem::init();
+    system.set_timing(TimingMode::Vip);
+    system.load_rom(rom).unwrap();
+    system.registers.set_d(10);
+
+    let mut drawn = false;
+    for _ in 0..60 {
+        let step = system.step(false).unwrap();
+        let clock = system.vip_clock().unwrap();
+        assert!(clock.frame_cycle() < INTERPRETER_CYCLES, "frame cycle {}", clock.frame_cycle());
+        if step.opcode == Some(0x00E0) {
+            assert!(step.frame_done);
+            assert_eq!(system.registers.get_d(), 8);
+        }
+        if step.opcode == Some(0xD001) {
+            drawn = true;
+            break;
+        }
+    }
+    assert!(drawn);
+    assert_eq!(system.registers.get_d(), 7);
+}
@@ -0,0 +1,86 @@
+// Local Modules Use //
+use chip8emu::trace::{read_binary, OpClass, TraceFormat, Tracer};
+use chip8emu::ChipSystem;
+
+/// Writes the BCD of V0 with a long `LD I`, then draws its first digit
+/// twice so the second draw collides
+const ROM: [u8; 14] = [
+    0x60, 0x80,             // 0x200 LD V0, 0x80
+    0xF0, 0x00, 0x03, 0x00, // 0x202 LD I, long 0x300
+    0xF0, 0x33,             // 0x206 LD B, V0
+    0xD0, 0x01,             // 0x208 DRW V0, V0, 1
+    0xD0, 0x01,             // 0x20A DRW V0, V0, 1
+    0x12, 0x0C              // 0x20C JP 0x20C
+];
+
+/// Run the ROM for `steps` steps through a tracer, returns the trace,
+/// the second draw waits a step for the display
+fn trace(format: TraceFormat, steps: usize, filter: impl Fn(&mut Tracer<Vec<u8>>)) -> Vec<u8> {
+    let mut system = ChipSystem::init();
+    system.load_rom(ROM.to_vec()).unwrap();
+    let mut tracer = Tracer::init(Vec::new(), format);
+    filter(&mut tracer);
+    for _ in 0..steps {
+        tracer.step(&mut system).unwrap();
+    }
+    tracer.finish().unwrap()
+}
+
+/// Program counters of the records in a JSON Lines trace
+fn traced_pcs(trace: &[u8]) -> Vec<String> {
+    String::from_utf8(trace.to_vec()).unwrap()
+        .lines()
+        .map(|line| line.split('"').nth(5).unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn json_lines_record_every_change() {
+    let trace = String::from_utf8(trace(TraceFormat::JsonLines, 7, |_| ())).unwrap();
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!(lines, [
+        r#"{"index":0,"pc":"0x0200","opcode":"0x6080","asm":"LD V0, 0x80","class":"alu","regs":{"V0":"0x80"},"mem":{},"collision":null}"#,
+        r#"{"index":1,"pc":"0x0202","opcode":"0xf000","asm":"LD I, LONG 0x0300","class":"memory","regs":{"I":"0x0300"},"mem":{},"collision":null}"#,
+        r#"{"index":2,"pc":"0x0206","opcode":"0xf033","asm":"LD B, V0","class":"memory","regs":{},"mem":{"0x0300":"0x01","0x0301":"0x02","0x0302":"0x08"},"collision":null}"#,
+        r#"{"index":3,"pc":"0x0208","opcode":"0xd001","asm":"DRW V0, V0, 1","class":"display","regs":{},"mem":{},"collision":false}"#,
+        r#"{"index":5,"pc":"0x020a","opcode":"0xd001","asm":"DRW V0, V0, 1","class":"display","regs":{"VF":"0x01"},"mem":{},"collision":true}"#,
+        r#"{"index":6,"pc":"0x020c","opcode":"0x120c","asm":"JP 0x20C","class":"flow","regs":{},"mem":{},"collision":null}"#
+    ]);
+}
+
+#[test]
+fn read_binary_round_trips() {
+    let records = read_binary(&trace(TraceFormat::Binary, 7, |_| ())[..]).unwrap();
+    let json: Vec<String> = records.iter().map(|record| record.to_json()).collect();
+    let expected = String::from_utf8(trace(TraceFormat::JsonLines, 7, |_| ())).unwrap();
+    assert_eq!(json, expected.lines().collect::<Vec<&str>>());
+
+    let mut corrupt = trace(TraceFormat::Binary, 7, |_| ());
+    corrupt.pop();
+    assert!(read_binary(&corrupt[..]).is_err());
+    assert!(read_binary(&b"C8ST\x01\x00"[..]).is_err());
+}
+
+#[test]
+fn filters_pick_records() {
+    let ranged = trace(TraceFormat::JsonLines, 7, |tracer| {
+        tracer.add_range(0x202, 0x206);
+        tracer.add_range(0x20C, 0x20C);
+    });
+    assert_eq!(traced_pcs(&ranged), ["0x0202", "0x0206", "0x020c"]);
+
+    let classes = trace(TraceFormat::JsonLines, 7, |tracer| tracer.set_classes(&[OpClass::Alu, OpClass::Display]));
+    assert_eq!(traced_pcs(&classes), ["0x0200", "0x0208", "0x020a"]);
+
+    let both = trace(TraceFormat::JsonLines, 7, |tracer| {
+        tracer.add_range(0x200, 0x208);
+        tracer.set_classes(&[OpClass::Display]);
+    });
+    assert_eq!(traced_pcs(&both), ["0x0208"]);
+
+    let cleared = trace(TraceFormat::JsonLines, 7, |tracer| {
+        tracer.add_range(0x200, 0x200);
+        tracer.clear_filters();
+    });
+    assert_eq!(traced_pcs(&cleared).len(), 6);
+}